@@ -1,5 +1,7 @@
 use dotenv::dotenv;
 
-fn main() {
+fn main() -> anyhow::Result<()> {
     dotenv().ok();
+    // 提取pages中的frontmatter生成到generated目录
+    rsx::build::build()
 }
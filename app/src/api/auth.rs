@@ -100,15 +100,15 @@ fn check_login_state(token: &str) -> Result<User, String> {
         return Err("token is required".to_owned());
     }
     if let Some(tp) = check_token_exp(token) {
-        if let Some(user_token) = TOKEN_MAP.lock().unwrap().get(&tp.username) {
-            if user_token == token {
-                return Ok(User {
-                    id: tp.id,
-                    username: tp.username,
-                    password: "".to_owned(),
-                    email: "".to_owned(),
-                });
-            }
+        if let Some(user_token) = TOKEN_MAP.lock().unwrap().get(&tp.username)
+            && user_token == token
+        {
+            return Ok(User {
+                id: tp.id,
+                username: tp.username,
+                password: "".to_owned(),
+                email: "".to_owned(),
+            });
         }
         return Err("token is invalid".to_owned());
     }
@@ -219,24 +219,27 @@ pub async fn sign_in(req: HttpRequest, form: web::Json<LoginUser>) -> HttpRespon
                 id: user.id,
                 username: user.username,
             };
-            if let Some(token) = generate_token(&payload) {
-                if !token.is_empty() {
-                    TOKEN_MAP.lock().unwrap().insert(form.username.clone(), token.clone());
-                    return HttpResponse::Ok()
-                        .cookie(
-                            Cookie::build("token", &token)
-                                .domain("")
-                                .path("/")
-                                .secure(true)
-                                .http_only(true)
-                                .finish(),
-                        )
-                        .json(json!({
-                          "code": 0,
-                          "data": token,
-                          "msg": "success",
-                        }));
-                }
+            if let Some(token) = generate_token(&payload)
+                && !token.is_empty()
+            {
+                TOKEN_MAP
+                    .lock()
+                    .unwrap()
+                    .insert(form.username.clone(), token.clone());
+                return HttpResponse::Ok()
+                    .cookie(
+                        Cookie::build("token", &token)
+                            .domain("")
+                            .path("/")
+                            .secure(true)
+                            .http_only(true)
+                            .finish(),
+                    )
+                    .json(json!({
+                      "code": 0,
+                      "data": token,
+                      "msg": "success",
+                    }));
             }
             HttpResponse::Ok().json(json!({
                 "code": -1,
@@ -292,7 +295,8 @@ pub async fn sign_up(req: HttpRequest, form: web::Json<RegisterUser>) -> HttpRes
     match USER_MAP.lock() {
         Ok(mut user_map) => {
             if user_map.contains_key(&form.username) {
-                return HttpResponse::BadRequest().body(format!("username: {} is registered", form.username));
+                return HttpResponse::BadRequest()
+                    .body(format!("username: {} is registered", form.username));
             }
             let index = user_map.len() + 1;
             let user = User {
@@ -334,29 +338,32 @@ pub async fn sign_up(req: HttpRequest, form: web::Json<RegisterUser>) -> HttpRes
 /// assert_eq!(response.status(), StatusCode::OK);
 /// ```
 pub async fn refresh_token(req: HttpRequest) -> HttpResponse {
-    if let Some(value) = get_token_from_cookie(&req) {
-        if let Some(token) = check_token_exp(&value) {
-            let payload = TokenInput {
-                id: token.id,
-                username: token.username.clone(),
-            };
-            let new_token = generate_token(&payload).unwrap();
-            TOKEN_MAP.lock().unwrap().insert(token.username, new_token.clone());
-            return HttpResponse::Ok()
-                .cookie(
-                    Cookie::build("token", &new_token)
-                        .domain("")
-                        .path("/")
-                        .secure(true)
-                        .http_only(true)
-                        .finish(),
-                )
-                .json(json!({
-                    "code": 0,
-                    "msg": "success",
-                    "data": new_token,
-                }));
-        }
+    if let Some(value) = get_token_from_cookie(&req)
+        && let Some(token) = check_token_exp(&value)
+    {
+        let payload = TokenInput {
+            id: token.id,
+            username: token.username.clone(),
+        };
+        let new_token = generate_token(&payload).unwrap();
+        TOKEN_MAP
+            .lock()
+            .unwrap()
+            .insert(token.username, new_token.clone());
+        return HttpResponse::Ok()
+            .cookie(
+                Cookie::build("token", &new_token)
+                    .domain("")
+                    .path("/")
+                    .secure(true)
+                    .http_only(true)
+                    .finish(),
+            )
+            .json(json!({
+                "code": 0,
+                "msg": "success",
+                "data": new_token,
+            }));
     }
     HttpResponse::new(StatusCode::UNAUTHORIZED)
 }
@@ -33,7 +33,8 @@ pub struct UrlFormData {
 pub async fn post_form_url(req: HttpRequest, form: web::Form<UrlFormData>) -> HttpResponse {
     let content_type = req.headers().get("content-type").unwrap();
     if content_type != "application/x-www-form-urlencoded" {
-        return HttpResponse::BadRequest().body("content-type is not application/x-www-form-urlencoded");
+        return HttpResponse::BadRequest()
+            .body("content-type is not application/x-www-form-urlencoded");
     }
     HttpResponse::Ok().json(json!({
         "code": 0,
@@ -5,7 +5,9 @@ use actix_web::{Scope, web};
 mod auth;
 mod form;
 mod json;
+pub mod news;
 mod token;
+pub mod user;
 
 pub fn router_scope() -> Scope {
     web::scope("/api")
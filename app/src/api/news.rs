@@ -0,0 +1,19 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// 新闻详情
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewsInfo {
+    pub title: String,
+    pub url: String,
+}
+
+impl NewsInfo {
+    /// 根据页面地址加载新闻
+    pub async fn from_url(url: String) -> Result<Self> {
+        Ok(Self {
+            title: "rsx 0.1.0 发布".to_string(),
+            url,
+        })
+    }
+}
@@ -0,0 +1,36 @@
+use actix_web::HttpRequest;
+use actix_web::cookie::Cookie;
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::api::token::check_token_exp;
+
+/// 当前登录用户
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserInfo {
+    pub id: String,
+    pub username: String,
+}
+
+impl UserInfo {
+    /// 从cookie中的token读取当前登录用户
+    pub async fn from_cookies(cookies: Vec<Cookie<'static>>) -> Result<Self> {
+        let token = cookies
+            .iter()
+            .find(|cookie| cookie.name() == "token")
+            .ok_or_else(|| anyhow!("token cookie is missing"))?;
+        let payload =
+            check_token_exp(token.value()).ok_or_else(|| anyhow!("token is invalid or expired"))?;
+        Ok(Self {
+            id: payload.id,
+            username: payload.username,
+        })
+    }
+}
+
+/// 根据cookie中的token识别页面请求的登录用户，注册到`RsxServer::authenticate`
+pub fn authenticate(req: &HttpRequest) -> Option<rsx::context::User> {
+    let token = req.cookie("token")?;
+    let payload = check_token_exp(token.value())?;
+    Some(rsx::context::User::new(payload.id).with_name(payload.username))
+}
@@ -1,8 +1,23 @@
 use dotenv::dotenv;
+use rsx::server::RsxServer;
+
+mod api;
+
+/// build.rs根据pages生成的页面模块
+mod pages {
+    rsx::include_pages!();
+}
 
 #[actix_web::main]
-async fn main() -> std::io::Result<()> {
+async fn main() -> anyhow::Result<()> {
     dotenv().ok();
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
-    Ok(())
+    // `cargo run -- <dev|start|export|routes>`，没有子命令时启动服务器
+    rsx::cli::run(|config| {
+        RsxServer::new(config)
+            .scope(api::router_scope)
+            .props(pages::registry())
+            .authenticate(api::user::authenticate)
+    })
+    .await
 }
@@ -9,5 +9,9 @@ const dirname = path.dirname(fileURLToPath(import.meta.url))
 export default defineConfig({
     root: dirname,
     publicDir: path.join(dirname, 'public'),
-    plugins: [RsxPlugin(), react()]
+    plugins: [RsxPlugin(), react()],
+    // 生成dist/.vite/manifest.json，rsx服务端据此解析岛组件的构建产物
+    build: {
+        manifest: true
+    }
 })
@@ -0,0 +1,99 @@
+//! rsx页面中使用的过程宏
+//!
+//! 页面frontmatter中的`Response::json!`和`Request::all!`在代码生成时分别改写为
+//! [`response_json!`]和[`request_all!`]，通过`rsx`重新导出使用；
+//! `#[derive(Props)]`为页面props结构体实现`rsx::props::Props`
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::parse::Parser;
+use syn::punctuated::Punctuated;
+use syn::{DeriveInput, Expr, Token};
+
+/// 以JSON响应体创建`Response`，用法与`serde_json::json!`一致
+///
+/// ```ignore
+/// Response::json!({ "title": title, "list": [1, 2, 3] })
+/// ```
+#[proc_macro]
+pub fn response_json(input: TokenStream) -> TokenStream {
+    let input = proc_macro2::TokenStream::from(input);
+    if input.is_empty() {
+        return syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "Response::json! expects a JSON value, such as `Response::json!({ \"key\": value })`",
+        )
+        .to_compile_error()
+        .into();
+    }
+    quote! {
+        ::rsx::Response::from(
+            ::rsx::ServerResponse::json(::rsx::json!(#input))
+                .expect("serde_json::Value is always serializable"),
+        )
+    }
+    .into()
+}
+
+/// 并发执行多个返回`Result`的future，全部成功时返回结果元组，任一失败时返回该错误，
+/// 与`Promise.all`一致
+///
+/// ```ignore
+/// let (user, news) = Request::all!(load_user(id), load_news(url)).await?;
+/// ```
+#[proc_macro]
+pub fn request_all(input: TokenStream) -> TokenStream {
+    let exprs = match Punctuated::<Expr, Token![,]>::parse_terminated.parse(input) {
+        Ok(exprs) => exprs,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    if exprs.is_empty() {
+        return syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "Request::all! expects at least one future",
+        )
+        .to_compile_error()
+        .into();
+    }
+    let futures = exprs.iter().map(|expr| {
+        quote! {
+            async {
+                ::core::result::Result::map_err(
+                    (#expr).await,
+                    ::core::convert::Into::<::rsx::__private::anyhow::Error>::into,
+                )
+            }
+        }
+    });
+    quote! {
+        async {
+            ::rsx::__private::futures_util::try_join!(#(#futures),*)
+        }
+    }
+    .into()
+}
+
+/// 为页面props结构体实现`rsx::props::Props`，结构体还需要派生`Serialize`
+///
+/// ```ignore
+/// #[derive(Serialize, Props)]
+/// pub struct UserProps {
+///     id: u64,
+///     title: String,
+/// }
+/// ```
+#[proc_macro_derive(Props)]
+pub fn derive_props(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    if let syn::Data::Union(data) = &input.data {
+        return syn::Error::new(data.union_token.span, "Props cannot be derived for unions")
+            .to_compile_error()
+            .into();
+    }
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    quote! {
+        impl #impl_generics ::rsx::props::Props for #ident #ty_generics #where_clause {}
+    }
+    .into()
+}
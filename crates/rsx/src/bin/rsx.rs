@@ -0,0 +1,7 @@
+//! `rsx`命令行，见[`rsx::cli`]
+
+fn main() -> anyhow::Result<()> {
+    dotenv::dotenv().ok();
+    env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
+    rsx::cli::main()
+}
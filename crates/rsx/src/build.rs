@@ -1,3 +1,471 @@
-pub fn build() {
-    println!("rsx build run");
+//! 页面模块代码生成
+//!
+//! 提取每个页面的Rust frontmatter生成到`generated/`目录，并生成注册props函数的`mod.rs`，
+//! 在build.rs中调用[`build`]，在代码中通过[`include_pages!`](crate::include_pages)引入；
+//! 页面中派生了`Props`的结构体生成为`props.d.ts`中的TypeScript类型；页面导入的.rsx组件
+//! 同样生成模块，组件的props函数注册到组件ID下
+
+use crate::config::{CONFIG_FILE, Config, Profile};
+use crate::parser::{self, Block, ParseError};
+use crate::router;
+use crate::template::{self, normalize};
+use crate::typescript::TypeScope;
+use anyhow::{Context as _, Result};
+use proc_macro2::{LineColumn, TokenStream, TokenTree};
+use std::collections::{HashSet, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// 生成文件的首行标记，清理过期文件时只删除带有该标记的文件
+pub const GENERATED_HEADER: &str = "// @generated by rsx";
+
+/// build.rs传递给编译器的环境变量，值为生成的`mod.rs`路径
+pub const GENERATED_MOD_ENV: &str = "RSX_GENERATED_MOD";
+
+/// props函数名称
+pub(crate) const PROPS_FN: &str = "get_server_props";
+
+/// 动态路由页面列出预渲染参数的函数名称
+const STATIC_PATHS_FN: &str = "get_static_paths";
+
+/// 页面缓存有效期（秒）的常量名称
+const REVALIDATE_CONST: &str = "REVALIDATE";
+
+/// 页面props的TypeScript类型声明文件
+pub const TYPESCRIPT_FILE: &str = "props.d.ts";
+
+/// 页面中的宏语法糖及其改写目标，如`Response::json!`改写为`::rsx::response_json!`
+const MACRO_SUGAR: [(&str, &str, &str); 2] = [
+    ("Response", "json", "::rsx::response_json!"),
+    ("Request", "all", "::rsx::request_all!"),
+];
+
+/// 生成的页面模块
+#[derive(Debug, Clone)]
+pub struct PageModule {
+    /// 页面模板名称，如`users/[id]`；组件为组件ID，如`src_components_header`
+    pub name: String,
+    /// 是否为组件
+    pub component: bool,
+    /// 模块名称，如`page_users__id_`、`component_src_components_header`
+    pub module: String,
+    /// 页面源文件
+    pub source: PathBuf,
+    /// 生成的.rs文件
+    pub file: PathBuf,
+    /// 是否定义了`get_server_props`
+    pub has_props: bool,
+    /// 是否定义了`get_static_paths`，只对页面生效
+    pub has_static_paths: bool,
+    /// 是否声明了`REVALIDATE`，只对页面生效
+    pub has_revalidate: bool,
+    /// 派生了`Props`的结构体名称及其TypeScript类型
+    pub props_type: Option<(String, String)>,
+}
+
+/// 在build.rs中生成页面模块，项目根目录为`CARGO_MANIFEST_DIR`
+///
+/// 配置分层加载，release构建使用`[production]`，其余使用`[dev]`
+pub fn build() -> Result<()> {
+    let profile = match std::env::var("PROFILE").as_deref() {
+        Ok("release") => Profile::Production,
+        _ => Profile::Dev,
+    };
+    let config = Config::layered(std::env::var("CARGO_MANIFEST_DIR").ok(), Some(profile))?;
+    let root = Path::new(config.root());
+    let modules = generate(&config)?;
+    // 只有在build.rs中运行时才输出cargo指令
+    if std::env::var_os("OUT_DIR").is_some() {
+        println!(
+            "cargo:rerun-if-changed={}",
+            root.join(&config.pages).display()
+        );
+        for module in modules.iter().filter(|module| module.component) {
+            println!("cargo:rerun-if-changed={}", module.source.display());
+        }
+        // 不存在的文件会让cargo每次都重新运行build.rs
+        let config_file = root.join(CONFIG_FILE);
+        if config_file.is_file() {
+            println!("cargo:rerun-if-changed={}", config_file.display());
+        }
+        println!(
+            "cargo:rustc-env={GENERATED_MOD_ENV}={}",
+            root.join(&config.generated).join("mod.rs").display()
+        );
+    }
+    Ok(())
+}
+
+/// 生成全部页面模块和`mod.rs`注册表
+pub fn generate(config: &Config) -> Result<Vec<PageModule>> {
+    let root = Path::new(config.root());
+    let out = root.join(&config.generated);
+    fs::create_dir_all(&out).with_context(|| format!("failed to create {}", out.display()))?;
+    let out = out.canonicalize()?;
+
+    let mut modules = Vec::new();
+    let mut names = HashSet::new();
+    // 页面、错误页面及其导入的组件，组件按导入关系逐层展开，同一组件只生成一次
+    let pages = root.join(&config.pages);
+    let layouts = root.join(&config.layouts);
+    let mut queue: VecDeque<(String, bool, PathBuf)> = router::scan(&pages)?
+        .into_iter()
+        .map(|route| (route.name, route.file))
+        .chain(router::error_pages(&pages))
+        .map(|(name, file)| (name, false, file))
+        .collect();
+    let mut visited = HashSet::new();
+    while let Some((name, component, source)) = queue.pop_front() {
+        let file = parser::parse_file(&source)?;
+        let dir = source.parent().unwrap_or(root);
+        // 布局与组件相同，按组件ID注册props函数
+        let layouts = if component {
+            Vec::new()
+        } else {
+            template::layout_files(&pages, &layouts, &source, &file)?
+        };
+        let imports = file
+            .imports
+            .iter()
+            .filter(|import| import.is_rsx())
+            .map(|import| normalize(&dir.join(&import.source)));
+        for path in layouts.into_iter().chain(imports) {
+            if visited.insert(path.clone()) {
+                queue.push_back((template::component_id(root, &path), true, path));
+            }
+        }
+        let Some(frontmatter) = &file.frontmatter else {
+            continue;
+        };
+        let ast = check_frontmatter(frontmatter, &source)?;
+        let has_fn = |name: &str| {
+            ast.items
+                .iter()
+                .any(|item| matches!(item, syn::Item::Fn(f) if f.sig.ident == name))
+        };
+        let has_props = has_fn(PROPS_FN);
+        let has_static_paths = !component && has_fn(STATIC_PATHS_FN);
+        let has_revalidate = !component
+            && ast
+                .items
+                .iter()
+                .any(|item| matches!(item, syn::Item::Const(c) if c.ident == REVALIDATE_CONST));
+        let props_type = props_struct(&ast).and_then(|name| {
+            let ty = TypeScope::new(&ast).declare(&name)?;
+            Some((name, ty))
+        });
+        let module = if component {
+            format!("component_{name}")
+        } else {
+            module_name(&name)
+        };
+        let module = unique_name(module, &mut names);
+        let path = out.join(format!("{module}.rs"));
+        let content = desugar(&frontmatter.content);
+        write_if_changed(&path, &page_source(frontmatter, &content, &source))?;
+        modules.push(PageModule {
+            name,
+            component,
+            module,
+            source,
+            file: path,
+            has_props,
+            has_static_paths,
+            has_revalidate,
+            props_type,
+        });
+    }
+    write_if_changed(&out.join("mod.rs"), &registry_source(&modules))?;
+    write_if_changed(&out.join(TYPESCRIPT_FILE), &typescript_source(&modules))?;
+    remove_stale(&out, &modules)?;
+    Ok(modules)
+}
+
+/// 检查frontmatter语法，错误位置换算为.rsx文件中的行列号
+fn check_frontmatter(frontmatter: &Block, path: &Path) -> Result<syn::File, ParseError> {
+    syn::parse_file(&frontmatter.content).map_err(|err| {
+        let start = err.span().start();
+        let column = if start.line == 1 {
+            frontmatter.span.start.column + start.column
+        } else {
+            start.column + 1
+        };
+        ParseError {
+            message: err.to_string(),
+            line: frontmatter.source_line(start.line),
+            column,
+            file: Some(path.to_path_buf()),
+        }
+    })
+}
+
+/// 查找frontmatter中第一个派生了`Props`的结构体
+fn props_struct(ast: &syn::File) -> Option<String> {
+    ast.items.iter().find_map(|item| {
+        let syn::Item::Struct(s) = item else {
+            return None;
+        };
+        let mut derived = false;
+        for attr in s.attrs.iter().filter(|attr| attr.path().is_ident("derive")) {
+            let _ = attr.parse_nested_meta(|meta| {
+                derived |= meta
+                    .path
+                    .segments
+                    .last()
+                    .is_some_and(|seg| seg.ident == "Props");
+                Ok(())
+            });
+        }
+        derived.then(|| s.ident.to_string())
+    })
+}
+
+/// 将`Response::json!`等宏语法糖改写为rsx导出的宏，改写只发生在同一行内，不影响行号
+fn desugar(content: &str) -> String {
+    let Ok(tokens) = TokenStream::from_str(content) else {
+        return content.to_string();
+    };
+    let mut replacements = Vec::new();
+    collect_sugar(tokens, &mut replacements);
+
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(content.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let offset = |at: LineColumn| {
+        let start = line_starts[at.line - 1];
+        content[start..]
+            .char_indices()
+            .nth(at.column)
+            .map_or(content.len(), |(i, _)| start + i)
+    };
+    let mut out = content.to_string();
+    for (start, end, target) in replacements.into_iter().rev() {
+        out.replace_range(offset(start)..offset(end), target);
+    }
+    out
+}
+
+/// 收集`Type::name!`形式的宏调用位置，按出现顺序排列
+fn collect_sugar(tokens: TokenStream, out: &mut Vec<(LineColumn, LineColumn, &'static str)>) {
+    let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+    let is_punct =
+        |i: usize, c: char| matches!(tokens.get(i), Some(TokenTree::Punct(p)) if p.as_char() == c);
+    let mut i = 0;
+    while i < tokens.len() {
+        if let TokenTree::Group(group) = &tokens[i] {
+            collect_sugar(group.stream(), out);
+        }
+        let sugar = MACRO_SUGAR.iter().find(|(ty, name, _)| {
+            matches!(&tokens[i], TokenTree::Ident(ident) if ident == ty)
+                && is_punct(i + 1, ':')
+                && is_punct(i + 2, ':')
+                && matches!(tokens.get(i + 3), Some(TokenTree::Ident(ident)) if ident == name)
+                && is_punct(i + 4, '!')
+                // 跳过`rsx::Response::json!`这类带路径前缀的调用
+                && (i == 0 || !is_punct(i - 1, ':'))
+        });
+        if let Some((_, _, target)) = sugar {
+            out.push((tokens[i].span().start(), tokens[i + 4].span().end(), target));
+            i += 5;
+        } else {
+            i += 1;
+        }
+    }
+}
+
+/// 生成页面模块源码，用注释行补齐frontmatter之前的行，使行号与.rsx文件一致
+fn page_source(frontmatter: &Block, content: &str, source: &Path) -> String {
+    let mut out = format!(
+        "{GENERATED_HEADER} from {}, line numbers match the source file\n",
+        source.display()
+    );
+    for _ in 2..frontmatter.span.start.line {
+        out.push('\n');
+    }
+    out.push_str(content);
+    if !out.ends_with('\n') {
+        out.push('\n');
+    }
+    out
+}
+
+/// 生成`mod.rs`注册表源码
+fn registry_source(modules: &[PageModule]) -> String {
+    let mut out = format!("{GENERATED_HEADER}, do not edit\n");
+    for module in modules {
+        out.push_str(&format!(
+            "\n#[path = {:?}]\n#[allow(dead_code, unused_imports, unused_variables, non_snake_case, clippy::all)]\npub mod {};\n",
+            module.file.to_string_lossy(),
+            module.module
+        ));
+    }
+    out.push_str("\n/// 注册全部页面和组件的props函数\n#[allow(unused_variables)]\n");
+    out.push_str("pub fn register(props: &mut ::rsx::props::PropsRegistry) {\n");
+    for module in modules.iter().filter(|module| module.has_props) {
+        let method = if module.component {
+            "register_component"
+        } else {
+            "register"
+        };
+        out.push_str(&format!(
+            "    props.{method}({:?}, {}::{PROPS_FN});\n",
+            module.name, module.module
+        ));
+    }
+    for module in modules.iter().filter(|module| module.has_static_paths) {
+        out.push_str(&format!(
+            "    props.register_static_paths({:?}, {}::{STATIC_PATHS_FN});\n",
+            module.name, module.module
+        ));
+    }
+    for module in modules.iter().filter(|module| module.has_revalidate) {
+        out.push_str(&format!(
+            "    props.register_revalidate({:?}, ::std::time::Duration::from_secs({}::{REVALIDATE_CONST}));\n",
+            module.name, module.module
+        ));
+    }
+    out.push_str("}\n");
+    out.push_str(
+        "\n/// 创建包含全部页面props函数的注册表\n\
+         pub fn registry() -> ::rsx::props::PropsRegistry {\n    \
+         let mut props = ::rsx::props::PropsRegistry::new();\n    \
+         register(&mut props);\n    \
+         props\n}\n",
+    );
+    out
+}
+
+/// 生成`props.d.ts`，每个页面的props类型命名为页面名称加`Props`，如`UsersIdProps`
+fn typescript_source(modules: &[PageModule]) -> String {
+    let mut out = format!("{GENERATED_HEADER}, do not edit\n");
+    for module in modules.iter().filter(|module| !module.component) {
+        let Some((name, ty)) = &module.props_type else {
+            continue;
+        };
+        out.push_str(&format!(
+            "\n/** `{name}` in {} */\nexport type {} = {ty};\n",
+            module.source.display(),
+            type_name(&module.name)
+        ));
+    }
+    out
+}
+
+/// 根据页面名称生成TypeScript类型名称，如`users/[id]`生成`UsersIdProps`
+fn type_name(name: &str) -> String {
+    let mut out: String = name
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|c| c.to_ascii_uppercase().to_string() + chars.as_str())
+                .unwrap_or_default()
+        })
+        .collect();
+    out.push_str("Props");
+    out
+}
+
+/// 根据页面名称生成模块名称，如`users/[id]`生成`page_users__id_`
+fn module_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("page_{name}")
+}
+
+/// 模块名称冲突时追加序号
+fn unique_name(name: String, names: &mut HashSet<String>) -> String {
+    let mut unique = name.clone();
+    let mut index = 2;
+    while !names.insert(unique.clone()) {
+        unique = format!("{name}_{index}");
+        index += 1;
+    }
+    unique
+}
+
+/// 内容变化时才写入，避免触发不必要的重新编译
+fn write_if_changed(path: &Path, content: &str) -> Result<()> {
+    if fs::read_to_string(path).is_ok_and(|old| old == content) {
+        return Ok(());
+    }
+    fs::write(path, content).with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// 删除已不存在页面对应的生成文件
+fn remove_stale(out: &Path, modules: &[PageModule]) -> Result<()> {
+    let keep: HashSet<&Path> = modules.iter().map(|module| module.file.as_path()).collect();
+    for entry in fs::read_dir(out)? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|ext| ext != "rs")
+            || path.file_name().is_some_and(|name| name == "mod.rs")
+            || keep.contains(path.as_path())
+        {
+            continue;
+        }
+        if fs::read_to_string(&path).is_ok_and(|content| content.starts_with(GENERATED_HEADER)) {
+            fs::remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitize_module_names() {
+        let mut names = HashSet::new();
+        assert_eq!(
+            unique_name(module_name("users/[id]"), &mut names),
+            "page_users__id_"
+        );
+        assert_eq!(unique_name(module_name("index"), &mut names), "page_index");
+        assert_eq!(
+            unique_name(module_name("users/_id_"), &mut names),
+            "page_users__id__2"
+        );
+    }
+
+    #[test]
+    fn typescript_type_names() {
+        assert_eq!(type_name("users/[id]"), "UsersIdProps");
+        assert_eq!(type_name("blog/[...slug]"), "BlogSlugProps");
+        assert_eq!(type_name("index"), "IndexProps");
+    }
+
+    #[test]
+    fn keep_frontmatter_line_numbers() {
+        let file = parser::parse("---\nuse rsx::Request;\n\nfn a() {}\n---\n").unwrap();
+        let frontmatter = file.frontmatter.unwrap();
+        let source = page_source(&frontmatter, &frontmatter.content, Path::new("a.rsx"));
+        assert_eq!(source.lines().nth(3), Some("fn a() {}"));
+        assert!(source.starts_with(GENERATED_HEADER));
+    }
+
+    #[test]
+    fn desugar_page_macros() {
+        let source = "async fn a(req: Request) -> Response {\n    let (x, y) = Request::all!(f(), g()).await?;\n    Response::json!({ \"s\": \"Response::json!\", \"v\": vec![x, y] })\n}\n// Response::json!\nrsx::Response::json!({})";
+        assert_eq!(
+            desugar(source),
+            "async fn a(req: Request) -> Response {\n    let (x, y) = ::rsx::request_all!(f(), g()).await?;\n    ::rsx::response_json!({ \"s\": \"Response::json!\", \"v\": vec![x, y] })\n}\n// Response::json!\nrsx::Response::json!({})"
+        );
+    }
+
+    #[test]
+    fn map_syntax_errors_to_rsx_lines() {
+        let file = parser::parse("---\nfn a() {}\ntrait B {\n    fn b() -> u8\n}\n---\n").unwrap();
+        let Err(err) = check_frontmatter(&file.frontmatter.unwrap(), Path::new("pages/b.rsx"))
+        else {
+            panic!("expected syntax error");
+        };
+        assert_eq!(err.line, 5);
+        assert!(err.to_string().starts_with("pages/b.rsx:5:"), "{err}");
+    }
 }
@@ -0,0 +1,215 @@
+//! `rsx`命令行
+//!
+//! 子命令共用[`Config`]的参数：
+//!
+//! - `dev`：以debug构建运行应用并启动开发服务器，见[`dev`](crate::dev)
+//! - `build`：用Vite构建客户端资源，再以release构建应用
+//! - `start`：以release构建运行应用并启动服务器
+//! - `export`：以release构建运行应用，将全部页面导出为静态HTML
+//! - `routes`：输出页面和API路由表，见[`RouteTable`]
+//!
+//! 页面的props函数编译在应用中，因此`rsx`可执行文件通过`cargo run -- <子命令>`把`dev`、
+//! `start`和`export`交给应用执行；应用的main中调用[`run`]解析同样的命令行，
+//! 没有子命令时为`start`
+
+use crate::config::{Config, Profile};
+use crate::routes::RouteTable;
+use crate::server::RsxServer;
+use anyhow::{Context as _, Result, bail};
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand};
+use std::path::Path;
+use std::process;
+
+/// Vite配置文件，存在时`build`先构建客户端资源
+const VITE_CONFIGS: [&str; 4] = [
+    "vite.config.js",
+    "vite.config.ts",
+    "vite.config.mjs",
+    "vite.config.mts",
+];
+
+/// 命令行参数
+#[derive(Debug, Clone, Parser)]
+#[command(
+    name = "rsx",
+    about = "rsx framework command line",
+    args_conflicts_with_subcommands = true
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// 没有子命令时`start`使用的配置
+    #[command(flatten)]
+    pub config: Config,
+}
+
+/// 子命令
+#[derive(Debug, Clone, Subcommand)]
+pub enum Command {
+    /// Run the app in development mode
+    Dev(Config),
+    /// Build client assets with Vite and the app in release mode
+    Build(Config),
+    /// Run the app in production mode
+    Start(Config),
+    /// Export all pages as static HTML into the dist directory
+    Export(Config),
+    /// Print page and API routes with their methods and source files
+    Routes(Config),
+}
+
+impl Cli {
+    /// 解析进程的命令行，没有子命令时为`start`
+    pub fn parse_command() -> Result<Command> {
+        Self::from_matches(&Self::command().get_matches())
+    }
+
+    /// 子命令，配置按子命令的profile分层加载，见[`Config::load`]
+    pub fn from_matches(matches: &ArgMatches) -> Result<Command> {
+        let cli = Self::from_arg_matches(matches)?;
+        // 子命令的参数在子命令的matches中，没有子命令时在顶层
+        let (command, matches) = match (cli.command, matches.subcommand()) {
+            (Some(command), Some((_, matches))) => (command, matches),
+            _ => (Command::Start(cli.config), matches),
+        };
+        let config = Config::from_matches(matches, command.profile())?;
+        Ok(match command {
+            Command::Dev(_) => Command::Dev(config),
+            Command::Build(_) => Command::Build(config),
+            Command::Start(_) => Command::Start(config),
+            Command::Export(_) => Command::Export(config),
+            Command::Routes(_) => Command::Routes(config),
+        })
+    }
+}
+
+impl Command {
+    /// 子命令的配置
+    pub fn config(&self) -> &Config {
+        match self {
+            Command::Dev(config)
+            | Command::Build(config)
+            | Command::Start(config)
+            | Command::Export(config)
+            | Command::Routes(config) => config,
+        }
+    }
+
+    /// 子命令使用的配置文件profile，`dev`为`[dev]`，`routes`不使用profile，其余为`[production]`
+    pub fn profile(&self) -> Option<Profile> {
+        match self {
+            Command::Dev(_) => Some(Profile::Dev),
+            Command::Routes(_) => None,
+            Command::Build(_) | Command::Start(_) | Command::Export(_) => Some(Profile::Production),
+        }
+    }
+}
+
+/// 在应用的main中执行命令行，`server`根据配置创建服务器
+///
+/// ```ignore
+/// rsx::cli::run(|config| RsxServer::new(config).props(pages::registry())).await
+/// ```
+pub async fn run(server: impl FnOnce(Config) -> RsxServer) -> Result<()> {
+    match Cli::parse_command()? {
+        Command::Dev(config) => server(config).dev().run().await,
+        Command::Start(config) => server(config).run().await,
+        Command::Export(config) => server(config).export().await.map(drop),
+        Command::Build(config) => build(&config),
+        Command::Routes(config) => routes(&config),
+    }
+}
+
+/// `rsx`可执行文件的入口，`dev`、`start`和`export`交给应用执行
+pub fn main() -> Result<()> {
+    // 子命令及其参数原样传给应用
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match Cli::parse_command()? {
+        Command::Dev(config) => cargo(&config, &["run"], &args),
+        Command::Start(config) | Command::Export(config) => {
+            cargo(&config, &["run", "--release"], &args)
+        }
+        Command::Build(config) => build(&config),
+        Command::Routes(config) => routes(&config),
+    }
+}
+
+/// 输出路由表
+fn routes(config: &Config) -> Result<()> {
+    print!("{}", RouteTable::scan(config)?);
+    Ok(())
+}
+
+/// 有Vite配置时构建客户端资源，然后以release构建应用
+fn build(config: &Config) -> Result<()> {
+    let root = Path::new(config.root());
+    if VITE_CONFIGS.iter().any(|file| root.join(file).is_file()) {
+        exec(
+            process::Command::new("npx")
+                .args(["vite", "build"])
+                .current_dir(root),
+        )?;
+    }
+    exec(
+        process::Command::new(cargo_bin())
+            .args(["build", "--release"])
+            .current_dir(root),
+    )
+}
+
+/// 在项目根目录执行`cargo <command> -- <args>`
+fn cargo(config: &Config, command: &[&str], args: &[String]) -> Result<()> {
+    exec(
+        process::Command::new(cargo_bin())
+            .args(command)
+            .arg("--")
+            .args(args)
+            .current_dir(config.root()),
+    )
+}
+
+/// cargo可执行文件，通过cargo运行时使用同一个cargo
+fn cargo_bin() -> String {
+    std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string())
+}
+
+/// 执行命令，非0退出时返回错误
+fn exec(command: &mut process::Command) -> Result<()> {
+    log::info!("running {command:?}");
+    let status = command
+        .status()
+        .with_context(|| format!("failed to run {command:?}"))?;
+    if !status.success() {
+        bail!("{command:?} exited with {status}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Command {
+        Cli::from_matches(&Cli::command().try_get_matches_from(args).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn parse_subcommands() {
+        Cli::command().debug_assert();
+        let Command::Routes(config) = parse(&["rsx", "routes", "--pages", "app/pages"]) else {
+            panic!("expected routes");
+        };
+        assert_eq!(config.pages, "app/pages");
+        assert!(config.root.is_some());
+        assert!(matches!(
+            parse(&["rsx", "export", "--dist", "out"]),
+            Command::Export(config) if config.dist == "out"
+        ));
+        // 没有子命令时为start
+        assert!(matches!(
+            parse(&["app", "--port", "3000"]),
+            Command::Start(config) if config.port == 3000
+        ));
+        assert!(Cli::try_parse_from(["rsx", "--port", "3000", "dev"]).is_err());
+    }
+}
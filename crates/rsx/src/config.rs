@@ -1,7 +1,43 @@
-use clap::Parser;
-use std::{fs, path::Path};
+//! 应用配置
+//!
+//! [`Config::load`]按以下顺序分层合并配置，后者覆盖前者：
+//!
+//! 1. 默认值，`name`等字段取自package.json
+//! 2. 配置文件：项目根目录的`rsx.toml`，没有时为Cargo.toml的`[package.metadata.rsx]`
+//! 3. 配置文件中当前profile的表，`rsx dev`为`[dev]`，其余命令为`[production]`
+//! 4. 环境变量`RSX_<字段>`，如`RSX_PORT`
+//! 5. 命令行参数
+//!
+//! ```toml
+//! port = 3000
+//!
+//! [dev]
+//! host = "127.0.0.1"
+//!
+//! [production]
+//! shutdown_timeout = 60
+//! ```
+//!
+//! `root`决定配置文件的位置，只能通过命令行或`RSX_ROOT`设置。[`Config::sources`]
+//! 返回每个字段的来源，便于排查部署环境中的配置
 
-#[derive(Debug, Clone, Parser)]
+use anyhow::{Context as _, Result, bail};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 配置文件名
+pub const CONFIG_FILE: &str = "rsx.toml";
+
+/// 环境变量前缀
+pub const ENV_PREFIX: &str = "RSX_";
+
+#[derive(Debug, Clone, Parser, Serialize, Deserialize)]
 #[command(name = "rsx", about = "rsx framework config")]
 pub struct Config {
     /// app name and process name , default is package.json.name or Cargo.toml.name
@@ -19,6 +55,12 @@ pub struct Config {
     /// The rsx pages directory
     #[arg(long, default_value = "src/pages")]
     pub pages: String,
+    /// The named layouts directory, pages pick one with `<template layout="name">`
+    #[arg(long, default_value = "src/layouts")]
+    pub layouts: String,
+    /// The API sources directory, scanned by `rsx routes`
+    #[arg(long, default_value = "src/api")]
+    pub api: String,
     /// The public directory for static files
     #[arg(long, default_value = "public")]
     pub public: String,
@@ -31,44 +73,297 @@ pub struct Config {
     /// The output directory for the dist files
     #[arg(long, default_value = "dist")]
     pub dist: String,
+    /// The public base path of the dist files, the same as Vite's `base`, e.g. `/app/`
+    #[arg(long, default_value = "/")]
+    pub base: String,
     /// The port for the server
     #[arg(long, default_value = "8888")]
     pub port: u16,
     /// The host for the server
     #[arg(long, default_value = "0.0.0.0")]
     pub host: String,
+    /// The graceful shutdown timeout in seconds, in-flight requests are drained within it
+    #[arg(long, default_value = "30")]
+    pub shutdown_timeout: u64,
+    /// 每个字段的来源，通过[`Config::sources`]读取
+    #[doc(hidden)]
+    #[arg(skip)]
+    #[serde(skip)]
+    pub sources: BTreeMap<String, ConfigSource>,
+}
+
+/// 配置文件中的profile
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Profile {
+    /// `[dev]`，用于`rsx dev`
+    Dev,
+    /// `[production]`，用于`rsx start`、`rsx build`和`rsx export`
+    Production,
+}
+
+impl Profile {
+    /// 配置文件中的表名
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Profile::Dev => "dev",
+            Profile::Production => "production",
+        }
+    }
+}
+
+/// 配置字段的来源
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    /// 默认值
+    Default,
+    /// package.json
+    PackageJson(PathBuf),
+    /// 配置文件，`table`为所在的表，如`package.metadata.rsx`
+    File {
+        path: PathBuf,
+        table: Option<String>,
+    },
+    /// 配置文件中的profile表
+    Profile { path: PathBuf, table: String },
+    /// 环境变量
+    Env(String),
+    /// 命令行参数
+    Cli,
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigSource::Default => write!(f, "default"),
+            ConfigSource::PackageJson(path) => write!(f, "{}", path.display()),
+            ConfigSource::File { path, table: None } => write!(f, "{}", path.display()),
+            ConfigSource::File {
+                path,
+                table: Some(table),
+            }
+            | ConfigSource::Profile { path, table } => write!(f, "{} [{table}]", path.display()),
+            ConfigSource::Env(name) => write!(f, "env {name}"),
+            ConfigSource::Cli => write!(f, "command line"),
+        }
+    }
 }
 
 impl Config {
+    /// 从进程的命令行参数分层加载`[production]`配置
+    ///
+    /// 某一层无效时（如配置文件格式错误）记录错误并跳过该层，其他层照常生效，
+    /// 命令行和环境变量给出的配置不会因为配置文件的错误而丢失
     pub fn new() -> Self {
-        let mut config = Self::try_parse().unwrap_or_default();
+        // 命令行不是rsx的参数时，如在测试中，忽略命令行
+        let (config, cli) = Self::command()
+            .try_get_matches()
+            .ok()
+            .and_then(|matches| Some((Self::from_arg_matches(&matches).ok()?, cli_ids(&matches))))
+            .unwrap_or_else(|| (Self::defaults(), Vec::new()));
+        let config = config.load_lenient(&cli, Some(Profile::Production), &|name| {
+            std::env::var(name).ok()
+        });
+        log::debug!("config: {config:?}");
+        config
+    }
+
+    /// 从解析后的命令行参数分层加载配置，命令行中给出的参数优先级最高
+    pub fn from_matches(matches: &ArgMatches, profile: Option<Profile>) -> Result<Self> {
+        let config = Self::from_arg_matches(matches)?;
+        config.load(&cli_ids(matches), profile, &|name| std::env::var(name).ok())
+    }
+
+    /// 分层加载项目`root`的配置，没有命令行参数，如在build.rs中
+    pub fn layered(root: Option<String>, profile: Option<Profile>) -> Result<Self> {
+        let config = Self {
+            root,
+            ..Self::defaults()
+        };
+        let cli = match config.root {
+            Some(_) => vec!["root".to_string()],
+            None => Vec::new(),
+        };
+        config.load(&cli, profile, &|name| std::env::var(name).ok())
+    }
+
+    /// 按默认值、配置文件、profile、环境变量和命令行的顺序合并配置
+    ///
+    /// `self`为命令行解析结果，其中只有`cli`列出的字段来自命令行；`env`读取环境变量
+    pub fn load(
+        self,
+        cli: &[String],
+        profile: Option<Profile>,
+        env: &dyn Fn(&str) -> Option<String>,
+    ) -> Result<Self> {
+        self.layers(cli, profile, env, true)
+    }
+
+    /// 与[`Config::load`]相同，但跳过无效的层并记录错误
+    fn load_lenient(
+        self,
+        cli: &[String],
+        profile: Option<Profile>,
+        env: &dyn Fn(&str) -> Option<String>,
+    ) -> Self {
+        // self为命令行解析结果，未给出的字段已是默认值
+        self.clone()
+            .layers(cli, profile, env, false)
+            .unwrap_or_else(|err| {
+                log::error!("failed to load config, using command line: {err:?}");
+                self
+            })
+    }
+
+    /// 合并各层配置，`strict`为false时无效的层记录错误后跳过
+    fn layers(
+        self,
+        cli: &[String],
+        profile: Option<Profile>,
+        env: &dyn Fn(&str) -> Option<String>,
+        strict: bool,
+    ) -> Result<Self> {
+        let skip = |result: Result<()>| match result {
+            Err(err) if !strict => {
+                log::error!("invalid config skipped: {err:?}");
+                Ok(())
+            }
+            result => result,
+        };
+        let mut config = Self::defaults();
+        // root决定配置文件的位置，先于其他字段确定
+        let root_env = format!("{ENV_PREFIX}ROOT");
+        let (root, source) = match (&self.root, env(&root_env)) {
+            (Some(root), _) if cli.iter().any(|id| id == "root") => {
+                (root.clone(), ConfigSource::Cli)
+            }
+            (_, Some(root)) => (root, ConfigSource::Env(root_env)),
+            _ => (current_dir(), ConfigSource::Default),
+        };
+        config.root = Some(root);
+        config.sources.insert("root".to_string(), source);
+        let root = PathBuf::from(config.root());
+
+        let package_json = root.join("package.json");
+        skip(read_package_json(&package_json).and_then(|layer| {
+            config.apply(layer, &ConfigSource::PackageJson(package_json.clone()))
+        }))?;
+
+        match ConfigFile::find(&root) {
+            Ok(Some(file)) => {
+                skip(config.apply(file.values.clone(), &file.source()))?;
+                if let Some(profile) = profile
+                    && let Some(values) = file.profiles.get(profile.as_str())
+                {
+                    skip(config.apply(values.clone(), &file.profile_source(profile)))?;
+                }
+            }
+            Ok(None) => {}
+            Err(err) => skip(Err(err))?,
+        }
 
+        let fields = config.fields()?;
+        for (key, value) in fields.iter().filter(|(key, _)| *key != "root") {
+            let name = format!("{ENV_PREFIX}{}", key.to_uppercase());
+            let Some(raw) = env(&name) else {
+                continue;
+            };
+            // 环境变量按字段当前值的类型解析
+            let value = match value {
+                Value::Number(_) => raw
+                    .trim()
+                    .parse::<u64>()
+                    .map(Value::from)
+                    .with_context(|| format!("invalid {name}: {raw}")),
+                _ => Ok(Value::String(raw)),
+            };
+            skip(value.and_then(|value| {
+                let layer = Map::from_iter([(key.clone(), value)]);
+                config.apply(layer, &ConfigSource::Env(name))
+            }))?;
+        }
+
+        let Value::Object(values) = serde_json::to_value(&self)? else {
+            unreachable!("config serializes to an object");
+        };
+        let layer = values
+            .into_iter()
+            .filter(|(key, _)| key != "root" && cli.contains(key))
+            .collect();
+        skip(config.apply(layer, &ConfigSource::Cli))?;
+        Ok(config)
+    }
+
+    /// 每个字段的来源，按字段名排序；未经分层加载的配置均为默认值
+    pub fn sources(&self) -> Vec<(String, ConfigSource)> {
+        self.fields()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(key, _)| {
+                let source = self
+                    .sources
+                    .get(&key)
+                    .cloned()
+                    .unwrap_or(ConfigSource::Default);
+                (key, source)
+            })
+            .collect()
+    }
+
+    /// 字段名及其值
+    fn fields(&self) -> Result<Map<String, Value>> {
+        match serde_json::to_value(self)? {
+            Value::Object(fields) => Ok(fields),
+            _ => bail!("config is not an object"),
+        }
+    }
+
+    /// 用`layer`中的值覆盖对应字段，并记录来源
+    fn apply(&mut self, layer: Map<String, Value>, source: &ConfigSource) -> Result<()> {
+        if layer.is_empty() {
+            return Ok(());
+        }
+        let mut fields = self.fields()?;
+        // 失败时保留原来的配置和来源
+        let mut sources = self.sources.clone();
+        for (key, value) in layer {
+            if key == "root" || !fields.contains_key(&key) {
+                bail!("unknown config key `{key}` in {source}");
+            }
+            fields.insert(key.clone(), value);
+            sources.insert(key, source.clone());
+        }
+        *self = serde_json::from_value(Value::Object(fields))
+            .with_context(|| format!("invalid config in {source}"))?;
+        self.sources = sources;
+        Ok(())
+    }
+
+    /// 用环境变量和package.json补全命令行没有提供的字段，root默认为当前目录
+    ///
+    /// 已废弃：只读取`RSX_NAME`、`RSX_VERSION`、`RSX_DESCRIPTION`、`RSX_AUTHOR`和`RSX_ROOT`，
+    /// 不读取配置文件、profile和其他环境变量，使用[`Config::load`]或[`Config::layered`]
+    #[deprecated(note = "use `Config::load` or `Config::layered`")]
+    pub fn resolve(mut self) -> Self {
         // 处理环境变量（如果命令行参数没有提供）
-        if config.name.is_none() {
-            config.name = std::env::var("RSX_NAME").ok();
+        if self.name.is_none() {
+            self.name = std::env::var("RSX_NAME").ok();
         }
-        if config.version.is_none() {
-            config.version = std::env::var("RSX_VERSION").ok();
+        if self.version.is_none() {
+            self.version = std::env::var("RSX_VERSION").ok();
         }
-        if config.description.is_none() {
-            config.description = std::env::var("RSX_DESCRIPTION").ok();
+        if self.description.is_none() {
+            self.description = std::env::var("RSX_DESCRIPTION").ok();
         }
-        if config.author.is_none() {
-            config.author = std::env::var("RSX_AUTHOR").ok();
+        if self.author.is_none() {
+            self.author = std::env::var("RSX_AUTHOR").ok();
         }
-        if config.root.is_none() {
-            config.root = std::env::var("RSX_ROOT").ok();
+        if self.root.is_none() {
+            self.root = std::env::var("RSX_ROOT").ok();
         }
 
         // 如果root仍然没有设置，使用当前目录
-        if config.root.is_none() {
-            config.root = Some(
-                std::env::current_dir()
-                    .unwrap()
-                    .to_str()
-                    .unwrap()
-                    .to_string(),
-            );
+        if self.root.is_none() {
+            self.root = Some(current_dir());
         }
 
         // 从package.json读取默认值（如果命令行参数和环境变量都没有提供）
@@ -77,31 +372,29 @@ impl Config {
             let json_content = fs::read_to_string(package_json).unwrap_or_default();
             let json: serde_json::Value = serde_json::from_str(&json_content).unwrap_or_default();
 
-            if config.name.is_none() {
-                config.name = json.get("name").and_then(|v| v.as_str()).map(String::from);
+            if self.name.is_none() {
+                self.name = json.get("name").and_then(|v| v.as_str()).map(String::from);
             }
-            if config.version.is_none() {
-                config.version = json
+            if self.version.is_none() {
+                self.version = json
                     .get("version")
                     .and_then(|v| v.as_str())
                     .map(String::from);
             }
-            if config.description.is_none() {
-                config.description = json
+            if self.description.is_none() {
+                self.description = json
                     .get("description")
                     .and_then(|v| v.as_str())
                     .map(String::from);
             }
-            if config.author.is_none() {
-                config.author = json
+            if self.author.is_none() {
+                self.author = json
                     .get("author")
                     .and_then(|v| v.as_str())
                     .map(String::from);
             }
         }
-
-        log::debug!("config: {config:?}");
-        config
+        self
     }
 
     /// 获取root路径，确保返回Some值
@@ -113,83 +406,166 @@ impl Config {
     pub fn from_args() -> Self {
         Self::new()
     }
-}
 
-impl Default for Config {
-    fn default() -> Self {
-        // 创建一个默认配置，不解析命令行参数
-        let mut config = Self {
+    /// 未补全的默认值
+    fn defaults() -> Self {
+        Self {
             name: None,
             version: None,
             description: None,
             author: None,
             pages: "src/pages".to_string(),
+            layouts: "src/layouts".to_string(),
+            api: "src/api".to_string(),
             public: "public".to_string(),
             root: None,
             generated: "generated".to_string(),
             dist: "dist".to_string(),
+            base: "/".to_string(),
             port: 8888,
             host: "0.0.0.0".to_string(),
-        };
-
-        // 处理环境变量
-        if config.name.is_none() {
-            config.name = std::env::var("RSX_NAME").ok();
+            shutdown_timeout: 30,
+            sources: BTreeMap::new(),
         }
-        if config.version.is_none() {
-            config.version = std::env::var("RSX_VERSION").ok();
+    }
+}
+
+impl Default for Config {
+    /// 各字段的默认值，root为当前目录；不读取环境变量和配置文件，分层加载见[`Config::load`]
+    fn default() -> Self {
+        Self {
+            root: Some(current_dir()),
+            ..Self::defaults()
         }
-        if config.description.is_none() {
-            config.description = std::env::var("RSX_DESCRIPTION").ok();
+    }
+}
+
+/// 命令行中给出的参数
+fn cli_ids(matches: &ArgMatches) -> Vec<String> {
+    matches
+        .ids()
+        .map(|id| id.as_str().to_string())
+        .filter(|id| matches.value_source(id) == Some(ValueSource::CommandLine))
+        .collect()
+}
+
+/// 当前目录
+fn current_dir() -> String {
+    std::env::current_dir()
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string()
+}
+
+/// package.json中的name、version、description和author
+fn read_package_json(path: &Path) -> Result<Map<String, Value>> {
+    if !path.is_file() {
+        return Ok(Map::new());
+    }
+    let content =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let json: Value = serde_json::from_str(&content).unwrap_or_default();
+    Ok(["name", "version", "description", "author"]
+        .into_iter()
+        .filter_map(|key| {
+            let value = json.get(key)?.as_str()?;
+            Some((key.to_string(), Value::String(value.to_string())))
+        })
+        .collect())
+}
+
+/// 读取的配置文件
+#[derive(Debug)]
+struct ConfigFile {
+    path: PathBuf,
+    /// Cargo.toml中配置所在的表
+    table: Option<String>,
+    values: Map<String, Value>,
+    profiles: BTreeMap<String, Map<String, Value>>,
+}
+
+impl ConfigFile {
+    /// 查找`root`中的rsx.toml，没有时读取Cargo.toml的`[package.metadata.rsx]`
+    fn find(root: &Path) -> Result<Option<Self>> {
+        let path = root.join(CONFIG_FILE);
+        if path.is_file() {
+            let table = read_toml(&path)?;
+            return Self::parse(path, None, table).map(Some);
         }
-        if config.author.is_none() {
-            config.author = std::env::var("RSX_AUTHOR").ok();
+        let path = root.join("Cargo.toml");
+        if !path.is_file() {
+            return Ok(None);
         }
-        if config.root.is_none() {
-            config.root = std::env::var("RSX_ROOT").ok();
+        let mut manifest = read_toml(&path)?;
+        let table = ["package", "metadata", "rsx"]
+            .iter()
+            .try_fold(&mut manifest, |table, key| {
+                table.get_mut(*key).and_then(Value::as_object_mut)
+            })
+            .map(std::mem::take);
+        match table {
+            Some(table) => {
+                Self::parse(path, Some("package.metadata.rsx".to_string()), table).map(Some)
+            }
+            None => Ok(None),
         }
+    }
 
-        // 如果root仍然没有设置，使用当前目录
-        if config.root.is_none() {
-            config.root = Some(
-                std::env::current_dir()
-                    .unwrap()
-                    .to_str()
-                    .unwrap()
-                    .to_string(),
-            );
+    /// 拆分profile表
+    fn parse(path: PathBuf, table: Option<String>, mut values: Map<String, Value>) -> Result<Self> {
+        let mut file = Self {
+            path,
+            table,
+            values: Map::new(),
+            profiles: BTreeMap::new(),
+        };
+        for profile in [Profile::Dev, Profile::Production] {
+            match values.remove(profile.as_str()) {
+                Some(Value::Object(profile_values)) => {
+                    file.profiles
+                        .insert(profile.as_str().to_string(), profile_values);
+                }
+                Some(_) => bail!(
+                    "[{}] in {} must be a table",
+                    profile.as_str(),
+                    file.source()
+                ),
+                None => {}
+            }
         }
+        file.values = values;
+        Ok(file)
+    }
 
-        // 从package.json读取默认值
-        let package_json = Path::new("package.json");
-        if package_json.exists() {
-            let json_content = fs::read_to_string(package_json).unwrap_or_default();
-            let json: serde_json::Value = serde_json::from_str(&json_content).unwrap_or_default();
+    fn source(&self) -> ConfigSource {
+        ConfigSource::File {
+            path: self.path.clone(),
+            table: self.table.clone(),
+        }
+    }
 
-            if config.name.is_none() {
-                config.name = json.get("name").and_then(|v| v.as_str()).map(String::from);
-            }
-            if config.version.is_none() {
-                config.version = json
-                    .get("version")
-                    .and_then(|v| v.as_str())
-                    .map(String::from);
-            }
-            if config.description.is_none() {
-                config.description = json
-                    .get("description")
-                    .and_then(|v| v.as_str())
-                    .map(String::from);
-            }
-            if config.author.is_none() {
-                config.author = json
-                    .get("author")
-                    .and_then(|v| v.as_str())
-                    .map(String::from);
-            }
+    fn profile_source(&self, profile: Profile) -> ConfigSource {
+        let table = match &self.table {
+            Some(table) => format!("{table}.{}", profile.as_str()),
+            None => profile.as_str().to_string(),
+        };
+        ConfigSource::Profile {
+            path: self.path.clone(),
+            table,
         }
+    }
+}
 
-        config
+/// 读取TOML文件并转换为JSON对象
+fn read_toml(path: &Path) -> Result<Map<String, Value>> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let table: toml::Table =
+        toml::from_str(&content).with_context(|| format!("failed to parse {}", path.display()))?;
+    match serde_json::to_value(table)? {
+        Value::Object(values) => Ok(values),
+        _ => bail!("{} is not a table", path.display()),
     }
 }
 
@@ -233,10 +609,14 @@ mod tests {
         let config = Config::default();
         assert_eq!(config.port, 8888);
         assert_eq!(config.host, "0.0.0.0");
+        assert_eq!(config.shutdown_timeout, 30);
         assert_eq!(config.pages, "src/pages");
+        assert_eq!(config.layouts, "src/layouts");
+        assert_eq!(config.api, "src/api");
         assert_eq!(config.public, "public");
         assert_eq!(config.generated, "generated");
         assert_eq!(config.dist, "dist");
+        assert_eq!(config.base, "/");
     }
 
     #[test]
@@ -255,8 +635,13 @@ mod tests {
             std::env::set_var("RSX_PORT", "9999");
         }
 
+        // 默认配置不读取环境变量，已废弃的resolve只读取RSX_NAME等字段
         let config = Config::default();
+        assert_eq!(config.name, None);
+        assert_eq!(config.port, 8888);
 
+        #[allow(deprecated)]
+        let config = Config::defaults().resolve();
         assert_eq!(config.name, Some("Test App".to_string()));
         // 注意：port是u16类型，有默认值，环境变量不会影响它
         assert_eq!(config.port, 8888);
@@ -267,4 +652,182 @@ mod tests {
             std::env::remove_var("RSX_PORT");
         }
     }
+
+    /// 模拟的环境变量
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> + use<> {
+        let vars: Vec<(String, String)> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        move |name| {
+            vars.iter()
+                .find(|(var, _)| var == name)
+                .map(|(_, value)| value.clone())
+        }
+    }
+
+    #[test]
+    fn test_layered_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().to_string_lossy().to_string();
+        fs::write(
+            dir.path().join(CONFIG_FILE),
+            "port = 3000\nhost = \"127.0.0.1\"\ndist = \"out\"\n\n\
+             [dev]\nport = 3001\nshutdown_timeout = 1\n\n[production]\nshutdown_timeout = 60\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("package.json"),
+            r#"{"name": "demo", "version": "1.0.0"}"#,
+        )
+        .unwrap();
+        let env = env(&[
+            ("RSX_ROOT", &root),
+            ("RSX_PORT", "4000"),
+            ("RSX_HOST", "::1"),
+            ("RSX_VERSION", "2.0.0"),
+        ]);
+        let cli = Config {
+            host: "localhost".to_string(),
+            ..Config::defaults()
+        };
+        let config = cli
+            .clone()
+            .load(&["host".to_string()], Some(Profile::Dev), &env)
+            .unwrap();
+        assert_eq!(config.pages, "src/pages");
+        assert_eq!(config.name.as_deref(), Some("demo"));
+        assert_eq!(config.dist, "out");
+        assert_eq!(config.shutdown_timeout, 1);
+        assert_eq!(config.version.as_deref(), Some("2.0.0"));
+        assert_eq!(config.port, 4000);
+        assert_eq!(config.host, "localhost");
+
+        let sources: BTreeMap<String, String> = config
+            .sources()
+            .into_iter()
+            .map(|(key, source)| (key, source.to_string()))
+            .collect();
+        let file = dir.path().join(CONFIG_FILE).display().to_string();
+        assert_eq!(sources["pages"], "default");
+        assert_eq!(
+            sources["name"],
+            dir.path().join("package.json").display().to_string()
+        );
+        assert_eq!(sources["dist"], file);
+        assert_eq!(sources["shutdown_timeout"], format!("{file} [dev]"));
+        assert_eq!(sources["port"], "env RSX_PORT");
+        assert_eq!(sources["root"], "env RSX_ROOT");
+        assert_eq!(sources["host"], "command line");
+
+        let config = cli.load(&[], Some(Profile::Production), &env).unwrap();
+        assert_eq!(config.shutdown_timeout, 60);
+        assert_eq!(config.host, "::1");
+    }
+
+    #[test]
+    fn test_cargo_metadata_config() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("Cargo.toml"),
+            "[package]\nname = \"app\"\n\n[package.metadata.rsx]\npages = \"app/pages\"\n\n\
+             [package.metadata.rsx.production]\nport = 80\n",
+        )
+        .unwrap();
+        let root = dir.path().to_string_lossy().to_string();
+        let config = Config::defaults()
+            .load(&[], Some(Profile::Production), &env(&[("RSX_ROOT", &root)]))
+            .unwrap();
+        assert_eq!(config.pages, "app/pages");
+        assert_eq!(config.port, 80);
+        let sources = config.sources();
+        let (_, port) = sources.iter().find(|(key, _)| key == "port").unwrap();
+        assert_eq!(
+            port.to_string(),
+            format!(
+                "{} [package.metadata.rsx.production]",
+                dir.path().join("Cargo.toml").display()
+            )
+        );
+
+        // 未知字段和无效的值报错
+        fs::write(dir.path().join(CONFIG_FILE), "prot = 80\n").unwrap();
+        let err = Config::defaults()
+            .load(&[], None, &env(&[("RSX_ROOT", &root)]))
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("unknown config key `prot`"),
+            "{err}"
+        );
+        fs::write(dir.path().join(CONFIG_FILE), "port = 70000\n").unwrap();
+        assert!(
+            Config::defaults()
+                .load(&[], None, &env(&[("RSX_ROOT", &root)]))
+                .is_err()
+        );
+        fs::remove_file(dir.path().join(CONFIG_FILE)).unwrap();
+        assert!(
+            Config::defaults()
+                .load(&[], None, &env(&[("RSX_ROOT", &root), ("RSX_PORT", "x")]))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_command_line_overrides_layers() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join(CONFIG_FILE),
+            "port = 3000\ndist = \"out\"\n",
+        )
+        .unwrap();
+        let root = dir.path().to_string_lossy().to_string();
+        let matches = Config::command()
+            .try_get_matches_from(["rsx", "--root", &root, "--port", "5000"])
+            .unwrap();
+        let config = Config::from_matches(&matches, None).unwrap();
+        // 命令行中没有给出的参数不覆盖配置文件
+        assert_eq!(config.port, 5000);
+        assert_eq!(config.dist, "out");
+        assert_eq!(config.root.as_deref(), Some(root.as_str()));
+    }
+
+    #[test]
+    fn test_skip_invalid_layers() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join(CONFIG_FILE),
+            "port = 3000
+prot = 80
+",
+        )
+        .unwrap();
+        let root = dir.path().to_string_lossy().to_string();
+        let env = env(&[
+            ("RSX_ROOT", &root),
+            ("RSX_HOST", "::1"),
+            ("RSX_SHUTDOWN_TIMEOUT", "x"),
+        ]);
+        let cli = Config {
+            dist: "out".to_string(),
+            ..Config::defaults()
+        };
+        assert!(cli.clone().load(&["dist".to_string()], None, &env).is_err());
+
+        // 配置文件和无效的环境变量被跳过，其他环境变量和命令行仍然生效
+        let config = cli.load_lenient(&["dist".to_string()], None, &env);
+        assert_eq!(config.root.as_deref(), Some(root.as_str()));
+        assert_eq!(config.port, 8888);
+        assert_eq!(config.shutdown_timeout, 30);
+        assert_eq!(config.host, "::1");
+        assert_eq!(config.dist, "out");
+        let sources: BTreeMap<String, String> = config
+            .sources()
+            .into_iter()
+            .map(|(key, source)| (key, source.to_string()))
+            .collect();
+        assert_eq!(sources["port"], "default");
+        assert_eq!(sources["host"], "env RSX_HOST");
+        assert_eq!(sources["dist"], "command line");
+    }
 }
@@ -1 +1,269 @@
-pub struct Context;
+//! 页面props函数的请求上下文
+//!
+//! `get_server_props`可以声明第二个参数`ctx: Context`，从中读取路由参数、查询参数、
+//! 当前登录用户、请求级扩展数据以及通过[`RsxServer::state`](crate::server::RsxServer::state)
+//! 共享的应用状态
+
+use crate::params::{ParamError, Params};
+use crate::stream::Deferred;
+use actix_web::dev::{Extensions, Payload};
+use actix_web::{Error, FromRequest, HttpMessage, HttpRequest, web};
+use futures_util::future::{Ready, ready};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::cell::{Ref, RefMut};
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// 上下文错误
+#[derive(Debug, Error)]
+pub enum ContextError {
+    /// 请求未登录，在页面中返回401
+    #[error("request is not authenticated")]
+    Unauthorized,
+    /// 没有注册该类型的应用状态
+    #[error("app state `{0}` is not registered")]
+    MissingState(&'static str),
+    /// 查询参数无法解析为目标类型
+    #[error("invalid query string: {0}")]
+    InvalidQuery(String),
+}
+
+/// 当前登录用户
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct User {
+    /// 用户ID
+    pub id: String,
+    /// 用户名
+    pub name: Option<String>,
+}
+
+impl User {
+    /// 创建只有ID的用户
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            name: None,
+        }
+    }
+
+    /// 设置用户名
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+}
+
+/// 根据请求识别登录用户的函数
+type AuthenticateFn = Arc<dyn Fn(&HttpRequest) -> Option<User> + Send + Sync>;
+
+/// 由[`RsxServer::authenticate`](crate::server::RsxServer::authenticate)注册为app data的认证函数
+#[derive(Clone)]
+pub(crate) struct Authenticator(pub(crate) AuthenticateFn);
+
+/// 页面props函数的请求上下文
+#[derive(Debug, Clone)]
+pub struct Context {
+    req: HttpRequest,
+    params: Params,
+    query: Vec<(String, String)>,
+    user: Option<User>,
+}
+
+impl Context {
+    /// 根据actix请求创建上下文
+    ///
+    /// 登录用户优先读取中间件放入请求扩展的[`User`]，其次调用注册的认证函数
+    pub fn new(req: &HttpRequest) -> Self {
+        let query = web::Query::<Vec<(String, String)>>::from_query(req.query_string())
+            .map(web::Query::into_inner)
+            .unwrap_or_else(|err| {
+                log::debug!(
+                    "ignore invalid query string {:?}: {err}",
+                    req.query_string()
+                );
+                Vec::new()
+            });
+        // 先释放扩展的借用，认证函数读取cookie时会写入扩展
+        let user = req.extensions().get::<User>().cloned();
+        let user = user.or_else(|| {
+            req.app_data::<Authenticator>()
+                .and_then(|authenticate| (authenticate.0)(req))
+        });
+        Self {
+            req: req.clone(),
+            params: Params::from_request(req),
+            query,
+            user,
+        }
+    }
+
+    /// 原始actix请求
+    pub fn request(&self) -> &HttpRequest {
+        &self.req
+    }
+
+    /// 动态路由捕获的全部参数
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
+    /// 获取动态路由参数并解析为指定类型
+    pub fn param<T>(&self, name: &str) -> Result<T, ParamError>
+    where
+        T: FromStr,
+        T::Err: std::fmt::Display,
+    {
+        self.params.get_as(name)
+    }
+
+    /// 获取查询参数，同名参数返回第一个
+    pub fn query(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// 将查询字符串解析为指定类型
+    pub fn query_as<T: DeserializeOwned>(&self) -> Result<T, ContextError> {
+        web::Query::<T>::from_query(self.req.query_string())
+            .map(web::Query::into_inner)
+            .map_err(|err| ContextError::InvalidQuery(err.to_string()))
+    }
+
+    /// 当前登录用户
+    pub fn user(&self) -> Option<&User> {
+        self.user.as_ref()
+    }
+
+    /// 当前登录用户的ID，未登录时返回[`ContextError::Unauthorized`]
+    pub fn get_user_id(&self) -> Result<&str, ContextError> {
+        self.user
+            .as_ref()
+            .map(|user| user.id.as_str())
+            .ok_or(ContextError::Unauthorized)
+    }
+
+    /// 请求级扩展数据，与中间件共享
+    pub fn extensions(&self) -> Ref<'_, Extensions> {
+        self.req.extensions()
+    }
+
+    /// 可变的请求级扩展数据
+    pub fn extensions_mut(&self) -> RefMut<'_, Extensions> {
+        self.req.extensions_mut()
+    }
+
+    /// 向请求级扩展数据中插入值，同类型的旧值会被替换
+    pub fn insert<T: 'static>(&self, value: T) {
+        self.req.extensions_mut().insert(value);
+    }
+
+    /// 从请求级扩展数据中读取值
+    pub fn get<T: Clone + 'static>(&self) -> Option<T> {
+        self.req.extensions().get::<T>().cloned()
+    }
+
+    /// 延迟加载页面数据，`future`的结果写入渲染数据的`key`
+    ///
+    /// 页面有`<defer>`区块时不等待该数据，先发送用props函数返回的数据渲染的外壳，
+    /// 数据就绪后再填充`await`了`key`的区块，见[`stream`](crate::stream)；
+    /// 页面没有`<defer>`区块时等待全部延迟数据后再渲染
+    pub fn defer<F, T>(&self, key: impl Into<String>, future: F)
+    where
+        F: Future<Output = anyhow::Result<T>> + 'static,
+        T: Serialize,
+    {
+        let future = async move { Ok(serde_json::to_value(future.await?)?) };
+        self.req
+            .extensions_mut()
+            .get_or_insert_with(Deferred::default)
+            .push(key.into(), Box::pin(future));
+    }
+
+    /// 获取共享的应用状态
+    pub fn state<T: 'static>(&self) -> Result<web::Data<T>, ContextError> {
+        self.req
+            .app_data::<web::Data<T>>()
+            .cloned()
+            .ok_or(ContextError::MissingState(std::any::type_name::<T>()))
+    }
+}
+
+impl FromRequest for Context {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Ok(Context::new(req)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn read_params_and_query() {
+        let req = TestRequest::default()
+            .uri("/users/42?tab=posts&page=2")
+            .param("id", "42")
+            .to_http_request();
+        let ctx = Context::new(&req);
+        assert_eq!(ctx.param::<u64>("id").unwrap(), 42);
+        assert_eq!(ctx.query("tab"), Some("posts"));
+        assert_eq!(ctx.query("missing"), None);
+
+        #[derive(Deserialize)]
+        struct Paging {
+            page: u32,
+        }
+        assert_eq!(ctx.query_as::<Paging>().unwrap().page, 2);
+    }
+
+    #[test]
+    fn resolve_user_from_extensions_or_authenticator() {
+        let req = TestRequest::default().to_http_request();
+        assert!(matches!(
+            Context::new(&req).get_user_id(),
+            Err(ContextError::Unauthorized)
+        ));
+
+        let authenticator = Authenticator(Arc::new(|req| {
+            req.headers().get("x-user").map(|_| User::new("1"))
+        }));
+        let req = TestRequest::default()
+            .app_data(authenticator.clone())
+            .insert_header(("x-user", "1"))
+            .to_http_request();
+        assert_eq!(Context::new(&req).get_user_id().unwrap(), "1");
+
+        let req = TestRequest::default()
+            .app_data(authenticator)
+            .to_http_request();
+        req.extensions_mut()
+            .insert(User::new("2").with_name("michael"));
+        let ctx = Context::new(&req);
+        assert_eq!(ctx.user().unwrap().name.as_deref(), Some("michael"));
+    }
+
+    #[test]
+    fn share_extensions_and_state() {
+        let req = TestRequest::default()
+            .app_data(web::Data::new(7u32))
+            .to_http_request();
+        let ctx = Context::new(&req);
+        ctx.insert("locale");
+        assert_eq!(ctx.get::<&str>(), Some("locale"));
+        assert_eq!(req.extensions().get::<&str>(), Some(&"locale"));
+        assert_eq!(**ctx.state::<u32>().unwrap(), 7);
+        assert!(matches!(
+            ctx.state::<String>(),
+            Err(ContextError::MissingState(_))
+        ));
+    }
+}
@@ -0,0 +1,294 @@
+//! 开发服务器
+//!
+//! `rsx dev`时监听`Config.pages`、`Config.layouts`、`src/components`和`Config.public`：
+//!
+//! - 修改.rsx文件后只重新编译受影响的页面并替换模板引擎，不需要重新cargo构建；
+//!   页面模板不变、只有未加作用域的样式变化时推送新样式，否则通知这些页面刷新
+//! - 修改public中的CSS时替换页面中对应的`<link>`，其他文件通知全部页面刷新
+//!
+//! 页面通过[`client_script`]插入的脚本连接[`DEV_WS_PATH`]接收[`DevMessage`]，
+//! 该脚本只在开发模式下插入。props函数编译在应用中，修改Rust代码仍需重启；
+//! 路由表在启动时注册，新增或删除页面时输出警告，同样需要重启
+
+use crate::config::Config;
+use crate::router::Router;
+use crate::template::style::STYLE_ID;
+use actix_web::{HttpRequest, HttpResponse, web};
+use actix_ws::Message;
+use anyhow::{Context as _, Result};
+use futures::channel::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use futures_util::{StreamExt, stream};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// 客户端脚本连接的WebSocket路径
+pub const DEV_WS_PATH: &str = "/__rsx/ws";
+
+/// 合并连续文件事件的等待时间，编辑器保存时通常会产生多个事件
+const DEBOUNCE: Duration = Duration::from_millis(50);
+
+/// 推送给页面的消息，序列化为`{"type": "reload", ...}`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DevMessage {
+    /// 刷新页面，`pages`为空时刷新全部页面
+    Reload {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pages: Option<Vec<String>>,
+    },
+    /// 重新加载public中路径为`path`的样式表
+    Css { path: String },
+    /// 替换页面的合并样式
+    Style { page: String, css: String },
+}
+
+/// 开发模式的客户端脚本，`page`为页面名称
+pub fn client_script(page: &str) -> String {
+    format!(
+        "<script id=\"__rsx_dev__\" data-page=\"{}\">(function(){{\
+         var page=document.currentScript.dataset.page,opened=false;\
+         function connect(){{var ws=new WebSocket((location.protocol==='https:'?'wss://':'ws://')+location.host+'{DEV_WS_PATH}');\
+         ws.onopen=function(){{if(opened)location.reload();opened=true}};\
+         ws.onmessage=function(e){{var m=JSON.parse(e.data);\
+         if(m.type==='reload'){{if(!m.pages||m.pages.indexOf(page)>=0)location.reload()}}\
+         else if(m.type==='css'){{document.querySelectorAll('link[rel=stylesheet]').forEach(function(l){{\
+         var u=new URL(l.href);if(u.pathname===m.path){{u.searchParams.set('t',Date.now());l.href=u.href}}}})}}\
+         else if(m.type==='style'&&m.page===page){{var s=document.getElementById('{STYLE_ID}');\
+         if(!s){{s=document.createElement('style');s.id='{STYLE_ID}';document.head.appendChild(s)}}s.textContent=m.css}}}};\
+         ws.onclose=function(){{setTimeout(connect,1000)}}}}\
+         connect()}})()</script>",
+        handlebars::html_escape(page)
+    )
+}
+
+/// 开发服务器，监听文件变更并向已连接的页面推送消息
+pub struct DevServer {
+    router: Arc<Router>,
+    /// 需要重新编译页面的.rsx所在目录
+    sources: Vec<PathBuf>,
+    public: PathBuf,
+    clients: Mutex<Vec<UnboundedSender<String>>>,
+    watcher: Mutex<Option<RecommendedWatcher>>,
+}
+
+impl DevServer {
+    /// 监听配置中的目录，`router`为以[`Router::with_dev_client`]创建的路由
+    pub fn new(config: &Config, router: Arc<Router>) -> Self {
+        let root = Path::new(config.root());
+        Self {
+            router,
+            sources: vec![
+                root.join(&config.pages),
+                root.join(&config.layouts),
+                root.join("src/components"),
+            ],
+            public: root.join(&config.public),
+            clients: Mutex::new(Vec::new()),
+            watcher: Mutex::new(None),
+        }
+    }
+
+    /// 开始监听文件变更，不存在的目录跳过
+    pub fn watch(self: &Arc<Self>) -> Result<()> {
+        let (tx, rx) = std::sync::mpsc::channel::<notify::Result<Event>>();
+        let mut watcher = notify::recommended_watcher(tx)?;
+        for dir in self.sources.iter().chain([&self.public]) {
+            if dir.is_dir() {
+                watcher
+                    .watch(dir, RecursiveMode::Recursive)
+                    .with_context(|| format!("failed to watch {}", dir.display()))?;
+            }
+        }
+        *self.watcher.lock().unwrap_or_else(|e| e.into_inner()) = Some(watcher);
+
+        // 服务器停止时DevServer被释放，watcher随之关闭通道，线程退出
+        let dev = Arc::downgrade(self);
+        std::thread::spawn(move || {
+            while let Ok(event) = rx.recv() {
+                let mut paths = Vec::new();
+                let mut next = Ok(event);
+                loop {
+                    match next {
+                        Ok(Ok(event)) if !event.kind.is_access() => {
+                            for path in event.paths {
+                                if !paths.contains(&path) {
+                                    paths.push(path);
+                                }
+                            }
+                        }
+                        Ok(Err(err)) => log::warn!("watch error: {err}"),
+                        _ => {}
+                    }
+                    next = rx.recv_timeout(DEBOUNCE).map_err(drop);
+                    if next.is_err() {
+                        break;
+                    }
+                }
+                let Some(dev) = dev.upgrade() else {
+                    break;
+                };
+                for message in dev.handle_changes(&paths) {
+                    dev.broadcast(&message);
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// 处理变更的文件，返回需要推送的消息
+    ///
+    /// 编译失败时保留原来的页面并输出错误，修正后再次保存即可
+    pub fn handle_changes(&self, paths: &[PathBuf]) -> Vec<DevMessage> {
+        let mut messages = Vec::new();
+        let templates: Vec<PathBuf> = paths
+            .iter()
+            .filter(|path| path.extension().is_some_and(|ext| ext == "rsx"))
+            .filter(|path| self.sources.iter().any(|dir| path.starts_with(dir)))
+            .cloned()
+            .collect();
+        if !templates.is_empty() {
+            let before = self.router.engine();
+            match self.router.reload(&templates) {
+                Ok(pages) => {
+                    let after = self.router.engine();
+                    let mut reload = Vec::new();
+                    for page in pages {
+                        if after.same_markup(&before, &page) {
+                            let css = after.page_styles(&page).to_string();
+                            messages.push(DevMessage::Style { page, css });
+                        } else {
+                            reload.push(page);
+                        }
+                    }
+                    if !reload.is_empty() {
+                        log::info!("pages recompiled: {}", reload.join(", "));
+                        messages.push(DevMessage::Reload {
+                            pages: Some(reload),
+                        });
+                    }
+                }
+                Err(err) => log::error!("failed to recompile pages: {err:?}"),
+            }
+        }
+        let mut reload_all = false;
+        for path in paths {
+            let Ok(rel) = path.strip_prefix(&self.public) else {
+                continue;
+            };
+            if path.extension().is_some_and(|ext| ext == "css") {
+                let path = format!("/{}", rel.to_string_lossy().replace('\\', "/"));
+                messages.push(DevMessage::Css { path });
+            } else {
+                reload_all = true;
+            }
+        }
+        if reload_all {
+            messages.push(DevMessage::Reload { pages: None });
+        }
+        messages
+    }
+
+    /// 向全部已连接的页面推送消息，移除已断开的连接
+    pub fn broadcast(&self, message: &DevMessage) {
+        let Ok(text) = serde_json::to_string(message) else {
+            return;
+        };
+        let mut clients = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+        clients.retain(|client| client.unbounded_send(text.clone()).is_ok());
+    }
+
+    /// 订阅推送的消息
+    fn subscribe(&self) -> UnboundedReceiver<String> {
+        let (tx, rx) = mpsc::unbounded();
+        self.clients
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(tx);
+        rx
+    }
+
+    /// 注册客户端脚本连接的WebSocket路由
+    pub fn configure(self: &Arc<Self>, cfg: &mut web::ServiceConfig) {
+        cfg.app_data(web::Data::from(self.clone()))
+            .route(DEV_WS_PATH, web::get().to(connect));
+    }
+}
+
+/// WebSocket连接上的事件
+enum WsEvent {
+    Push(String),
+    Client(Message),
+    Closed,
+}
+
+/// 接受客户端脚本的连接，转发推送的消息
+async fn connect(
+    req: HttpRequest,
+    body: web::Payload,
+    dev: web::Data<DevServer>,
+) -> actix_web::Result<HttpResponse> {
+    let (response, mut session, messages) = actix_ws::handle(&req, body)?;
+    let pushes = dev.subscribe().map(WsEvent::Push);
+    let received = messages
+        .take_while(|message| futures_util::future::ready(message.is_ok()))
+        .filter_map(|message| futures_util::future::ready(message.ok().map(WsEvent::Client)))
+        .chain(stream::iter([WsEvent::Closed]));
+    actix_rt::spawn(async move {
+        let mut events = stream::select(pushes, received);
+        while let Some(event) = events.next().await {
+            let sent = match event {
+                WsEvent::Push(text) => session.text(text).await,
+                WsEvent::Client(Message::Ping(bytes)) => session.pong(&bytes).await,
+                WsEvent::Client(Message::Close(reason)) => {
+                    let _ = session.close(reason).await;
+                    return;
+                }
+                WsEvent::Client(_) => Ok(()),
+                WsEvent::Closed => break,
+            };
+            if sent.is_err() {
+                return;
+            }
+        }
+        let _ = session.close(None).await;
+    });
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serialize_messages() {
+        let reload = DevMessage::Reload {
+            pages: Some(vec!["index".to_string()]),
+        };
+        assert_eq!(
+            serde_json::to_string(&reload).unwrap(),
+            r#"{"type":"reload","pages":["index"]}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&DevMessage::Reload { pages: None }).unwrap(),
+            r#"{"type":"reload"}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&DevMessage::Css {
+                path: "/app.css".to_string()
+            })
+            .unwrap(),
+            r#"{"type":"css","path":"/app.css"}"#
+        );
+    }
+
+    #[test]
+    fn client_script_escapes_page() {
+        let script = client_script("users/[id]\"");
+        assert!(script.starts_with("<script id=\"__rsx_dev__\" data-page=\"users/[id]&quot;\">"));
+        assert!(script.contains(DEV_WS_PATH));
+        assert!(script.ends_with("</script>"));
+    }
+}
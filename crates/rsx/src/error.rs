@@ -0,0 +1,312 @@
+//! 页面渲染错误
+//!
+//! props函数返回错误或模板渲染失败时返回500：
+//!
+//! - 开发模式下返回错误浮层页面，显示`anyhow`错误链、出错的.rsx源码行和请求信息，
+//!   页面同样连接开发服务器，修正后自动刷新
+//! - 生产模式下返回不含错误细节的通用页面，页面和响应头`x-rsx-error-id`中的错误ID
+//!   与日志中的相同，便于根据用户反馈查找日志
+
+use crate::build::PROPS_FN;
+use crate::parser;
+use actix_web::HttpRequest;
+use handlebars::{RenderError, html_escape};
+use std::backtrace::BacktraceStatus;
+use std::fmt::Write;
+use std::hash::{BuildHasher, RandomState};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 携带错误ID的响应头
+pub const ERROR_ID_HEADER: &str = "x-rsx-error-id";
+
+/// 源码摘录中出错行前后显示的行数
+const CONTEXT_LINES: usize = 3;
+
+/// 出错的阶段，用于在源码中定位出错的行
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// 执行页面或组件的props函数
+    Props,
+    /// 渲染模板
+    Render,
+}
+
+/// .rsx源码中出错位置附近的行
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceExcerpt {
+    /// 源文件，相对项目根目录
+    pub file: String,
+    /// 出错的行号，从1开始
+    pub line: usize,
+    /// 出错行及前后各几行，为行号和内容
+    pub lines: Vec<(usize, String)>,
+}
+
+impl SourceExcerpt {
+    /// 在源码中定位出错的行
+    ///
+    /// 模板渲染错误按Handlebars报告的行号定位：页面模板由`<template>`的内容生成并保持
+    /// 原来的换行，内联的组件位于使用它的行。其他错误查找错误信息中反引号引用的片段，
+    /// 如表达式解析错误中的表达式；找不到时props阶段定位到`get_server_props`的定义，
+    /// 渲染阶段定位到`<template>`
+    pub fn locate(file: &str, source: &str, err: &anyhow::Error, stage: Stage) -> Option<Self> {
+        let rendered = match stage {
+            Stage::Render => template_line(source, err),
+            Stage::Props => None,
+        };
+        let line = rendered
+            .or_else(|| {
+                err.chain()
+                    .flat_map(|cause| quoted(&cause.to_string()))
+                    .find_map(|snippet| find_line(source, &snippet))
+            })
+            .or_else(|| match stage {
+                Stage::Props => find_line(source, &format!("fn {PROPS_FN}")),
+                Stage::Render => find_line(source, "<template"),
+            })?;
+        let lines = source
+            .lines()
+            .enumerate()
+            .map(|(index, text)| (index + 1, text.to_string()))
+            .skip(line.saturating_sub(CONTEXT_LINES + 1))
+            .take(CONTEXT_LINES * 2 + 1)
+            .collect();
+        Some(Self {
+            file: file.to_string(),
+            line,
+            lines,
+        })
+    }
+
+    /// 读取源文件并定位出错的行，`root`为项目根目录
+    pub fn read(root: &Path, path: &Path, err: &anyhow::Error, stage: Stage) -> Option<Self> {
+        let source = std::fs::read_to_string(path).ok()?;
+        let file = path.strip_prefix(root).unwrap_or(path);
+        let file = file.to_string_lossy().replace('\\', "/");
+        Self::locate(&file, &source, err, stage)
+    }
+}
+
+/// Handlebars渲染错误在.rsx源码中的行号
+///
+/// 生成的模板的第1行对应`<template>`内容开始的行，`<defer>`区块单独编译，
+/// 行号与源码不对应
+fn template_line(source: &str, err: &anyhow::Error) -> Option<usize> {
+    let err = err
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<RenderError>())?;
+    if err
+        .template_name
+        .as_deref()
+        .is_some_and(|name| name.contains('#'))
+    {
+        return None;
+    }
+    let line = err.line_no?;
+    let start = parser::parse(source).ok()?.template?.span.start;
+    Some(start.line + line - 1)
+}
+
+/// 错误信息中反引号引用的片段，忽略不含字母数字的片段
+fn quoted(message: &str) -> Vec<String> {
+    message
+        .split('`')
+        .skip(1)
+        .step_by(2)
+        .map(str::trim)
+        .filter(|snippet| snippet.chars().any(char::is_alphanumeric))
+        .map(str::to_string)
+        .collect()
+}
+
+/// 包含`needle`的第一行的行号
+fn find_line(source: &str, needle: &str) -> Option<usize> {
+    source
+        .lines()
+        .position(|line| line.contains(needle))
+        .map(|index| index + 1)
+}
+
+/// 生成错误ID，16位十六进制
+pub fn error_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_nanos());
+    let hash = RandomState::new().hash_one((nanos, COUNTER.fetch_add(1, Ordering::Relaxed)));
+    format!("{hash:016x}")
+}
+
+/// 生产模式的通用错误页面
+pub fn generic_page(id: &str) -> String {
+    format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>500 Internal Server Error</title></head>\
+         <body style=\"font-family:system-ui,sans-serif;text-align:center;padding:4rem 1rem\">\
+         <h1>500</h1><p>An unexpected error occurred.</p>\
+         <p style=\"color:#888\">Error ID: <code>{}</code></p></body></html>",
+        html_escape(id)
+    )
+}
+
+/// 开发模式的错误浮层页面，`name`为出错的页面或组件
+pub fn overlay(
+    name: &str,
+    err: &anyhow::Error,
+    source: Option<&SourceExcerpt>,
+    req: &HttpRequest,
+) -> String {
+    let mut html = format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>Error: {name}</title><style>\
+         body{{margin:0;background:rgba(0,0,0,.85);color:#eee;font:14px/1.5 ui-monospace,monospace}}\
+         main{{max-width:960px;margin:2rem auto;padding:1.5rem 2rem;background:#1e1e1e;border-top:4px solid #e5484d}}\
+         h1{{font-size:1.25rem;color:#ff6369;white-space:pre-wrap}}h2{{font-size:1rem;color:#aaa;margin-top:2rem}}\
+         pre{{background:#111;padding:1rem;overflow:auto}}.line{{display:block}}\
+         .hl{{background:#5c1a1d}}.no{{display:inline-block;width:3em;color:#666;user-select:none}}\
+         table{{border-collapse:collapse}}td{{padding:.1rem 1rem .1rem 0;vertical-align:top}}\
+         td:first-child{{color:#aaa}}</style></head><body><main>\
+         <p style=\"color:#aaa\">Error in <b>{name}</b></p><h1>{message}</h1>",
+        name = html_escape(name),
+        message = html_escape(&err.to_string()),
+    );
+    let causes: Vec<String> = err.chain().skip(1).map(ToString::to_string).collect();
+    if !causes.is_empty() {
+        html.push_str("<h2>Caused by</h2><ol>");
+        for cause in causes {
+            let _ = write!(html, "<li>{}</li>", html_escape(&cause));
+        }
+        html.push_str("</ol>");
+    }
+    if let Some(source) = source {
+        let _ = write!(
+            html,
+            "<h2>{}:{}</h2><pre>",
+            html_escape(&source.file),
+            source.line
+        );
+        for (number, text) in &source.lines {
+            let class = if *number == source.line {
+                "line hl"
+            } else {
+                "line"
+            };
+            let _ = write!(
+                html,
+                "<span class=\"{class}\"><span class=\"no\">{number}</span>{}</span>",
+                html_escape(text)
+            );
+        }
+        html.push_str("</pre>");
+    }
+    let _ = write!(
+        html,
+        "<h2>Request</h2><table><tr><td>{}</td><td>{}</td></tr>",
+        req.method(),
+        html_escape(&req.uri().to_string())
+    );
+    for (key, value) in req.match_info().iter() {
+        let _ = write!(
+            html,
+            "<tr><td>param {}</td><td>{}</td></tr>",
+            html_escape(key),
+            html_escape(value)
+        );
+    }
+    for (key, value) in req.headers() {
+        let _ = write!(
+            html,
+            "<tr><td>{}</td><td>{}</td></tr>",
+            html_escape(key.as_str()),
+            html_escape(&String::from_utf8_lossy(value.as_bytes()))
+        );
+    }
+    html.push_str("</table>");
+    let backtrace = err.backtrace();
+    if backtrace.status() == BacktraceStatus::Captured {
+        let _ = write!(
+            html,
+            "<h2>Backtrace</h2><pre>{}</pre>",
+            html_escape(&backtrace.to_string())
+        );
+    }
+    html.push_str("</main></body></html>");
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+    use anyhow::{Context as _, anyhow};
+
+    const SOURCE: &str = "---\nuse rsx::Request;\n\npub async fn get_server_props(req: Request) {}\n---\n\
+                          <template>\n  <p>{title}</p>\n  <p>{a + )}</p>\n</template>";
+
+    #[test]
+    fn locate_error_line() {
+        let err = anyhow!("unexpected token in expression `a + )`");
+        let excerpt =
+            SourceExcerpt::locate("src/pages/a.rsx", SOURCE, &err, Stage::Render).unwrap();
+        assert_eq!(excerpt.line, 8);
+        assert_eq!(excerpt.lines.first().unwrap().0, 5);
+        assert_eq!(excerpt.lines.last().unwrap().0, 9);
+
+        let err = anyhow!("db down").context("load `.`");
+        let excerpt = SourceExcerpt::locate("src/pages/a.rsx", SOURCE, &err, Stage::Props).unwrap();
+        assert_eq!(excerpt.line, 4);
+        let excerpt =
+            SourceExcerpt::locate("src/pages/a.rsx", SOURCE, &err, Stage::Render).unwrap();
+        assert_eq!(excerpt.line, 6);
+    }
+
+    #[test]
+    fn locate_render_error_line() {
+        let render = |name: &str| {
+            let mut handlebars = handlebars::Handlebars::new();
+            handlebars
+                .register_template_string(name, "\n  <p>{{title}}</p>\n  <p>{{lookup}}</p>\n")
+                .unwrap();
+            let err = handlebars.render(name, &()).unwrap_err();
+            anyhow::Error::new(err).context("render page a")
+        };
+        // 模板内容从<template>所在的第6行开始，生成的模板第3行对应第8行
+        let err = render("a");
+        let excerpt =
+            SourceExcerpt::locate("src/pages/a.rsx", SOURCE, &err, Stage::Render).unwrap();
+        assert_eq!(excerpt.line, 8);
+        let excerpt = SourceExcerpt::locate("src/pages/a.rsx", SOURCE, &err, Stage::Props).unwrap();
+        assert_eq!(excerpt.line, 4);
+
+        let err = render("a#defer-0");
+        let excerpt =
+            SourceExcerpt::locate("src/pages/a.rsx", SOURCE, &err, Stage::Render).unwrap();
+        assert_eq!(excerpt.line, 6);
+    }
+
+    #[test]
+    fn render_overlay_and_generic_page() {
+        let err = Err::<(), _>(anyhow!("connection refused"))
+            .context("load <post>")
+            .unwrap_err();
+        let excerpt = SourceExcerpt::locate("src/pages/a.rsx", SOURCE, &err, Stage::Props);
+        let req = TestRequest::get()
+            .uri("/posts/1?draft=1")
+            .insert_header(("accept", "text/html"))
+            .to_http_request();
+        let html = overlay("posts/[id]", &err, excerpt.as_ref(), &req);
+        assert!(html.contains("<h1>load &lt;post&gt;</h1>"), "{html}");
+        assert!(html.contains("<li>connection refused</li>"));
+        assert!(html.contains("<h2>src/pages/a.rsx:4</h2>"), "{html}");
+        assert!(html.contains("<span class=\"line hl\"><span class=\"no\">4</span>pub async fn"));
+        assert!(html.contains("<td>GET</td><td>/posts/1?draft&#x3D;1</td>"));
+        assert!(html.contains("<td>accept</td><td>text/html</td>"));
+
+        let id = error_id();
+        assert_eq!(id.len(), 16);
+        assert_ne!(id, error_id());
+        let page = generic_page(&id);
+        assert!(page.contains(&id));
+        assert!(!page.contains("connection refused"));
+    }
+}
@@ -0,0 +1,163 @@
+//! 静态导出
+//!
+//! 用与服务器相同的服务配置在进程内处理合成的GET请求，将每个页面渲染为静态HTML写入
+//! `Config.dist`，`/x`同时写为`x/index.html`和`x.html`，`/`写为`index.html`。请求路径中
+//! 百分号编码的段解码后作为文件名，如`/posts/a%20b`写为`posts/a b/index.html`
+//!
+//! 导出目录与Vite构建产物的目录相同：页面引用的`/assets`和Vite复制的public文件已在其中，
+//! 导出后整个`dist`可以直接部署到静态托管服务；同名文件会被覆盖，如Vite生成的`index.html`
+//!
+//! 动态路由的页面按`get_static_paths`返回的参数逐个渲染，没有该函数的动态路由跳过；
+//! 非2xx响应的页面只记录日志，不写入文件。有`404.rsx`或`_error.rsx`时另外写入`404.html`，
+//! 静态托管服务通常用它响应不存在的路径
+
+use crate::router::{self, Route, Router};
+use actix_web::http::StatusCode;
+use actix_web::http::Uri;
+use actix_web::{App, test as actix_test, web};
+use anyhow::{Context as _, Result, bail};
+use percent_encoding::percent_decode_str;
+use serde_json::Map;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 导出的一个页面
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportedPage {
+    /// 路由路径，如`/users/[id]`
+    pub route: String,
+    /// 请求路径，如`/users/7`
+    pub url: String,
+    /// 写入的文件
+    pub files: Vec<PathBuf>,
+}
+
+/// 渲染全部页面并写入`dist`，`configure`为注册服务的函数
+pub(crate) async fn export<F>(
+    router: &Router,
+    configure: F,
+    dist: &Path,
+) -> Result<Vec<ExportedPage>>
+where
+    F: FnOnce(&mut web::ServiceConfig) + 'static,
+{
+    let app = actix_test::init_service(App::new().configure(configure)).await;
+    let mut pages = Vec::new();
+    for route in router.routes() {
+        for url in urls(router, route).await? {
+            url.parse::<Uri>()
+                .with_context(|| format!("invalid static path {url} for route {}", route.path))?;
+            let res = actix_test::call_service(
+                &app,
+                actix_test::TestRequest::get().uri(&url).to_request(),
+            )
+            .await;
+            let status = res.status();
+            if !status.is_success() {
+                log::warn!("skip exporting {url}: page responded {status}");
+                continue;
+            }
+            let body = actix_test::read_body(res).await;
+            let files = output_files(dist, &url)?;
+            for file in &files {
+                if let Some(dir) = file.parent() {
+                    fs::create_dir_all(dir)
+                        .with_context(|| format!("failed to create {}", dir.display()))?;
+                }
+                fs::write(file, &body)
+                    .with_context(|| format!("failed to write {}", file.display()))?;
+            }
+            log::info!("exported {url} ({} bytes)", body.len());
+            pages.push(ExportedPage {
+                route: route.path.clone(),
+                url,
+                files,
+            });
+        }
+    }
+    if let Some(html) = router::error_page(&router.engine(), StatusCode::NOT_FOUND, None) {
+        let file = dist.join("404.html");
+        fs::create_dir_all(dist).with_context(|| format!("failed to create {}", dist.display()))?;
+        fs::write(&file, html).with_context(|| format!("failed to write {}", file.display()))?;
+        log::info!("exported {}", file.display());
+    }
+    Ok(pages)
+}
+
+/// 路由需要导出的请求路径
+async fn urls(router: &Router, route: &Route) -> Result<Vec<String>> {
+    if !route.is_dynamic() {
+        return Ok(vec![route.url(&Map::new())?]);
+    }
+    match router.props().static_paths(&route.name).await {
+        Some(paths) => paths
+            .with_context(|| format!("get_static_paths of page {} failed", route.name))?
+            .iter()
+            .map(|params| route.url(params))
+            .collect(),
+        None => {
+            log::warn!(
+                "skip exporting dynamic route {}: no get_static_paths",
+                route.path
+            );
+            Ok(Vec::new())
+        }
+    }
+}
+
+/// 请求路径对应的输出文件，各段解码后不能为`.`、`..`、空段或包含路径分隔符
+fn output_files(dist: &Path, url: &str) -> Result<Vec<PathBuf>> {
+    let path = url.trim_matches('/');
+    if path.is_empty() {
+        return Ok(vec![dist.join("index.html")]);
+    }
+    let segments = path
+        .split('/')
+        .map(|segment| percent_decode_str(segment).decode_utf8())
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("static path {url} is not valid UTF-8"))?;
+    if segments
+        .iter()
+        .any(|segment| matches!(segment.as_ref(), "" | "." | "..") || segment.contains(['/', '\\']))
+    {
+        bail!("static path {url} cannot be written to {}", dist.display());
+    }
+    let dir = segments
+        .iter()
+        .fold(dist.to_path_buf(), |dir, s| dir.join(s.as_ref()));
+    let mut html = dir.clone().into_os_string();
+    html.push(".html");
+    Ok(vec![dir.join("index.html"), PathBuf::from(html)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_urls_to_files() {
+        let dist = Path::new("dist");
+        assert_eq!(
+            output_files(dist, "/").unwrap(),
+            [PathBuf::from("dist/index.html")]
+        );
+        assert_eq!(
+            output_files(dist, "/users/7").unwrap(),
+            [
+                PathBuf::from("dist/users/7/index.html"),
+                PathBuf::from("dist/users/7.html")
+            ]
+        );
+        assert_eq!(
+            output_files(dist, "/posts/a%20b/%E6%8C%87%E5%8D%97").unwrap(),
+            [
+                PathBuf::from("dist/posts/a b/指南/index.html"),
+                PathBuf::from("dist/posts/a b/指南.html")
+            ]
+        );
+        assert!(output_files(dist, "/docs/../secret").is_err());
+        assert!(output_files(dist, "/docs/%2E%2E/secret").is_err());
+        assert!(output_files(dist, "/a%2Fb").is_err());
+        assert!(output_files(dist, "/a//b").is_err());
+    }
+}
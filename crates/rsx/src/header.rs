@@ -112,13 +112,13 @@ impl From<actix_http::header::HeaderMap> for Header {
     fn from(headers: actix_http::header::HeaderMap) -> Self {
         let mut new_headers = HeaderMap::new();
         for (name, value) in headers.iter() {
-            if let Ok(value_str) = value.to_str() {
-                if let (Ok(key), Ok(value)) = (
+            if let Ok(value_str) = value.to_str()
+                && let (Ok(key), Ok(value)) = (
                     HeaderName::from_str(name.as_str()),
                     HeaderValue::from_str(value_str),
-                ) {
-                    new_headers.insert(key, value);
-                }
+                )
+            {
+                new_headers.insert(key, value);
             }
         }
         Header(new_headers)
@@ -129,13 +129,13 @@ impl From<Header> for actix_http::header::HeaderMap {
     fn from(header: Header) -> Self {
         let mut new_headers = actix_http::header::HeaderMap::new();
         for (name, value) in header.0.iter() {
-            if let Ok(value_str) = value.to_str() {
-                if let (Ok(key), Ok(value)) = (
+            if let Ok(value_str) = value.to_str()
+                && let (Ok(key), Ok(value)) = (
                     actix_web::http::header::HeaderName::from_str(name.as_str()),
                     actix_web::http::header::HeaderValue::from_str(value_str),
-                ) {
-                    new_headers.insert(key, value);
-                }
+                )
+            {
+                new_headers.insert(key, value);
             }
         }
         new_headers
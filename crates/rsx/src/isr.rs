@@ -0,0 +1,327 @@
+//! 增量静态再生成(ISR)
+//!
+//! 页面frontmatter中声明`pub const REVALIDATE: u64 = 60;`后，页面的渲染结果按请求路径缓存在
+//! `quick_cache`中，有效期为`REVALIDATE`秒。过期后仍然立即返回缓存的页面，同时在后台
+//! 重新渲染，成功后替换缓存(stale-while-revalidate)，失败或非2xx时保留旧页面
+//!
+//! 同一路径同时只有一次渲染：缓存未命中时并发的请求等待第一个请求的结果，后台再生成期间
+//! 的请求继续返回旧页面。[`Isr::revalidate`]按需将页面标记为过期，如在CMS的webhook中调用，
+//! 下一次请求触发再生成。API中通过`web::Data<Isr>`获取
+//!
+//! 缓存的键为去掉`.html`、`/index.html`和末尾`/`的请求路径，查询参数、用户和请求头都不参与
+//! 缓存，因此同一路径的所有请求得到相同的页面；非2xx或设置了Cookie的响应不缓存。
+//! 已登录用户的请求，以及带有`Authorization`或`Cookie`请求头的请求可能渲染出个性化的页面，
+//! 这类请求不读写缓存，每次直接渲染。响应头`x-rsx-cache`为`HIT`、`STALE`、`MISS`或`BYPASS`
+
+use crate::context::Context;
+use actix_web::http::StatusCode;
+use actix_web::http::header::{self, HeaderName, HeaderValue};
+use actix_web::{HttpResponse, body};
+use anyhow::{Result, anyhow};
+use bytes::Bytes;
+use quick_cache::sync::Cache;
+use std::collections::HashSet;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// 标记缓存状态的响应头
+pub const CACHE_HEADER: &str = "x-rsx-cache";
+
+/// 默认最多缓存的页面数
+pub const DEFAULT_CAPACITY: usize = 1000;
+
+/// 缓存的页面响应
+#[derive(Debug, Clone)]
+pub struct CachedPage {
+    status: StatusCode,
+    headers: Vec<(HeaderName, HeaderValue)>,
+    body: Bytes,
+    expires: Instant,
+}
+
+impl CachedPage {
+    /// 读取响应体，`revalidate`后过期
+    async fn from_response(res: HttpResponse, revalidate: Duration) -> Result<Self> {
+        let (res, body) = res.into_parts();
+        let body = body::to_bytes(body)
+            .await
+            .map_err(|err| anyhow!("failed to read page body: {err}"))?;
+        let headers = res
+            .headers()
+            .iter()
+            .filter(|(name, _)| **name != header::CONTENT_LENGTH)
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        Ok(Self {
+            status: res.status(),
+            headers,
+            body,
+            expires: Instant::now() + revalidate,
+        })
+    }
+
+    /// 是否已过期
+    pub fn is_stale(&self) -> bool {
+        Instant::now() >= self.expires
+    }
+
+    /// 页面内容
+    pub fn body(&self) -> &Bytes {
+        &self.body
+    }
+
+    /// 生成响应，`state`写入缓存状态响应头
+    fn response(&self, state: &'static str) -> HttpResponse {
+        let mut res = HttpResponse::build(self.status);
+        for (name, value) in &self.headers {
+            res.append_header((name.clone(), value.clone()));
+        }
+        res.insert_header((CACHE_HEADER, state));
+        res.body(self.body.clone())
+    }
+}
+
+/// 响应是否可以缓存
+fn cacheable(res: &HttpResponse) -> bool {
+    res.status().is_success() && !res.headers().contains_key(header::SET_COOKIE)
+}
+
+/// 请求是否可能渲染出个性化的页面：已登录，或带有`Authorization`或`Cookie`请求头
+pub fn is_personalized(ctx: &Context) -> bool {
+    let headers = ctx.request().headers();
+    ctx.user().is_some()
+        || headers.contains_key(header::AUTHORIZATION)
+        || headers.contains_key(header::COOKIE)
+}
+
+/// 标记没有使用缓存的响应，用于个性化的请求
+pub(crate) fn bypass(mut res: HttpResponse) -> HttpResponse {
+    res.headers_mut().insert(
+        HeaderName::from_static(CACHE_HEADER),
+        HeaderValue::from_static("BYPASS"),
+    );
+    res
+}
+
+/// 请求路径对应的缓存键，如`/posts/1.html`和`/posts/1/`都为`/posts/1`
+pub fn cache_key(path: &str) -> String {
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    let path = path
+        .strip_suffix("/index.html")
+        .or_else(|| path.strip_suffix(".html"))
+        .unwrap_or(path)
+        .trim_end_matches('/');
+    format!("/{}", path.trim_start_matches('/'))
+}
+
+/// 页面缓存，由[`Router`](crate::router::Router)持有
+pub struct Isr {
+    cache: Cache<String, Arc<CachedPage>>,
+    /// 正在后台再生成的缓存键
+    regenerating: Mutex<HashSet<String>>,
+}
+
+impl Default for Isr {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl Isr {
+    /// 创建最多缓存`capacity`个页面的缓存
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            cache: Cache::new(capacity),
+            regenerating: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// 缓存的页面，`path`为请求路径
+    pub fn get(&self, path: &str) -> Option<Arc<CachedPage>> {
+        self.cache.get(&cache_key(path))
+    }
+
+    /// 将页面标记为过期，下一次请求返回旧页面并在后台再生成，页面未缓存时返回`false`
+    pub fn revalidate(&self, path: &str) -> bool {
+        let key = cache_key(path);
+        let Some(page) = self.cache.get(&key) else {
+            return false;
+        };
+        let mut page = (*page).clone();
+        page.expires = Instant::now();
+        self.cache.insert(key, Arc::new(page));
+        log::debug!("page {path} marked for revalidation");
+        true
+    }
+
+    /// 删除缓存的页面，下一次请求重新渲染
+    pub fn remove(&self, path: &str) -> bool {
+        self.cache.remove(&cache_key(path)).is_some()
+    }
+
+    /// 清空缓存，开发模式下重新编译页面后调用
+    pub fn clear(&self) {
+        self.cache.clear();
+    }
+
+    /// 缓存的页面数
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// 是否没有缓存页面
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    /// 返回缓存的页面，未命中时调用`render`渲染，过期时在后台调用`render`再生成
+    pub(crate) async fn serve<F, Fut>(
+        self: &Arc<Self>,
+        path: &str,
+        revalidate: Duration,
+        render: F,
+    ) -> HttpResponse
+    where
+        F: FnOnce() -> Fut + 'static,
+        Fut: Future<Output = HttpResponse> + 'static,
+    {
+        let key = cache_key(path);
+        let guard = match self.cache.get_value_or_guard_async(&key).await {
+            Ok(page) if page.is_stale() => {
+                self.regenerate(key, revalidate, render);
+                return page.response("STALE");
+            }
+            Ok(page) => return page.response("HIT"),
+            Err(guard) => guard,
+        };
+        // 未缓存的响应丢弃guard，等待中的请求各自渲染
+        let res = render().await;
+        if !cacheable(&res) {
+            return res;
+        }
+        match CachedPage::from_response(res, revalidate).await {
+            Ok(page) => {
+                let page = Arc::new(page);
+                let _ = guard.insert(page.clone());
+                page.response("MISS")
+            }
+            Err(err) => {
+                log::error!("cache page {key} error: {err:?}");
+                HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+        }
+    }
+
+    /// 在后台再生成页面，同一路径已在再生成时跳过
+    fn regenerate<F, Fut>(self: &Arc<Self>, key: String, revalidate: Duration, render: F)
+    where
+        F: FnOnce() -> Fut + 'static,
+        Fut: Future<Output = HttpResponse> + 'static,
+    {
+        let Some(guard) = Regenerating::start(self, key) else {
+            return;
+        };
+        actix_rt::spawn(async move {
+            let res = render().await;
+            let key = &guard.key;
+            if !cacheable(&res) {
+                log::warn!(
+                    "regenerate page {key} responded {}, keep stale page",
+                    res.status()
+                );
+                return;
+            }
+            match CachedPage::from_response(res, revalidate).await {
+                Ok(page) => {
+                    guard.isr.cache.insert(key.clone(), Arc::new(page));
+                    log::debug!("page {key} regenerated");
+                }
+                Err(err) => log::error!("regenerate page {key} error: {err:?}"),
+            }
+        });
+    }
+}
+
+/// 再生成中的标记，结束或任务被取消时移除
+struct Regenerating {
+    isr: Arc<Isr>,
+    key: String,
+}
+
+impl Regenerating {
+    fn start(isr: &Arc<Isr>, key: String) -> Option<Self> {
+        let mut regenerating = isr.regenerating.lock().unwrap_or_else(|e| e.into_inner());
+        regenerating.insert(key.clone()).then(|| Self {
+            isr: isr.clone(),
+            key,
+        })
+    }
+}
+
+impl Drop for Regenerating {
+    fn drop(&mut self) {
+        let mut regenerating = self
+            .isr
+            .regenerating
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        regenerating.remove(&self.key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_cache_keys() {
+        assert_eq!(cache_key("/"), "/");
+        assert_eq!(cache_key("/index.html"), "/");
+        assert_eq!(cache_key("/posts/1"), "/posts/1");
+        assert_eq!(cache_key("/posts/1.html"), "/posts/1");
+        assert_eq!(cache_key("/posts/1/?page=2"), "/posts/1");
+        assert_eq!(cache_key("/posts/index.html"), "/posts");
+    }
+
+    #[actix_rt::test]
+    async fn serve_stale_while_revalidate() {
+        let isr = Arc::new(Isr::default());
+        let render = |body: &'static str| move || async move { HttpResponse::Ok().body(body) };
+        let state = |res: &HttpResponse| {
+            res.headers()
+                .get(CACHE_HEADER)
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .to_string()
+        };
+
+        let res = isr.serve("/a", Duration::from_secs(60), render("1")).await;
+        assert_eq!(state(&res), "MISS");
+        let res = isr
+            .serve("/a.html", Duration::from_secs(60), render("2"))
+            .await;
+        assert_eq!(state(&res), "HIT");
+        assert!(!isr.revalidate("/b"));
+
+        assert!(isr.revalidate("/a"));
+        let res = isr.serve("/a", Duration::from_secs(60), render("2")).await;
+        assert_eq!(state(&res), "STALE");
+        assert_eq!(body::to_bytes(res.into_body()).await.unwrap(), "1");
+        // 等待后台再生成完成
+        while !isr.regenerating.lock().unwrap().is_empty() {
+            actix_rt::task::yield_now().await;
+        }
+        assert_eq!(isr.get("/a").unwrap().body(), "2");
+
+        // 非2xx响应不缓存
+        let res = isr
+            .serve("/c", Duration::from_secs(60), || async {
+                HttpResponse::NotFound().finish()
+            })
+            .await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        assert!(isr.get("/c").is_none());
+    }
+}
@@ -1,11 +1,57 @@
+// 让过程宏生成的`::rsx`路径在本crate内也能解析
+extern crate self as rsx;
+
 pub mod build;
+pub mod cli;
 pub mod config;
 pub mod context;
+pub mod dev;
+pub mod error;
+pub mod export;
 pub mod fetch;
 pub mod header;
+pub mod isr;
+pub mod manifest;
+pub mod params;
+pub mod parser;
 pub mod props;
 pub mod request;
 pub mod response;
 pub mod router;
+pub mod routes;
 pub mod server;
 pub mod shared;
+pub mod stream;
+pub mod template;
+pub mod typescript;
+
+pub use actix_web::http::StatusCode;
+pub use context::Context;
+pub use props::Props;
+pub use request::Request;
+pub use response::{Response, ServerResponse};
+pub use rsx_macros::{Props, request_all, response_json};
+pub use serde_json::json;
+
+/// 过程宏展开代码使用的依赖
+#[doc(hidden)]
+pub mod __private {
+    pub use anyhow;
+    pub use futures_util;
+}
+
+/// 引入build.rs中[`build::build`]生成的页面模块注册表
+///
+/// ```ignore
+/// mod pages {
+///     rsx::include_pages!();
+/// }
+///
+/// RsxServer::new(config).props(pages::registry())
+/// ```
+#[macro_export]
+macro_rules! include_pages {
+    () => {
+        include!(env!("RSX_GENERATED_MOD"));
+    };
+}
@@ -0,0 +1,364 @@
+//! Vite构建清单
+//!
+//! 读取`vite build`在dist目录生成的`manifest.json`（开启`build.manifest`），将源文件如
+//! `src/react/news.app.tsx`解析为构建后带hash的JS和CSS文件，并在页面的`<head>`中插入
+//! 样式、`<link rel="modulepreload">`和`<script type="module">`标签
+
+use crate::config::Config;
+use anyhow::{Context as _, Result};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// dist目录中清单文件的位置，Vite 5起位于`.vite`目录
+pub const MANIFEST_FILES: [&str; 2] = [".vite/manifest.json", "manifest.json"];
+
+/// 清单中的一个构建产物
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Chunk {
+    /// 构建后的文件，相对dist目录，如`assets/news.app-4ed993c7.js`
+    pub file: String,
+    /// 源文件，相对项目根目录
+    #[serde(default)]
+    pub src: Option<String>,
+    /// 是否为入口
+    #[serde(default)]
+    pub is_entry: bool,
+    /// 静态导入的其他产物，值为清单中的键
+    #[serde(default)]
+    pub imports: Vec<String>,
+    /// 动态导入的其他产物，不需要预加载
+    #[serde(default)]
+    pub dynamic_imports: Vec<String>,
+    /// 产物及其导入的CSS文件
+    #[serde(default)]
+    pub css: Vec<String>,
+    /// 引用的静态资源
+    #[serde(default)]
+    pub assets: Vec<String>,
+}
+
+/// 一组入口需要的资源地址，均已去重
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Assets {
+    /// 需要执行的入口脚本
+    pub scripts: Vec<String>,
+    /// 需要预加载的模块
+    pub preloads: Vec<String>,
+    /// 样式表
+    pub styles: Vec<String>,
+}
+
+impl Assets {
+    /// 是否没有资源
+    pub fn is_empty(&self) -> bool {
+        self.scripts.is_empty() && self.preloads.is_empty() && self.styles.is_empty()
+    }
+
+    /// 生成`<head>`中的标签，样式在前，预加载在后，入口脚本最后
+    pub fn tags(&self) -> String {
+        let mut out = String::new();
+        for href in &self.styles {
+            out.push_str(&format!(r#"<link rel="stylesheet" href="{href}">"#));
+        }
+        for href in &self.preloads {
+            out.push_str(&format!(r#"<link rel="modulepreload" href="{href}">"#));
+        }
+        for src in &self.scripts {
+            out.push_str(&format!(r#"<script type="module" src="{src}"></script>"#));
+        }
+        out
+    }
+}
+
+/// Vite构建清单
+#[derive(Debug, Clone, Default)]
+pub struct Manifest {
+    chunks: HashMap<String, Chunk>,
+    /// 构建产物的公共路径，对应Vite的`base`
+    base: String,
+    /// dist目录
+    dist: PathBuf,
+}
+
+impl Manifest {
+    /// 从配置的dist目录读取清单，公共路径为`Config.base`，没有清单文件时返回`None`
+    pub fn load(config: &Config) -> Result<Option<Self>> {
+        let dist = Path::new(config.root()).join(&config.dist);
+        match MANIFEST_FILES
+            .iter()
+            .map(|file| dist.join(file))
+            .find(|path| path.is_file())
+        {
+            Some(path) => Ok(Some(Self::from_file(&path, &dist)?.with_base(&config.base))),
+            None => Ok(None),
+        }
+    }
+
+    /// 读取清单文件，`dist`为构建产物所在目录
+    pub fn from_file(path: &Path, dist: impl Into<PathBuf>) -> Result<Self> {
+        let json = fs::read_to_string(path)
+            .with_context(|| format!("failed to read vite manifest {}", path.display()))?;
+        let mut manifest = Self::from_json(&json)
+            .with_context(|| format!("invalid vite manifest {}", path.display()))?;
+        manifest.dist = dist.into();
+        Ok(manifest)
+    }
+
+    /// 解析清单内容
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(Self {
+            chunks: serde_json::from_str(json)?,
+            base: "/".to_string(),
+            dist: PathBuf::new(),
+        })
+    }
+
+    /// 设置公共路径，默认为`/`
+    pub fn with_base(mut self, base: impl Into<String>) -> Self {
+        let mut base = base.into();
+        if !base.ends_with('/') {
+            base.push('/');
+        }
+        self.base = base;
+        self
+    }
+
+    /// 构建产物的公共路径
+    pub fn base(&self) -> &str {
+        &self.base
+    }
+
+    /// 构建产物目录在服务器上的路径，如`/app/assets`；公共路径为完整URL（如CDN）时
+    /// 产物不经过服务器，仍以`/assets`提供
+    pub fn mount_path(&self, dir: &str) -> String {
+        if self.base.starts_with('/') {
+            format!("{}{dir}", self.base)
+        } else {
+            format!("/{dir}")
+        }
+    }
+
+    /// dist目录
+    pub fn dist(&self) -> &Path {
+        &self.dist
+    }
+
+    /// 构建产物所在的顶层目录，如`assets`，服务器需要以静态文件提供这些目录
+    pub fn asset_dirs(&self) -> Vec<String> {
+        let mut dirs: Vec<String> = self
+            .chunks
+            .values()
+            .flat_map(|chunk| {
+                std::iter::once(&chunk.file)
+                    .chain(&chunk.css)
+                    .chain(&chunk.assets)
+            })
+            .filter_map(|file| file.split_once('/').map(|(dir, _)| dir.to_string()))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        dirs.sort();
+        dirs
+    }
+
+    /// 查找源文件对应的产物，`src`可以带开头的`/`或`./`
+    pub fn chunk(&self, src: &str) -> Option<&Chunk> {
+        self.chunks.get(key(src))
+    }
+
+    /// 源文件构建后的地址，如`/assets/news.app-4ed993c7.js`
+    pub fn url(&self, src: &str) -> Option<String> {
+        self.chunk(src).map(|chunk| self.href(&chunk.file))
+    }
+
+    /// 收集入口需要的资源，`scripts`中的入口立即执行，`modules`中的入口只预加载，
+    /// 如按需加载的岛组件；清单中没有的入口被忽略
+    pub fn assets<S: AsRef<str>>(&self, scripts: &[S], modules: &[S]) -> Assets {
+        let mut assets = Assets::default();
+        let mut visited = HashSet::new();
+        for (src, execute) in scripts
+            .iter()
+            .map(|src| (src, true))
+            .chain(modules.iter().map(|src| (src, false)))
+        {
+            let src = key(src.as_ref());
+            let Some(chunk) = self.chunks.get(src) else {
+                continue;
+            };
+            if !visited.insert(src.to_string()) {
+                continue;
+            }
+            let href = self.href(&chunk.file);
+            if execute {
+                assets.scripts.push(href);
+            } else {
+                push_unique(&mut assets.preloads, href);
+            }
+            self.collect(chunk, &mut assets, &mut visited);
+        }
+        assets
+    }
+
+    /// 收集产物的CSS和静态导入
+    fn collect(&self, chunk: &Chunk, assets: &mut Assets, visited: &mut HashSet<String>) {
+        for css in &chunk.css {
+            push_unique(&mut assets.styles, self.href(css));
+        }
+        for import in &chunk.imports {
+            if !visited.insert(import.clone()) {
+                continue;
+            }
+            if let Some(chunk) = self.chunks.get(import) {
+                push_unique(&mut assets.preloads, self.href(&chunk.file));
+                self.collect(chunk, assets, visited);
+            }
+        }
+    }
+
+    fn href(&self, file: &str) -> String {
+        format!("{}{file}", self.base)
+    }
+}
+
+/// 清单的键相对项目根目录，不带开头的`/`或`./`
+fn key(src: &str) -> &str {
+    src.trim_start_matches("./").trim_start_matches('/')
+}
+
+fn push_unique(list: &mut Vec<String>, value: String) {
+    if !list.contains(&value) {
+        list.push(value);
+    }
+}
+
+/// 将标签插入到`</head>`之前，没有`</head>`时不插入
+pub fn inject_head(html: &mut String, tags: &str) -> bool {
+    if tags.is_empty() {
+        return false;
+    }
+    match html.find("</head>") {
+        Some(index) => {
+            html.insert_str(index, tags);
+            true
+        }
+        None => false,
+    }
+}
+
+/// 将标签插入到`</head>`之前，没有`</head>`时插入到`<body>`开始标签之后，
+/// 都没有时插入到最前面，用于页面片段
+pub fn inject_page(html: &mut String, tags: &str) {
+    if tags.is_empty() || inject_head(html, tags) {
+        return;
+    }
+    let body = html.match_indices("<body").find_map(|(index, _)| {
+        let rest = &html[index + "<body".len()..];
+        rest.starts_with(|c: char| c == '>' || c.is_ascii_whitespace())
+            .then(|| rest.find('>').map(|end| index + "<body".len() + end + 1))
+            .flatten()
+    });
+    html.insert_str(body.unwrap_or(0), tags);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"{
+        "_shared-B7PI925R.js": {
+            "file": "assets/shared-B7PI925R.js",
+            "name": "shared",
+            "css": ["assets/shared-ChJ_j-JJ.css"]
+        },
+        "src/react/news.app.tsx": {
+            "file": "assets/news.app-BRBmoGS9.js",
+            "name": "news.app",
+            "src": "src/react/news.app.tsx",
+            "isEntry": true,
+            "imports": ["_shared-B7PI925R.js"],
+            "dynamicImports": ["src/react/lazy.tsx"],
+            "css": ["assets/news.app-5UjPuW-k.css"]
+        },
+        "src/pages/index.rsx": {
+            "file": "assets/index-Dq2x1dKt.js",
+            "src": "src/pages/index.rsx",
+            "isEntry": true,
+            "imports": ["_shared-B7PI925R.js"]
+        },
+        "src/react/lazy.tsx": {
+            "file": "assets/lazy-2Xy8vQ1a.js",
+            "src": "src/react/lazy.tsx",
+            "isDynamicEntry": true
+        }
+    }"#;
+
+    #[test]
+    fn resolve_entries() {
+        let manifest = Manifest::from_json(MANIFEST).unwrap();
+        assert_eq!(
+            manifest.url("/src/react/news.app.tsx").as_deref(),
+            Some("/assets/news.app-BRBmoGS9.js")
+        );
+        assert_eq!(manifest.url("./src/missing.tsx"), None);
+        assert_eq!(manifest.asset_dirs(), ["assets"]);
+
+        let assets = manifest.assets(&["src/pages/index.rsx"], &["/src/react/news.app.tsx"]);
+        assert_eq!(assets.scripts, ["/assets/index-Dq2x1dKt.js"]);
+        assert_eq!(
+            assets.preloads,
+            ["/assets/shared-B7PI925R.js", "/assets/news.app-BRBmoGS9.js"]
+        );
+        assert_eq!(
+            assets.styles,
+            [
+                "/assets/shared-ChJ_j-JJ.css",
+                "/assets/news.app-5UjPuW-k.css"
+            ]
+        );
+        assert_eq!(
+            assets.tags(),
+            "<link rel=\"stylesheet\" href=\"/assets/shared-ChJ_j-JJ.css\">\
+             <link rel=\"stylesheet\" href=\"/assets/news.app-5UjPuW-k.css\">\
+             <link rel=\"modulepreload\" href=\"/assets/shared-B7PI925R.js\">\
+             <link rel=\"modulepreload\" href=\"/assets/news.app-BRBmoGS9.js\">\
+             <script type=\"module\" src=\"/assets/index-Dq2x1dKt.js\"></script>"
+        );
+    }
+
+    #[test]
+    fn prefix_base_and_inject() {
+        let manifest = Manifest::from_json(MANIFEST).unwrap().with_base("/static");
+        let assets = manifest.assets::<&str>(&[], &["src/react/lazy.tsx"]);
+        assert_eq!(assets.preloads, ["/static/assets/lazy-2Xy8vQ1a.js"]);
+        assert!(assets.styles.is_empty());
+
+        let mut html = "<html><head><title>t</title></head><body></body></html>".to_string();
+        assert!(inject_head(&mut html, &assets.tags()));
+        assert_eq!(
+            html,
+            "<html><head><title>t</title>\
+             <link rel=\"modulepreload\" href=\"/static/assets/lazy-2Xy8vQ1a.js\"></head>\
+             <body></body></html>"
+        );
+        assert!(!inject_head(&mut "<p></p>".to_string(), "<link>"));
+
+        let mut html = "<body class=\"a\"><p></p></body>".to_string();
+        inject_page(&mut html, "<link>");
+        assert_eq!(html, "<body class=\"a\"><link><p></p></body>");
+        let mut html = "<bodyx></bodyx>".to_string();
+        inject_page(&mut html, "<link>");
+        assert_eq!(html, "<link><bodyx></bodyx>");
+        assert_eq!(manifest.mount_path("assets"), "/static/assets");
+        let cdn = Manifest::from_json(MANIFEST)
+            .unwrap()
+            .with_base("https://cdn.example.com/app");
+        assert_eq!(
+            cdn.url("src/react/news.app.tsx").as_deref(),
+            Some("https://cdn.example.com/app/assets/news.app-BRBmoGS9.js")
+        );
+        assert_eq!(cdn.mount_path("assets"), "/assets");
+    }
+}
@@ -0,0 +1,142 @@
+use actix_web::HttpRequest;
+use serde::{Serialize, Serializer, ser::SerializeMap};
+use std::str::FromStr;
+
+/// 路由参数错误
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ParamError {
+    #[error("route param `{0}` is missing")]
+    Missing(String),
+    #[error("route param `{name}` has invalid value `{value}`: {message}")]
+    Invalid {
+        name: String,
+        value: String,
+        message: String,
+    },
+}
+
+/// 动态路由捕获的参数，如`users/[id].rsx`中的`id`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Params(Vec<(String, String)>);
+
+impl Params {
+    /// 创建空的参数表
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 从actix的路由匹配结果中读取参数
+    pub fn from_request(req: &HttpRequest) -> Self {
+        Self(
+            req.match_info()
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        )
+    }
+
+    /// 插入参数
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        let name = name.into();
+        let value = value.into();
+        match self.0.iter_mut().find(|(key, _)| *key == name) {
+            Some((_, old)) => *old = value,
+            None => self.0.push((name, value)),
+        }
+    }
+
+    /// 获取参数的原始值
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// 获取参数并解析为指定类型
+    pub fn get_as<T>(&self, name: &str) -> Result<T, ParamError>
+    where
+        T: FromStr,
+        T::Err: std::fmt::Display,
+    {
+        let value = self
+            .get(name)
+            .ok_or_else(|| ParamError::Missing(name.to_string()))?;
+        value.parse().map_err(|err: T::Err| ParamError::Invalid {
+            name: name.to_string(),
+            value: value.to_string(),
+            message: err.to_string(),
+        })
+    }
+
+    /// 获取catch-all参数的各级路径，如`[...slug]`匹配`a/b`时返回`["a", "b"]`
+    pub fn segments(&self, name: &str) -> Vec<&str> {
+        self.get(name)
+            .map(|value| value.split('/').filter(|s| !s.is_empty()).collect())
+            .unwrap_or_default()
+    }
+
+    /// 遍历全部参数
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+
+    /// 是否没有参数
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> FromIterator<(K, V)> for Params {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut params = Params::new();
+        for (name, value) in iter {
+            params.insert(name, value);
+        }
+        params
+    }
+}
+
+impl Serialize for Params {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (name, value) in &self.0 {
+            map.serialize_entry(name, value)?;
+        }
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_typed_param() {
+        let params: Params = [("id", "42"), ("slug", "a/b/c")].into_iter().collect();
+        assert_eq!(params.get("id"), Some("42"));
+        assert_eq!(params.get_as::<u64>("id"), Ok(42));
+        assert_eq!(params.segments("slug"), vec!["a", "b", "c"]);
+        assert!(params.segments("missing").is_empty());
+    }
+
+    #[test]
+    fn typed_param_errors() {
+        let params: Params = [("id", "abc")].into_iter().collect();
+        assert_eq!(
+            params.get_as::<u64>("page"),
+            Err(ParamError::Missing("page".to_string()))
+        );
+        let err = params.get_as::<u64>("id").unwrap_err();
+        assert!(matches!(err, ParamError::Invalid { .. }));
+        assert!(err.to_string().contains("`abc`"));
+    }
+
+    #[test]
+    fn serialize_as_map() {
+        let params: Params = [("id", "1")].into_iter().collect();
+        assert_eq!(serde_json::to_string(&params).unwrap(), r#"{"id":"1"}"#);
+    }
+}
@@ -0,0 +1,117 @@
+use std::path::PathBuf;
+
+/// 源码中的位置，行列号均从1开始
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Position {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// 源码中的一段区间
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+/// .rsx文件中的区块类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockKind {
+    Frontmatter,
+    Script,
+    Template,
+    Style,
+}
+
+impl BlockKind {
+    /// 区块对应的标签名
+    pub fn tag_name(&self) -> &'static str {
+        match self {
+            BlockKind::Frontmatter => "---",
+            BlockKind::Script => "script",
+            BlockKind::Template => "template",
+            BlockKind::Style => "style",
+        }
+    }
+}
+
+/// 区块标签上的属性
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attribute {
+    pub name: String,
+    pub value: Option<String>,
+}
+
+/// .rsx文件中的一个区块
+#[derive(Debug, Clone)]
+pub struct Block {
+    pub kind: BlockKind,
+    pub attrs: Vec<Attribute>,
+    /// 区块内容，保持源码原样
+    pub content: String,
+    /// 区块内容在源码中的区间
+    pub span: Span,
+}
+
+impl Block {
+    /// 获取区块标签上的属性值
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|attr| attr.name == name)
+            .and_then(|attr| attr.value.as_deref())
+    }
+
+    /// 判断区块标签上是否存在属性
+    pub fn has_attr(&self, name: &str) -> bool {
+        self.attrs.iter().any(|attr| attr.name == name)
+    }
+
+    /// 将区块内的行号（从1开始）换算为源文件中的行号
+    pub fn source_line(&self, line: usize) -> usize {
+        self.span.start.line + line.saturating_sub(1)
+    }
+}
+
+/// `<script>`中的默认导入，如`import Meta from '../components/meta.rsx'`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Import {
+    pub name: String,
+    pub source: String,
+}
+
+impl Import {
+    /// 是否导入的是.rsx组件
+    pub fn is_rsx(&self) -> bool {
+        self.source.ends_with(".rsx")
+    }
+}
+
+/// 解析后的.rsx单文件组件
+#[derive(Debug, Clone, Default)]
+pub struct RsxFile {
+    pub path: Option<PathBuf>,
+    pub frontmatter: Option<Block>,
+    pub script: Option<Block>,
+    pub template: Option<Block>,
+    pub style: Option<Block>,
+    pub imports: Vec<Import>,
+}
+
+impl RsxFile {
+    /// 按类型获取区块
+    pub fn block(&self, kind: BlockKind) -> Option<&Block> {
+        match kind {
+            BlockKind::Frontmatter => self.frontmatter.as_ref(),
+            BlockKind::Script => self.script.as_ref(),
+            BlockKind::Template => self.template.as_ref(),
+            BlockKind::Style => self.style.as_ref(),
+        }
+    }
+
+    /// 按名称查找导入
+    pub fn import(&self, name: &str) -> Option<&Import> {
+        self.imports.iter().find(|import| import.name == name)
+    }
+}
@@ -0,0 +1,417 @@
+//! .rsx单文件组件解析
+//!
+//! 将.rsx文件拆分为Rust frontmatter、`<script>`、`<template>`和`<style>`四个区块
+
+mod ast;
+
+pub use ast::*;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+lazy_static! {
+    static ref IMPORT_RE: Regex =
+        Regex::new(r#"(?m)^\s*import\s+([A-Za-z_$][\w$]*)\s+from\s+['"]([^'"]+)['"]"#).unwrap();
+}
+
+/// 解析错误，包含出错的行列号
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{}{line}:{column}: {message}", file_prefix(.file))]
+pub struct ParseError {
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+    pub file: Option<PathBuf>,
+}
+
+fn file_prefix(file: &Option<PathBuf>) -> String {
+    file.as_ref()
+        .map(|file| format!("{}:", file.display()))
+        .unwrap_or_default()
+}
+
+/// 解析.rsx源码
+pub fn parse(source: &str) -> Result<RsxFile, ParseError> {
+    Parser::new(source).parse()
+}
+
+/// 读取并解析.rsx文件
+pub fn parse_file(path: impl AsRef<Path>) -> anyhow::Result<RsxFile> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path)?;
+    let mut file = parse(&source).map_err(|mut err| {
+        err.file = Some(path.to_path_buf());
+        err
+    })?;
+    file.path = Some(path.to_path_buf());
+    Ok(file)
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    pos: usize,
+    line_starts: Vec<usize>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(source: &'a str) -> Self {
+        let mut line_starts = vec![0];
+        line_starts.extend(source.match_indices('\n').map(|(i, _)| i + 1));
+        let pos = if source.starts_with('\u{feff}') { 3 } else { 0 };
+        Self {
+            source,
+            pos,
+            line_starts,
+        }
+    }
+
+    fn parse(mut self) -> Result<RsxFile, ParseError> {
+        let mut file = RsxFile::default();
+        self.skip_whitespace();
+        if self.at_fence() {
+            file.frontmatter = Some(self.parse_frontmatter()?);
+        }
+
+        loop {
+            self.skip_whitespace();
+            if self.pos >= self.source.len() {
+                break;
+            }
+            let rest = &self.source[self.pos..];
+            if rest.starts_with("<!--") {
+                let end = rest
+                    .find("-->")
+                    .ok_or_else(|| self.error_at(self.pos, "unterminated comment"))?;
+                self.pos += end + 3;
+                continue;
+            }
+            if self.at_fence() {
+                return Err(self.error_at(
+                    self.pos,
+                    "frontmatter must be placed at the beginning of the file",
+                ));
+            }
+            if !rest.starts_with('<') {
+                return Err(self.error_at(self.pos, "unexpected content outside of a block"));
+            }
+
+            let start = self.pos;
+            let name: String = rest[1..]
+                .chars()
+                .take_while(|c| c.is_ascii_alphanumeric() || *c == '-')
+                .collect();
+            let kind = match name.as_str() {
+                "script" => BlockKind::Script,
+                "template" => BlockKind::Template,
+                "style" => BlockKind::Style,
+                _ => {
+                    return Err(self.error_at(
+                        start,
+                        format!(
+                            "unexpected top-level tag <{name}>, expected <script>, <template> or <style>"
+                        ),
+                    ));
+                }
+            };
+            let block = self.parse_block(kind)?;
+            let slot = match kind {
+                BlockKind::Script => &mut file.script,
+                BlockKind::Template => &mut file.template,
+                BlockKind::Style => &mut file.style,
+                BlockKind::Frontmatter => unreachable!(),
+            };
+            if slot.is_some() {
+                return Err(self.error_at(start, format!("duplicate <{name}> block")));
+            }
+            *slot = Some(block);
+        }
+
+        if let Some(script) = &file.script {
+            file.imports = parse_imports(&script.content);
+        }
+        Ok(file)
+    }
+
+    /// 当前行是否为`---`
+    fn at_fence(&self) -> bool {
+        let rest = &self.source[self.pos..];
+        let line = rest.split('\n').next().unwrap_or_default();
+        line.trim_end() == "---"
+    }
+
+    fn parse_frontmatter(&mut self) -> Result<Block, ParseError> {
+        let open = self.pos;
+        self.pos = self.line_end(self.pos);
+        let content_start = self.pos;
+        while self.pos < self.source.len() {
+            let line_start = self.pos;
+            let line_end = self.line_end(line_start);
+            if self.source[line_start..line_end].trim() == "---" {
+                self.pos = line_end;
+                return Ok(Block {
+                    kind: BlockKind::Frontmatter,
+                    attrs: Vec::new(),
+                    content: self.source[content_start..line_start].to_string(),
+                    span: self.span(content_start, line_start),
+                });
+            }
+            self.pos = line_end;
+        }
+        Err(self.error_at(open, "unterminated frontmatter, expected a closing `---`"))
+    }
+
+    fn parse_block(&mut self, kind: BlockKind) -> Result<Block, ParseError> {
+        let tag = kind.tag_name();
+        let open = self.pos;
+        self.pos += tag.len() + 1;
+        let attrs = self.parse_attrs(open, tag)?;
+        let content_start = self.pos;
+        let close = format!("</{tag}");
+        let content_end = if kind == BlockKind::Template {
+            self.find_template_close(content_start)
+        } else {
+            self.source[content_start..]
+                .find(&close)
+                .map(|i| content_start + i)
+        }
+        .ok_or_else(|| self.error_at(open, format!("unclosed <{tag}> block")))?;
+
+        let after = &self.source[content_end + close.len()..];
+        let gt = after
+            .find('>')
+            .ok_or_else(|| self.error_at(content_end, format!("malformed </{tag}> tag")))?;
+        if !after[..gt].trim().is_empty() {
+            return Err(self.error_at(content_end, format!("malformed </{tag}> tag")));
+        }
+        self.pos = content_end + close.len() + gt + 1;
+        Ok(Block {
+            kind,
+            attrs,
+            content: self.source[content_start..content_end].to_string(),
+            span: self.span(content_start, content_end),
+        })
+    }
+
+    fn parse_attrs(&mut self, open: usize, tag: &str) -> Result<Vec<Attribute>, ParseError> {
+        let mut attrs = Vec::new();
+        loop {
+            self.skip_whitespace();
+            let rest = &self.source[self.pos..];
+            if rest.is_empty() {
+                return Err(self.error_at(open, format!("unterminated <{tag}> tag")));
+            }
+            if rest.starts_with('>') {
+                self.pos += 1;
+                return Ok(attrs);
+            }
+            let name_len = rest
+                .find(|c: char| c.is_whitespace() || c == '=' || c == '>')
+                .unwrap_or(rest.len());
+            if name_len == 0 {
+                return Err(self.error_at(self.pos, "invalid attribute"));
+            }
+            let name = rest[..name_len].to_string();
+            self.pos += name_len;
+            self.skip_whitespace();
+            let mut value = None;
+            if self.source[self.pos..].starts_with('=') {
+                self.pos += 1;
+                self.skip_whitespace();
+                value = Some(self.parse_attr_value()?);
+            }
+            attrs.push(Attribute { name, value });
+        }
+    }
+
+    fn parse_attr_value(&mut self) -> Result<String, ParseError> {
+        let rest = &self.source[self.pos..];
+        match rest.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let end = rest[1..]
+                    .find(quote)
+                    .ok_or_else(|| self.error_at(self.pos, "unterminated attribute value"))?;
+                let value = rest[1..end + 1].to_string();
+                self.pos += end + 2;
+                Ok(value)
+            }
+            _ => {
+                let len = rest
+                    .find(|c: char| c.is_whitespace() || c == '>')
+                    .unwrap_or(rest.len());
+                self.pos += len;
+                Ok(rest[..len].to_string())
+            }
+        }
+    }
+
+    /// 查找与`<template>`匹配的闭合标签，允许模板内嵌套`<template>`元素
+    fn find_template_close(&self, from: usize) -> Option<usize> {
+        let mut depth = 0usize;
+        let mut pos = from;
+        while let Some(i) = self.source[pos..].find('<') {
+            let at = pos + i;
+            let rest = &self.source[at..];
+            if rest.starts_with("</template") {
+                if depth == 0 {
+                    return Some(at);
+                }
+                depth -= 1;
+            } else if rest.starts_with("<template")
+                && rest[9..].starts_with(|c: char| c.is_whitespace() || c == '>')
+            {
+                depth += 1;
+            }
+            pos = at + 1;
+        }
+        None
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.source[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    /// 返回下一行的起始位置
+    fn line_end(&self, from: usize) -> usize {
+        self.source[from..]
+            .find('\n')
+            .map(|i| from + i + 1)
+            .unwrap_or(self.source.len())
+    }
+
+    fn position(&self, offset: usize) -> Position {
+        let line = self.line_starts.partition_point(|start| *start <= offset);
+        let line_start = self.line_starts[line - 1];
+        Position {
+            offset,
+            line,
+            column: self.source[line_start..offset].chars().count() + 1,
+        }
+    }
+
+    fn span(&self, start: usize, end: usize) -> Span {
+        Span {
+            start: self.position(start),
+            end: self.position(end),
+        }
+    }
+
+    fn error_at(&self, offset: usize, message: impl Into<String>) -> ParseError {
+        let position = self.position(offset);
+        ParseError {
+            message: message.into(),
+            line: position.line,
+            column: position.column,
+            file: None,
+        }
+    }
+}
+
+/// 提取`<script>`中的默认导入
+fn parse_imports(script: &str) -> Vec<Import> {
+    IMPORT_RE
+        .captures_iter(script)
+        .map(|caps| Import {
+            name: caps[1].to_string(),
+            source: caps[2].to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"---
+use rsx::{Request, Response};
+---
+
+<script lang="ts">
+    import Meta from '../components/meta.rsx';
+    import NewsApp from '../react/news.app.tsx';
+</script>
+
+<template>
+    <template><i>nested</i></template>
+    <div>{title}</div>
+</template>
+
+<style scoped>
+    div { display: flex; }
+</style>
+"#;
+
+    #[test]
+    fn parse_all_blocks() {
+        let file = parse(SOURCE).unwrap();
+        let frontmatter = file.frontmatter.unwrap();
+        assert_eq!(frontmatter.content, "use rsx::{Request, Response};\n");
+        assert_eq!(frontmatter.span.start.line, 2);
+        assert_eq!(frontmatter.span.start.column, 1);
+
+        let script = file.script.unwrap();
+        assert_eq!(script.attr("lang"), Some("ts"));
+
+        let template = file.template.unwrap();
+        assert!(
+            template
+                .content
+                .contains("<template><i>nested</i></template>")
+        );
+        assert!(template.content.contains("<div>{title}</div>"));
+        assert_eq!(template.span.start.line, 10);
+        assert_eq!(template.source_line(3), 12);
+
+        let style = file.style.unwrap();
+        assert!(style.has_attr("scoped"));
+        assert_eq!(style.attr("scoped"), None);
+    }
+
+    #[test]
+    fn parse_script_imports() {
+        let file = parse(SOURCE).unwrap();
+        assert_eq!(file.imports.len(), 2);
+        assert_eq!(file.imports[0].name, "Meta");
+        assert!(file.imports[0].is_rsx());
+        assert!(!file.import("NewsApp").unwrap().is_rsx());
+    }
+
+    #[test]
+    fn parse_without_frontmatter() {
+        let file = parse("<template>\n    <meta charset=\"UTF-8\" />\n</template>\n").unwrap();
+        assert!(file.frontmatter.is_none());
+        assert!(file.script.is_none());
+        assert!(file.template.is_some());
+    }
+
+    #[test]
+    fn error_on_unterminated_frontmatter() {
+        let err = parse("---\nfn main() {}\n<template></template>").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 1));
+        assert!(err.message.contains("frontmatter"));
+    }
+
+    #[test]
+    fn error_on_unclosed_block() {
+        let err = parse("<script>\n</script>\n\n  <style>\ndiv {}\n").unwrap_err();
+        assert_eq!((err.line, err.column), (4, 3));
+        assert_eq!(err.to_string(), "4:3: unclosed <style> block");
+    }
+
+    #[test]
+    fn error_on_unexpected_content() {
+        let err = parse("<template></template>\n<div></div>").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 1));
+
+        let err = parse("<template></template>\ntext").unwrap_err();
+        assert_eq!(err.message, "unexpected content outside of a block");
+    }
+
+    #[test]
+    fn error_on_duplicate_block() {
+        let err = parse("<style></style>\n<style></style>").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 1));
+    }
+}
@@ -1 +1,504 @@
-pub struct Props;
+//! 页面`get_server_props`函数的注册与执行
+//!
+//! 生成的页面模块把各自的props函数注册到[`PropsRegistry`]，路由在渲染页面前调用它，
+//! 2xx响应的JSON响应体作为Handlebars渲染上下文，其他状态码的响应直接返回给客户端
+//!
+//! props函数可以是`fn(Request)`或`fn(Request, Context)`，见[`PropsHandler`]；
+//! 返回值可以是响应，也可以是实现了[`Props`]的结构体
+//!
+//! 动态路由的页面还可以注册`get_static_paths`，列出静态导出时需要预渲染的路由参数；
+//! 声明了`REVALIDATE`的页面注册缓存有效期，见[`isr`](crate::isr)
+
+use crate::context::Context;
+use crate::request::Request;
+use crate::response::{Response, ServerResponse};
+use crate::template::island::{Islands, LOADER};
+use actix_web::{HttpResponse, body, http::header};
+use anyhow::{Result, anyhow};
+use futures_util::future::LocalBoxFuture;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 页面的类型化props，通过`#[derive(Serialize, Props)]`实现
+///
+/// props序列化后作为模板渲染上下文和客户端hydration数据，代码生成时会为frontmatter中
+/// 派生了`Props`的结构体生成同名字段的TypeScript类型
+///
+/// ```ignore
+/// #[derive(Serialize, Props)]
+/// pub struct UserProps {
+///     id: u64,
+///     title: String,
+/// }
+///
+/// pub async fn get_server_props(req: Request) -> Result<UserProps> {
+///     Ok(UserProps { id: req.param("id")?, title: "用户详情".to_string() })
+/// }
+/// ```
+pub trait Props: Serialize {
+    /// 序列化为渲染上下文，props必须序列化为JSON对象
+    fn to_context(&self) -> Result<Map<String, Value>> {
+        match serde_json::to_value(self)? {
+            Value::Object(map) => Ok(map),
+            other => Err(anyhow!(
+                "props must serialize to a JSON object, got {}",
+                json_kind(&other)
+            )),
+        }
+    }
+}
+
+/// JSON值的类型名称，用于错误信息
+fn json_kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// props函数的返回值，`get_server_props`可以返回`Response`、`ServerResponse`、
+/// 实现了[`Props`]的结构体或它们的`Result`
+pub trait IntoPropsResponse {
+    fn into_props_response(self) -> Result<Response>;
+}
+
+impl IntoPropsResponse for Response {
+    fn into_props_response(self) -> Result<Response> {
+        Ok(self)
+    }
+}
+
+impl IntoPropsResponse for ServerResponse {
+    fn into_props_response(self) -> Result<Response> {
+        Ok(Response::from(self))
+    }
+}
+
+impl<P: Props> IntoPropsResponse for P {
+    fn into_props_response(self) -> Result<Response> {
+        Ok(Response::from(ServerResponse::json(self.to_context()?)?))
+    }
+}
+
+impl<T, E> IntoPropsResponse for Result<T, E>
+where
+    T: IntoPropsResponse,
+    E: Into<anyhow::Error>,
+{
+    fn into_props_response(self) -> Result<Response> {
+        self.map_err(Into::into)?.into_props_response()
+    }
+}
+
+/// 可注册为props函数的异步函数，`Args`区分是否接收[`Context`]参数
+pub trait PropsHandler<Args>: Send + Sync + 'static {
+    fn call(&self, req: Request, ctx: Context) -> LocalBoxFuture<'static, Result<Response>>;
+}
+
+impl<F, Fut> PropsHandler<(Request,)> for F
+where
+    F: Fn(Request) -> Fut + Send + Sync + 'static,
+    Fut: Future + 'static,
+    Fut::Output: IntoPropsResponse,
+{
+    fn call(&self, req: Request, _: Context) -> LocalBoxFuture<'static, Result<Response>> {
+        let fut = self(req);
+        Box::pin(async move { fut.await.into_props_response() })
+    }
+}
+
+impl<F, Fut> PropsHandler<(Request, Context)> for F
+where
+    F: Fn(Request, Context) -> Fut + Send + Sync + 'static,
+    Fut: Future + 'static,
+    Fut::Output: IntoPropsResponse,
+{
+    fn call(&self, req: Request, ctx: Context) -> LocalBoxFuture<'static, Result<Response>> {
+        let fut = self(req, ctx);
+        Box::pin(async move { fut.await.into_props_response() })
+    }
+}
+
+/// `get_static_paths`的返回值，每一项为一组路由参数，如`vec![json!({"id": 1})]`，
+/// catch-all参数的值可以是路径字符串或各段组成的数组
+pub trait IntoStaticPaths {
+    fn into_static_paths(self) -> Result<Vec<Map<String, Value>>>;
+}
+
+impl<T: Serialize> IntoStaticPaths for Vec<T> {
+    fn into_static_paths(self) -> Result<Vec<Map<String, Value>>> {
+        self.into_iter()
+            .map(|params| match serde_json::to_value(params)? {
+                Value::Object(map) => Ok(map),
+                other => Err(anyhow!(
+                    "static path params must serialize to a JSON object, got {}",
+                    json_kind(&other)
+                )),
+            })
+            .collect()
+    }
+}
+
+impl<T, E> IntoStaticPaths for Result<T, E>
+where
+    T: IntoStaticPaths,
+    E: Into<anyhow::Error>,
+{
+    fn into_static_paths(self) -> Result<Vec<Map<String, Value>>> {
+        self.map_err(Into::into)?.into_static_paths()
+    }
+}
+
+/// 类型擦除后的`get_static_paths`函数
+type StaticPathsFn =
+    Arc<dyn Fn() -> LocalBoxFuture<'static, Result<Vec<Map<String, Value>>>> + Send + Sync>;
+
+/// 类型擦除后的props函数
+type PropsFn =
+    Arc<dyn Fn(Request, Context) -> LocalBoxFuture<'static, Result<Response>> + Send + Sync>;
+
+/// 擦除props函数的类型
+fn erase<H, Args>(handler: H) -> PropsFn
+where
+    H: PropsHandler<Args>,
+{
+    Arc::new(move |req, ctx| handler.call(req, ctx))
+}
+
+/// 页面名称到props函数的注册表，组件的props函数按组件ID单独注册
+#[derive(Clone, Default)]
+pub struct PropsRegistry {
+    fns: HashMap<String, PropsFn>,
+    components: HashMap<String, PropsFn>,
+    static_paths: HashMap<String, StaticPathsFn>,
+    revalidate: HashMap<String, Duration>,
+}
+
+impl PropsRegistry {
+    /// 创建空的注册表
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 注册页面的props函数，`page`为页面模板名称，如`users/[id]`
+    pub fn register<H, Args>(&mut self, page: impl Into<String>, handler: H)
+    where
+        H: PropsHandler<Args>,
+    {
+        self.fns.insert(page.into(), erase(handler));
+    }
+
+    /// 注册组件的props函数，`id`为组件ID，如`src_components_header`
+    pub fn register_component<H, Args>(&mut self, id: impl Into<String>, handler: H)
+    where
+        H: PropsHandler<Args>,
+    {
+        self.components.insert(id.into(), erase(handler));
+    }
+
+    /// 注册动态路由页面的`get_static_paths`函数，静态导出时按返回的参数渲染页面
+    pub fn register_static_paths<F, Fut>(&mut self, page: impl Into<String>, f: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future + 'static,
+        Fut::Output: IntoStaticPaths,
+    {
+        self.static_paths.insert(
+            page.into(),
+            Arc::new(move || {
+                let fut = f();
+                Box::pin(async move { fut.await.into_static_paths() })
+            }),
+        );
+    }
+
+    /// 调用页面的`get_static_paths`函数，未注册时返回`None`
+    pub async fn static_paths(&self, page: &str) -> Option<Result<Vec<Map<String, Value>>>> {
+        let f = self.static_paths.get(page)?.clone();
+        Some(f().await)
+    }
+
+    /// 注册页面的缓存有效期，过期后在后台再生成
+    pub fn register_revalidate(&mut self, page: impl Into<String>, revalidate: Duration) {
+        self.revalidate.insert(page.into(), revalidate);
+    }
+
+    /// 页面的缓存有效期，未注册时页面不缓存
+    pub fn revalidate(&self, page: &str) -> Option<Duration> {
+        self.revalidate.get(page).copied()
+    }
+
+    /// 页面是否注册了props函数
+    pub fn contains(&self, page: &str) -> bool {
+        self.fns.contains_key(page)
+    }
+
+    /// 调用页面的props函数，未注册时返回`None`
+    pub async fn call(&self, page: &str, req: Request, ctx: Context) -> Option<Result<Response>> {
+        let f = self.fns.get(page)?.clone();
+        Some(f(req, ctx).await)
+    }
+
+    /// 组件是否注册了props函数
+    pub fn contains_component(&self, id: &str) -> bool {
+        self.components.contains_key(id)
+    }
+
+    /// 调用组件的props函数，未注册时返回`None`
+    pub async fn call_component(
+        &self,
+        id: &str,
+        req: Request,
+        ctx: Context,
+    ) -> Option<Result<Response>> {
+        let f = self.components.get(id)?.clone();
+        Some(f(req, ctx).await)
+    }
+
+    /// 已注册的页面数量
+    pub fn len(&self) -> usize {
+        self.fns.len()
+    }
+
+    /// 是否没有注册任何页面
+    pub fn is_empty(&self) -> bool {
+        self.fns.is_empty()
+    }
+}
+
+/// 服务端数据所在`<script>`元素的ID，与编译器的`RSX_SERVER_PROPS_ID`一致
+pub const SERVER_PROPS_ID: &str = "__rsx_script__";
+
+/// 服务端数据中岛props所在的键，值为岛ID到props的映射
+pub const ISLANDS_KEY: &str = "__rsx_islands";
+
+/// 生成服务端数据的`<script type="application/json">`元素，有岛时再加上岛的加载脚本
+///
+/// `page`为页面的hydration数据，页面props在顶层，岛的props放在[`ISLANDS_KEY`]下；
+/// 没有hydration数据也没有岛时为空
+pub fn server_props_script(page: Option<&Value>, islands: &Islands) -> Result<String> {
+    if page.is_none() && islands.is_empty() {
+        return Ok(String::new());
+    }
+    let mut data = match page {
+        Some(Value::Object(data)) => data.clone(),
+        Some(other) => return Err(anyhow!("hydration data must be an object, got {other}")),
+        None => Map::new(),
+    };
+    if !islands.is_empty() {
+        data.insert(
+            ISLANDS_KEY.to_string(),
+            Value::Object(islands.props().clone()),
+        );
+    }
+    let json = script_json(&data)?;
+    let loader = if islands.is_empty() { "" } else { LOADER };
+    Ok(format!(
+        r#"<script id="{SERVER_PROPS_ID}" type="application/json">{json}</script>{loader}"#
+    ))
+}
+
+/// 序列化为可以安全嵌入`<script>`的JSON，转义`<`、`>`、`&`和JavaScript中的行分隔符
+pub fn script_json<T: Serialize + ?Sized>(data: &T) -> serde_json::Result<String> {
+    Ok(serde_json::to_string(data)?
+        .replace('<', "\\u003c")
+        .replace('>', "\\u003e")
+        .replace('&', "\\u0026")
+        .replace('\u{2028}', "\\u2028")
+        .replace('\u{2029}', "\\u2029"))
+}
+
+/// 将服务端数据脚本插入到`</body>`之前，没有`</body>`时追加到末尾
+pub fn inject_server_props(
+    html: &mut String,
+    page: Option<&Value>,
+    islands: &Islands,
+) -> Result<()> {
+    let script = server_props_script(page, islands)?;
+    match html.rfind("</body>") {
+        Some(index) => html.insert_str(index, &script),
+        None => html.push_str(&script),
+    }
+    Ok(())
+}
+
+/// props响应的处理结果
+pub enum PropsOutcome {
+    /// 使用JSON数据渲染页面，`headers`会附加到页面响应上
+    Render {
+        data: Value,
+        headers: header::HeaderMap,
+    },
+    /// 非2xx响应，跳过渲染直接返回
+    Respond(HttpResponse),
+}
+
+/// 解析props函数返回的响应
+pub async fn resolve(response: Response) -> Result<PropsOutcome> {
+    let response = HttpResponse::from(response);
+    if !response.status().is_success() {
+        return Ok(PropsOutcome::Respond(response));
+    }
+    let (response, body) = response.into_parts();
+    let bytes = body::to_bytes(body)
+        .await
+        .map_err(|err| anyhow!("failed to read props body: {err}"))?;
+    let data = if bytes.iter().all(u8::is_ascii_whitespace) {
+        Value::Object(Map::new())
+    } else {
+        serde_json::from_slice(&bytes)
+            .map_err(|err| anyhow!("props body is not valid JSON: {err}"))?
+    };
+    let mut headers = response.headers().clone();
+    headers.remove(header::CONTENT_TYPE);
+    headers.remove(header::CONTENT_LENGTH);
+    Ok(PropsOutcome::Render { data, headers })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+    use actix_web::{FromRequest, dev::Payload};
+    use serde_json::json;
+
+    async fn request() -> (Request, Context) {
+        let req = TestRequest::default()
+            .uri("/users/42")
+            .param("id", "42")
+            .to_http_request();
+        let request = Request::from_request(&req, &mut Payload::None)
+            .await
+            .unwrap();
+        (request, Context::new(&req))
+    }
+
+    #[actix_rt::test]
+    async fn call_registered_props() {
+        let mut registry = PropsRegistry::new();
+        registry.register("each", |_req: Request| async {
+            ServerResponse::json(json!({"title": "列表"}))
+        });
+        assert!(registry.contains("each"));
+        let (req, ctx) = request().await;
+        assert!(registry.call("missing", req, ctx).await.is_none());
+
+        let (req, ctx) = request().await;
+        let response = registry.call("each", req, ctx).await.unwrap();
+        let PropsOutcome::Render { data, .. } = resolve(response.unwrap()).await.unwrap() else {
+            panic!("expected render");
+        };
+        assert_eq!(data, json!({"title": "列表"}));
+    }
+
+    #[actix_rt::test]
+    async fn short_circuit_non_success() {
+        let response = ServerResponse::new(StatusCode::NOT_FOUND).into();
+        let PropsOutcome::Respond(res) = resolve(response).await.unwrap() else {
+            panic!("expected respond");
+        };
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_rt::test]
+    async fn keep_headers_and_reject_invalid_body() {
+        let response = ServerResponse::text(String::new())
+            .header(
+                header::CACHE_CONTROL,
+                header::HeaderValue::from_static("no-store"),
+            )
+            .into();
+        let PropsOutcome::Render { data, headers } = resolve(response).await.unwrap() else {
+            panic!("expected render");
+        };
+        assert_eq!(data, json!({}));
+        assert_eq!(headers.get(header::CACHE_CONTROL).unwrap(), "no-store");
+        assert!(headers.get(header::CONTENT_TYPE).is_none());
+
+        let response = ServerResponse::text("not json".to_string()).into();
+        assert!(resolve(response).await.is_err());
+    }
+
+    #[actix_rt::test]
+    async fn propagate_props_errors() {
+        let mut registry = PropsRegistry::new();
+        registry.register("user", |req: Request| async move {
+            let id = req.param::<u64>("name")?;
+            ServerResponse::json(json!({ "id": id })).map_err(anyhow::Error::from)
+        });
+        let (req, ctx) = request().await;
+        let Some(Err(err)) = registry.call("user", req, ctx).await else {
+            panic!("expected props error");
+        };
+        assert!(err.to_string().contains("`name`"));
+    }
+
+    #[derive(Serialize)]
+    struct UserProps {
+        id: u64,
+        name: &'static str,
+    }
+
+    impl Props for UserProps {}
+
+    #[actix_rt::test]
+    async fn return_typed_props() {
+        let mut registry = PropsRegistry::new();
+        registry.register("user", |req: Request| async move {
+            anyhow::Ok(UserProps {
+                id: req.param("id")?,
+                name: "michael",
+            })
+        });
+        let (req, ctx) = request().await;
+        let response = registry.call("user", req, ctx).await.unwrap();
+        let PropsOutcome::Render { data, .. } = resolve(response.unwrap()).await.unwrap() else {
+            panic!("expected render");
+        };
+        assert_eq!(data, json!({"id": 42, "name": "michael"}));
+
+        #[derive(Serialize)]
+        struct Count(u32);
+        impl Props for Count {}
+        let err = Count(1).into_props_response().err().unwrap();
+        assert!(err.to_string().contains("got number"), "{err}");
+    }
+
+    #[test]
+    fn escape_hydration_payload() {
+        let mut html = "<html><body><p></p></body></html>".to_string();
+        let page = json!({"html": "</script><b>&"});
+        inject_server_props(&mut html, Some(&page), &Islands::default()).unwrap();
+        assert_eq!(
+            html,
+            r#"<html><body><p></p><script id="__rsx_script__" type="application/json">{"html":"\u003c/script\u003e\u003cb\u003e\u0026"}</script></body></html>"#
+        );
+        assert_eq!(server_props_script(None, &Islands::default()).unwrap(), "");
+        assert!(server_props_script(Some(&json!([1])), &Islands::default()).is_err());
+    }
+
+    #[actix_rt::test]
+    async fn inject_context_argument() {
+        let mut registry = PropsRegistry::new();
+        registry.register("user", |_req: Request, ctx: Context| async move {
+            let id = ctx.param::<u64>("id")?;
+            ServerResponse::json(json!({ "id": id, "user": ctx.user() }))
+                .map_err(anyhow::Error::from)
+        });
+        let (req, ctx) = request().await;
+        let response = registry.call("user", req, ctx).await.unwrap();
+        let PropsOutcome::Render { data, .. } = resolve(response.unwrap()).await.unwrap() else {
+            panic!("expected render");
+        };
+        assert_eq!(data, json!({"id": 42, "user": null}));
+    }
+}
@@ -1,9 +1,12 @@
 use crate::header::Header;
+use crate::params::{ParamError, Params};
+use actix_web::cookie::Cookie;
 use actix_web::http::{Method, Uri};
 use actix_web::{Error, FromRequest, HttpRequest, dev::Payload, web::Bytes};
 use futures_util::future::LocalBoxFuture;
 use serde::de::DeserializeOwned;
 use serde_json;
+use std::str::FromStr;
 
 /// 按web标准实现Request
 /// https://developer.mozilla.org/zh-CN/docs/Web/API/Request
@@ -12,6 +15,8 @@ pub struct Request {
     method: Method,
     url: Uri,
     headers: Header,
+    params: Params,
+    cookies: Vec<Cookie<'static>>,
     body: Bytes,
     body_used: bool,
 }
@@ -32,6 +37,33 @@ impl Request {
         &self.headers
     }
 
+    /// 获取请求携带的全部cookie
+    pub fn cookies(&self) -> Vec<Cookie<'static>> {
+        self.cookies.clone()
+    }
+
+    /// 获取指定名称的cookie值
+    pub fn cookie(&self, name: &str) -> Option<&str> {
+        self.cookies
+            .iter()
+            .find(|cookie| cookie.name() == name)
+            .map(Cookie::value)
+    }
+
+    /// 获取动态路由捕获的全部参数
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
+    /// 获取动态路由参数并解析为指定类型，如`users/[id].rsx`中的`req.param::<u64>("id")`
+    pub fn param<T>(&self, name: &str) -> Result<T, ParamError>
+    where
+        T: FromStr,
+        T::Err: std::fmt::Display,
+    {
+        self.params.get_as(name)
+    }
+
     /// 检查URL是否有效
     pub fn is_invalid_url(&self) -> bool {
         self.url.to_string().starts_with("http://") || self.url.to_string().starts_with("https://")
@@ -84,11 +116,18 @@ impl FromRequest for Request {
             let method = req_clone.method().clone();
             let url = req_clone.uri().to_owned();
             let headers = Header::from(req_clone.headers().clone());
+            let params = Params::from_request(&req_clone);
+            let cookies = req_clone
+                .cookies()
+                .map(|cookies| cookies.clone())
+                .unwrap_or_default();
             let body = Bytes::from_request(&req_clone, &mut payload_clone).await?;
             Ok(Request {
                 method,
                 url,
                 headers,
+                params,
+                cookies,
                 body,
                 body_used: false,
             })
@@ -125,11 +164,9 @@ mod tests {
 
     #[actix_rt::test]
     async fn consume_request_body_as_text() {
-        let req = TestRequest::default()
+        let (req, mut payload) = TestRequest::default()
             .set_payload(Bytes::from_static(b"Hello, world!"))
-            .to_http_request();
-
-        let mut payload = Payload::None;
+            .to_http_parts();
         let request = Request::from_request(&req, &mut payload).await.unwrap();
 
         let text = request.text().await.unwrap();
@@ -138,12 +175,10 @@ mod tests {
 
     #[actix_rt::test]
     async fn consume_request_body_as_json() {
-        let req = TestRequest::default()
+        let (req, mut payload) = TestRequest::default()
             .insert_header((header::CONTENT_TYPE, "application/json"))
             .set_payload(Bytes::from_static(b"{\"key\":\"value\"}"))
-            .to_http_request();
-
-        let mut payload = Payload::None;
+            .to_http_parts();
         let request = Request::from_request(&req, &mut payload).await.unwrap();
 
         let json: serde_json::Value = request.json().await.unwrap();
@@ -152,11 +187,9 @@ mod tests {
 
     #[actix_rt::test]
     async fn consume_request_body_as_bytes() {
-        let req = TestRequest::default()
+        let (req, mut payload) = TestRequest::default()
             .set_payload(Bytes::from_static(b"binary data"))
-            .to_http_request();
-
-        let mut payload = Payload::None;
+            .to_http_parts();
         let request = Request::from_request(&req, &mut payload).await.unwrap();
 
         let bytes = request.bytes().await.unwrap();
@@ -185,4 +218,31 @@ mod tests {
         let result = Request::from_request(&req, &mut payload).await;
         assert!(result.is_ok());
     }
+
+    #[actix_rt::test]
+    async fn read_request_cookies() {
+        let req = TestRequest::default()
+            .insert_header((header::COOKIE, "token=abc; theme=dark"))
+            .to_http_request();
+
+        let mut payload = Payload::None;
+        let request = Request::from_request(&req, &mut payload).await.unwrap();
+        assert_eq!(request.cookies().len(), 2);
+        assert_eq!(request.cookie("token"), Some("abc"));
+        assert_eq!(request.cookie("missing"), None);
+    }
+
+    #[actix_rt::test]
+    async fn read_typed_route_params() {
+        let req = TestRequest::default()
+            .uri("/users/42")
+            .param("id", "42")
+            .to_http_request();
+
+        let mut payload = Payload::None;
+        let request = Request::from_request(&req, &mut payload).await.unwrap();
+        assert_eq!(request.params().get("id"), Some("42"));
+        assert_eq!(request.param::<u64>("id").unwrap(), 42);
+        assert!(request.param::<u64>("name").is_err());
+    }
 }
@@ -1,10 +1,12 @@
 use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::web::Bytes;
 use actix_web::{
     HttpResponse,
     body::{self, BoxBody, MessageBody},
     http::{StatusCode, header},
 };
 use anyhow::Result;
+use futures_util::Stream;
 use http::HeaderMap;
 use reqwest::Response as ReqwestResponse;
 use serde::{Serialize, de::DeserializeOwned};
@@ -123,6 +125,25 @@ impl ServerResponse {
         res.body(BoxBody::new(body))
     }
 
+    /// 创建一个 HTML 响应
+    pub fn html(body: String) -> Self {
+        let mut res = Self::text(body);
+        res.headers.insert(
+            header::CONTENT_TYPE,
+            header::HeaderValue::from_static("text/html; charset=utf-8"),
+        );
+        res
+    }
+
+    /// 创建一个流式 HTML 响应，流中的每一块生成后立即发送给客户端
+    pub fn html_stream<S, E>(stream: S) -> Self
+    where
+        S: Stream<Item = Result<Bytes, E>> + 'static,
+        E: Into<Box<dyn std::error::Error>> + 'static,
+    {
+        Self::html(String::new()).body(BoxBody::new(body::BodyStream::new(stream)))
+    }
+
     /// 创建一个 JSON 响应
     pub fn json<T: Serialize>(value: T) -> Result<Self, serde_json::Error> {
         let body = serde_json::to_string(&value)?;
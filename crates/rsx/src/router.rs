@@ -1 +1,924 @@
-pub struct Router;
+use crate::config::Config;
+use crate::context::{Context, ContextError};
+use crate::dev;
+use crate::error::{self, ERROR_ID_HEADER, SourceExcerpt, Stage};
+use crate::isr::{self, Isr};
+use crate::manifest::{self, Manifest};
+use crate::params::ParamError;
+use crate::props::{self, PropsOutcome, PropsRegistry};
+use crate::request::Request;
+use crate::response::{Response, ServerResponse};
+use crate::stream;
+use crate::template::{COMPONENTS_KEY, TemplateEngine, normalize};
+use actix_web::{HttpResponse, Scope, http::StatusCode, web};
+use anyhow::Result;
+use futures_util::FutureExt;
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use walkdir::WalkDir;
+
+/// 路由路径中的一段
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", content = "name", rename_all = "snake_case")]
+pub enum Segment {
+    /// 静态路径，如`users`
+    Static(String),
+    /// 动态参数，如`[id]`
+    Dynamic(String),
+    /// catch-all参数，匹配一级或多级路径，如`[...slug]`
+    CatchAll(String),
+    /// 可选catch-all参数，匹配零级或多级路径，如`[[...slug]]`
+    OptionalCatchAll(String),
+}
+
+impl Segment {
+    /// 解析文件名或目录名
+    pub fn parse(name: &str) -> Self {
+        if let Some(param) = name
+            .strip_prefix("[[...")
+            .and_then(|rest| rest.strip_suffix("]]"))
+        {
+            Segment::OptionalCatchAll(param.to_string())
+        } else if let Some(param) = name
+            .strip_prefix("[...")
+            .and_then(|rest| rest.strip_suffix(']'))
+        {
+            Segment::CatchAll(param.to_string())
+        } else if let Some(param) = name
+            .strip_prefix('[')
+            .and_then(|rest| rest.strip_suffix(']'))
+        {
+            Segment::Dynamic(param.to_string())
+        } else {
+            Segment::Static(name.to_string())
+        }
+    }
+
+    /// 匹配优先级，值越小越优先
+    fn rank(&self) -> u8 {
+        match self {
+            Segment::Static(_) => 0,
+            Segment::Dynamic(_) => 1,
+            Segment::CatchAll(_) => 2,
+            Segment::OptionalCatchAll(_) => 3,
+        }
+    }
+
+    /// 是否为catch-all参数
+    fn is_catch_all(&self) -> bool {
+        matches!(self, Segment::CatchAll(_) | Segment::OptionalCatchAll(_))
+    }
+
+    /// 转换为actix的路径模式
+    fn pattern(&self) -> String {
+        match self {
+            Segment::Static(name) => name.clone(),
+            Segment::Dynamic(name) => format!("{{{name}}}"),
+            Segment::CatchAll(name) | Segment::OptionalCatchAll(name) => format!("{{{name}:.+}}"),
+        }
+    }
+}
+
+impl std::fmt::Display for Segment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Segment::Static(name) => write!(f, "{name}"),
+            Segment::Dynamic(name) => write!(f, "[{name}]"),
+            Segment::CatchAll(name) => write!(f, "[...{name}]"),
+            Segment::OptionalCatchAll(name) => write!(f, "[[...{name}]]"),
+        }
+    }
+}
+
+/// 页面路由
+#[derive(Debug, Clone, Serialize)]
+pub struct Route {
+    /// 路由路径，如`/each`、`/users/[id]`
+    pub path: String,
+    /// 匹配的全部actix路径模式，如`/each`和`/each.html`
+    pub paths: Vec<String>,
+    /// 路由路径的各段，不含`index`
+    pub segments: Vec<Segment>,
+    /// 页面模板名称，为页面相对pages目录的路径，如`users/index`
+    pub name: String,
+    /// 页面源文件
+    pub file: PathBuf,
+}
+
+impl Route {
+    /// 根据页面相对pages目录的路径创建路由
+    pub fn from_page(relative: &Path, file: PathBuf) -> Result<Self> {
+        let names = relative
+            .with_extension("")
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        let name = names.join("/");
+        let is_index = names.last().is_some_and(|last| last == "index");
+        let segments = names[..names.len() - usize::from(is_index)]
+            .iter()
+            .map(|name| Segment::parse(name))
+            .collect::<Vec<_>>();
+        if let Some(pos) = segments.iter().position(Segment::is_catch_all)
+            && pos + 1 != segments.len()
+        {
+            anyhow::bail!(
+                "catch-all segment `{}` must be the last segment of {}",
+                segments[pos],
+                relative.display()
+            );
+        }
+
+        let join = |segments: &[Segment]| {
+            let path = segments
+                .iter()
+                .map(Segment::pattern)
+                .collect::<Vec<_>>()
+                .join("/");
+            format!("/{path}")
+        };
+        let mut paths = Vec::new();
+        match segments.last() {
+            Some(Segment::OptionalCatchAll(_)) => {
+                paths.push(join(&segments[..segments.len() - 1]));
+                paths.push(join(&segments));
+            }
+            Some(Segment::CatchAll(_)) => paths.push(join(&segments)),
+            _ => {
+                let base = join(&segments);
+                let html = if is_index {
+                    format!("{}/index.html", base.trim_end_matches('/'))
+                } else {
+                    format!("{base}.html")
+                };
+                // 动态参数也能匹配`.html`后缀，需要先匹配`.html`路径
+                if segments.iter().any(|s| matches!(s, Segment::Dynamic(_))) {
+                    paths = vec![html, base];
+                } else {
+                    paths = vec![base, html];
+                }
+            }
+        }
+        let path = format!(
+            "/{}",
+            segments
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("/")
+        );
+        Ok(Self {
+            path,
+            paths,
+            segments,
+            name,
+            file,
+        })
+    }
+
+    /// 是否包含动态参数
+    pub fn is_dynamic(&self) -> bool {
+        self.segments
+            .iter()
+            .any(|segment| !matches!(segment, Segment::Static(_)))
+    }
+
+    /// 用路由参数生成请求路径，如`/users/[id]`和`{"id": 7}`生成`/users/7`
+    ///
+    /// catch-all参数的值可以是路径字符串或各段组成的数组，可选catch-all参数可以省略；
+    /// 动态参数的值不能包含`/`。每段都经过百分号编码，如`{"id": "a b"}`生成`/users/a%20b`
+    pub fn url(&self, params: &Map<String, Value>) -> Result<String> {
+        let value = |name: &str| match params.get(name) {
+            Some(Value::String(value)) => Some(value.clone()),
+            Some(Value::Number(value)) => Some(value.to_string()),
+            Some(Value::Array(values)) => Some(
+                values
+                    .iter()
+                    .map(|value| match value {
+                        Value::String(value) => value.clone(),
+                        other => other.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("/"),
+            ),
+            _ => None,
+        };
+        let required = |name: &str| {
+            value(name)
+                .filter(|value| !value.is_empty())
+                .ok_or_else(|| anyhow::anyhow!("missing param `{name}` for route {}", self.path))
+        };
+        let encode = |part: &str| utf8_percent_encode(part, PATH_SEGMENT).to_string();
+        let mut parts = Vec::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Static(name) => parts.push(encode(name)),
+                Segment::Dynamic(name) => {
+                    let value = required(name)?;
+                    if value.contains('/') {
+                        anyhow::bail!(
+                            "param `{name}` for route {} cannot contain `/`: {value}",
+                            self.path
+                        );
+                    }
+                    parts.push(encode(&value));
+                }
+                Segment::CatchAll(name) => parts.extend(required(name)?.split('/').map(encode)),
+                Segment::OptionalCatchAll(name) => {
+                    if let Some(value) = value(name).filter(|value| !value.is_empty()) {
+                        parts.extend(value.split('/').map(encode))
+                    }
+                }
+            }
+        }
+        Ok(format!("/{}", parts.join("/")))
+    }
+
+    /// 路由排序：逐段比较，静态 > 动态 > catch-all > 可选catch-all，再按路径字典序
+    fn cmp_rank(&self, other: &Self) -> std::cmp::Ordering {
+        let rank = |route: &Self| route.segments.iter().map(Segment::rank).collect::<Vec<_>>();
+        rank(self)
+            .cmp(&rank(other))
+            .then_with(|| self.path.cmp(&other.path))
+    }
+}
+
+/// 路径段中需要百分号编码的字符，只保留字母、数字和`-._~`
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// 扫描pages目录下的全部.rsx页面，返回按匹配优先级排序的路由表，
+/// 以`_`开头的文件如`_layout.rsx`不是页面
+pub fn scan(pages: &Path) -> Result<Vec<Route>> {
+    let mut routes = Vec::new();
+    for entry in WalkDir::new(pages).sort_by_file_name() {
+        let entry = entry?;
+        let path = entry.path();
+        if !entry.file_type().is_file()
+            || path.extension().is_none_or(|ext| ext != "rsx")
+            || entry.file_name().to_string_lossy().starts_with('_')
+            || entry.depth() == 1 && is_error_page(path)
+        {
+            continue;
+        }
+        let relative = path.strip_prefix(pages)?;
+        routes.push(Route::from_page(relative, path.to_path_buf())?);
+    }
+    // actix按注册顺序匹配，静态路由需要排在动态路由之前
+    routes.sort_by(Route::cmp_rank);
+    Ok(routes)
+}
+
+/// pages目录顶层的错误页面：`404.rsx`和`500.rsx`用于各自的状态码，
+/// `_error.rsx`用于其余状态码及缺少对应页面的情况
+pub const ERROR_PAGES: [&str; 3] = ["404", "500", ERROR_PAGE];
+
+/// 通用错误页面的名称
+pub const ERROR_PAGE: &str = "_error";
+
+/// 是否为错误页面的文件
+fn is_error_page(path: &Path) -> bool {
+    path.file_stem()
+        .is_some_and(|stem| ERROR_PAGES.iter().any(|name| stem == *name))
+}
+
+/// pages目录中存在的错误页面，为页面名称和文件
+pub fn error_pages(pages: &Path) -> Vec<(String, PathBuf)> {
+    ERROR_PAGES
+        .iter()
+        .map(|name| (name.to_string(), pages.join(format!("{name}.rsx"))))
+        .filter(|(_, file)| file.is_file())
+        .collect()
+}
+
+/// 可整体替换的模板引擎，开发模式下修改文件后替换为重新编译的引擎
+type EngineCell = Arc<RwLock<Arc<TemplateEngine>>>;
+
+/// 基于pages目录的文件系统路由
+pub struct Router {
+    /// pages目录
+    pages: PathBuf,
+    routes: Vec<Route>,
+    /// 错误页面，为页面名称和文件
+    error_pages: Vec<(String, PathBuf)>,
+    engine: EngineCell,
+    props: Arc<PropsRegistry>,
+    isr: Arc<Isr>,
+}
+
+impl Router {
+    /// 根据配置扫描pages目录并编译全部页面
+    ///
+    /// dist目录中有Vite清单时，岛组件和页面入口使用构建后的文件
+    pub fn new(config: &Config) -> Result<Self> {
+        let root = Path::new(config.root());
+        let mut engine = TemplateEngine::new(root)
+            .with_layouts(root.join(&config.pages), root.join(&config.layouts));
+        if let Some(manifest) = Manifest::load(config)? {
+            log::info!("vite manifest loaded from {}", manifest.dist().display());
+            engine = engine.with_manifest(manifest);
+        }
+        Self::with_engine(engine, root.join(&config.pages))
+    }
+
+    /// 扫描指定的pages目录，`root`为项目根目录，命名布局位于pages同级的`layouts`目录
+    pub fn from_dir(pages: impl AsRef<Path>, root: impl AsRef<Path>) -> Result<Self> {
+        let pages = pages.as_ref();
+        let layouts = pages
+            .parent()
+            .map_or_else(|| PathBuf::from("layouts"), |dir| dir.join("layouts"));
+        Self::from_dirs(pages, layouts, root)
+    }
+
+    /// 扫描指定的pages目录，`layouts`为命名布局目录，`root`为项目根目录
+    pub fn from_dirs(
+        pages: impl AsRef<Path>,
+        layouts: impl AsRef<Path>,
+        root: impl AsRef<Path>,
+    ) -> Result<Self> {
+        let engine = TemplateEngine::new(root.as_ref()).with_layouts(&pages, layouts);
+        Self::with_engine(engine, pages)
+    }
+
+    /// 扫描pages目录，用配置好的模板引擎编译全部页面
+    fn with_engine(mut engine: TemplateEngine, pages: impl AsRef<Path>) -> Result<Self> {
+        let routes = scan(pages.as_ref())?;
+        for route in &routes {
+            engine.register_page(&route.name, &route.file)?;
+            log::debug!("route {} -> {}", route.path, route.file.display());
+        }
+        let error_pages = error_pages(pages.as_ref());
+        for (name, file) in &error_pages {
+            engine.register_page(name, file)?;
+            log::debug!("error page {name} -> {}", file.display());
+        }
+        Ok(Self {
+            pages: pages.as_ref().to_path_buf(),
+            routes,
+            error_pages,
+            engine: Arc::new(RwLock::new(Arc::new(engine))),
+            props: Arc::new(PropsRegistry::new()),
+            isr: Arc::new(Isr::default()),
+        })
+    }
+
+    /// 设置页面的props函数注册表
+    pub fn with_props(mut self, props: PropsRegistry) -> Self {
+        self.props = Arc::new(props);
+        self
+    }
+
+    /// 路由表
+    pub fn routes(&self) -> &[Route] {
+        &self.routes
+    }
+
+    /// 当前的模板引擎
+    pub fn engine(&self) -> Arc<TemplateEngine> {
+        self.engine
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// 开发模式，渲染的页面插入连接开发服务器的客户端脚本
+    pub fn with_dev_client(self) -> Self {
+        let engine = (*self.engine()).clone().with_dev_client();
+        *self.engine.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(engine);
+        self
+    }
+
+    /// 重新编译受`changed`中的.rsx文件影响的页面，返回重新编译的页面名称
+    ///
+    /// 页面文件本身或其用到的布局、组件被修改时页面受影响；编译失败时保留原来的引擎。
+    /// 重新编译后清空ISR缓存
+    ///
+    /// 路由已注册到actix，路由表不变：新增或删除页面时输出警告，需要重启才能生效，
+    /// 重启前已删除的页面仍按原来的模板渲染，见[`Router::route_changes`]
+    pub fn reload(&self, changed: &[PathBuf]) -> Result<Vec<String>> {
+        let pages_dir = normalize(&self.pages);
+        if changed
+            .iter()
+            .any(|path| normalize(path).starts_with(&pages_dir))
+        {
+            let (added, removed) = self.route_changes()?;
+            if !added.is_empty() || !removed.is_empty() {
+                log::warn!(
+                    "pages added: [{}], removed: [{}]; restart the server to update routes",
+                    added.join(", "),
+                    removed.join(", ")
+                );
+            }
+        }
+        let current = self.engine();
+        let mut engine = (*current).clone();
+        let forgotten = engine.forget(changed);
+        let changed: Vec<PathBuf> = changed.iter().map(|path| normalize(path)).collect();
+        let mut pages = Vec::new();
+        let files = self
+            .routes
+            .iter()
+            .map(|route| (&route.name, &route.file))
+            .chain(self.error_pages.iter().map(|(name, file)| (name, file)))
+            .filter(|(_, file)| file.is_file());
+        for (name, file) in files {
+            let affected = changed.contains(&normalize(file))
+                || current
+                    .page_components(name)
+                    .iter()
+                    .any(|id| forgotten.contains(id));
+            if affected {
+                engine.register_page(name, file)?;
+                pages.push(name.clone());
+            }
+        }
+        if !pages.is_empty() {
+            *self.engine.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(engine);
+            self.isr.clear();
+        }
+        Ok(pages)
+    }
+
+    /// 重新扫描pages目录，返回路由表之外新增的页面和已删除的页面，
+    /// 为路由路径或错误页面名称
+    pub fn route_changes(&self) -> Result<(Vec<String>, Vec<String>)> {
+        let mut current: Vec<(String, PathBuf)> = scan(&self.pages)?
+            .into_iter()
+            .map(|route| (route.path, route.file))
+            .collect();
+        current.extend(error_pages(&self.pages));
+        let known: Vec<(&str, &PathBuf)> = self
+            .routes
+            .iter()
+            .map(|route| (route.path.as_str(), &route.file))
+            .chain(
+                self.error_pages
+                    .iter()
+                    .map(|(name, file)| (name.as_str(), file)),
+            )
+            .collect();
+        let added = current
+            .iter()
+            .filter(|(_, file)| !known.iter().any(|(_, known)| *known == file))
+            .map(|(name, _)| name.clone())
+            .collect();
+        let removed = known
+            .iter()
+            .filter(|(_, file)| !current.iter().any(|(_, current)| current == *file))
+            .map(|(name, _)| name.to_string())
+            .collect();
+        Ok((added, removed))
+    }
+
+    /// 错误页面的名称
+    pub fn error_pages(&self) -> impl Iterator<Item = &str> {
+        self.error_pages.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// 未匹配任何路由的请求，有`404.rsx`或`_error.rsx`时渲染错误页面
+    pub fn not_found(&self) -> HttpResponse {
+        let status = StatusCode::NOT_FOUND;
+        error_response(status, error_page(&self.engine(), status, None))
+    }
+
+    /// props函数注册表
+    pub fn props(&self) -> &Arc<PropsRegistry> {
+        &self.props
+    }
+
+    /// 声明了`REVALIDATE`的页面的缓存
+    pub fn isr(&self) -> &Arc<Isr> {
+        &self.isr
+    }
+
+    /// 生成包含全部页面路由的actix Scope
+    ///
+    /// 空前缀的Scope会拦截所有请求，需要与其他服务共存时使用[`Router::configure`]
+    pub fn scope(&self) -> Scope {
+        web::scope("").configure(|cfg| self.configure(cfg))
+    }
+
+    /// 将全部页面路由注册到actix的ServiceConfig
+    ///
+    /// 声明了`REVALIDATE`的页面从缓存返回，见[`isr`](crate::isr)；
+    /// 未匹配的请求由[`Router::not_found`]处理
+    pub fn configure(&self, cfg: &mut web::ServiceConfig) {
+        for route in &self.routes {
+            let engine = self.engine.clone();
+            let props = self.props.clone();
+            let isr = self.isr.clone();
+            let revalidate = self.props.revalidate(&route.name);
+            let name = route.name.clone();
+            cfg.service(web::resource(route.paths.clone()).route(web::get().to(
+                move |req: Request, ctx: Context| {
+                    let engine = engine.read().unwrap_or_else(|e| e.into_inner()).clone();
+                    let props = props.clone();
+                    let isr = isr.clone();
+                    let name = name.clone();
+                    async move {
+                        let Some(revalidate) = revalidate else {
+                            return serve_page(engine, props, &name, req, ctx).await;
+                        };
+                        if isr::is_personalized(&ctx) {
+                            return isr::bypass(serve_page(engine, props, &name, req, ctx).await);
+                        }
+                        let path = ctx.request().path().to_string();
+                        isr.serve(&path, revalidate, move || async move {
+                            serve_page(engine, props, &name, req, ctx).await
+                        })
+                        .await
+                    }
+                },
+            )));
+        }
+        let engine = self.engine.clone();
+        cfg.default_service(web::to(move || {
+            let engine = engine.read().unwrap_or_else(|e| e.into_inner()).clone();
+            let status = StatusCode::NOT_FOUND;
+            async move { error_response(status, error_page(&engine, status, None)) }
+        }));
+    }
+}
+
+/// 渲染页面，props函数或渲染中的panic按500处理，渲染中的panic由[`catch_render`]
+/// 单独捕获，这里捕获的是props函数中的panic
+async fn serve_page(
+    engine: Arc<TemplateEngine>,
+    props: Arc<PropsRegistry>,
+    name: &str,
+    req: Request,
+    ctx: Context,
+) -> HttpResponse {
+    let page = render_page(engine.clone(), props, name, req, ctx.clone());
+    match AssertUnwindSafe(page).catch_unwind().await {
+        Ok(response) => response,
+        Err(panic) => {
+            let err = anyhow::anyhow!("page panicked: {}", panic_message(panic.as_ref()));
+            internal_error(&engine, name, None, Stage::Props, err, &ctx)
+        }
+    }
+}
+
+/// 渲染模板，panic转为错误，错误浮层据此定位到`<template>`而不是props函数
+pub(crate) fn catch_render<T>(render: impl FnOnce() -> anyhow::Result<T>) -> anyhow::Result<T> {
+    std::panic::catch_unwind(AssertUnwindSafe(render)).unwrap_or_else(|panic| {
+        Err(anyhow::anyhow!(
+            "render panicked: {}",
+            panic_message(panic.as_ref())
+        ))
+    })
+}
+
+/// panic的消息
+pub(crate) fn panic_message(panic: &(dyn std::any::Any + Send)) -> &str {
+    panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic")
+}
+
+/// 执行页面的props函数并渲染页面，有`<defer>`区块且定义了props函数的页面流式渲染
+async fn render_page(
+    engine: Arc<TemplateEngine>,
+    props: Arc<PropsRegistry>,
+    name: &str,
+    req: Request,
+    ctx: Context,
+) -> HttpResponse {
+    let params = json!(req.params());
+    let (page, components) = futures_util::future::join(
+        props.call(name, req.clone(), ctx.clone()),
+        component_props(&engine, &props, name, &req, &ctx),
+    )
+    .await;
+    let components = match components {
+        Ok(components) => components,
+        Err(response) => return response,
+    };
+    let (mut data, headers, hydrate) = match page {
+        None => (json!({}), Default::default(), false),
+        Some(Ok(response)) => match props::resolve(response).await {
+            Ok(PropsOutcome::Render { data, headers }) => (data, headers, true),
+            Ok(PropsOutcome::Respond(response)) => return response,
+            Err(err) => return internal_error(&engine, name, None, Stage::Props, err, &ctx),
+        },
+        Some(Err(err)) => return internal_error(&engine, name, None, Stage::Props, err, &ctx),
+    };
+    if let Value::Object(data) = &mut data {
+        data.entry("params").or_insert(params);
+        if !components.is_empty() {
+            data.insert(COMPONENTS_KEY.to_string(), Value::Object(components));
+        }
+    }
+    // 有延迟数据时先发送外壳，没有`<defer>`区块时等待全部延迟数据再渲染
+    let deferred = stream::Deferred::take(&ctx);
+    if !deferred.is_empty() {
+        if engine.deferred_regions(name) > 0 {
+            return stream::render_page(engine, name, data, hydrate, headers, deferred, &ctx);
+        }
+        if let Err(err) = deferred.resolve_into(&mut data).await {
+            return internal_error(&engine, name, None, Stage::Props, err, &ctx);
+        }
+    }
+    // 只有定义了props函数的页面才需要hydration数据，组件的props只在服务端使用
+    let hydration = hydrate.then(|| {
+        let mut hydration = data.clone();
+        if let Value::Object(hydration) = &mut hydration {
+            hydration.remove(COMPONENTS_KEY);
+        }
+        hydration
+    });
+    let html = catch_render(|| engine.render_hydrated(name, &data, hydration.as_ref()));
+    match html {
+        Ok(html) => {
+            let mut response = ServerResponse::html(html);
+            for (key, value) in headers {
+                response = response.append_header(key, value);
+            }
+            Response::from(response).into()
+        }
+        Err(err) => internal_error(&engine, name, None, Stage::Render, err, &ctx),
+    }
+}
+
+/// 并发执行页面`name`用到的组件的props函数，返回组件ID到props的映射，非2xx响应或出错时直接返回
+pub(crate) async fn component_props(
+    engine: &TemplateEngine,
+    props: &PropsRegistry,
+    name: &str,
+    req: &Request,
+    ctx: &Context,
+) -> Result<Map<String, Value>, HttpResponse> {
+    let calls = engine
+        .page_components(name)
+        .iter()
+        .filter(|id| props.contains_component(id))
+        .map(|id| async move {
+            let response = props.call_component(id, req.clone(), ctx.clone()).await;
+            let outcome = match response {
+                Some(Ok(response)) => props::resolve(response).await,
+                Some(Err(err)) => Err(err),
+                None => Ok(PropsOutcome::Render {
+                    data: json!({}),
+                    headers: Default::default(),
+                }),
+            };
+            (id, outcome)
+        });
+    let mut components = Map::new();
+    for (id, outcome) in futures_util::future::join_all(calls).await {
+        match outcome {
+            Ok(PropsOutcome::Render { data, .. }) => {
+                components.insert(id.clone(), data);
+            }
+            Ok(PropsOutcome::Respond(response)) => return Err(response),
+            Err(err) => {
+                return Err(internal_error(
+                    engine,
+                    name,
+                    Some(id),
+                    Stage::Props,
+                    err,
+                    ctx,
+                ));
+            }
+        }
+    }
+    Ok(components)
+}
+
+/// 记录错误并返回500，未登录错误返回401，路由参数错误返回404
+///
+/// `component`为出错的组件ID；开发模式下返回错误浮层，否则返回带错误ID的通用页面，
+/// 见[`error`](crate::error)
+pub(crate) fn internal_error(
+    engine: &TemplateEngine,
+    name: &str,
+    component: Option<&str>,
+    stage: Stage,
+    err: anyhow::Error,
+    ctx: &Context,
+) -> HttpResponse {
+    if let Some(ContextError::Unauthorized) = err.downcast_ref::<ContextError>() {
+        let status = StatusCode::UNAUTHORIZED;
+        return error_response(status, error_page(engine, status, None));
+    }
+    if let Some(err) = err.downcast_ref::<ParamError>() {
+        log::debug!("render page {name} not found: {err}");
+        let status = StatusCode::NOT_FOUND;
+        return error_response(status, error_page(engine, status, None));
+    }
+    let id = error::error_id();
+    let failed = component.unwrap_or(name);
+    log::error!("render page {name} error [{id}] in {failed}: {err:?}");
+    let status = StatusCode::INTERNAL_SERVER_ERROR;
+    let body = if engine.is_dev() {
+        let source = engine
+            .source_path(failed)
+            .and_then(|path| SourceExcerpt::read(engine.root(), &path, &err, stage));
+        let mut html = error::overlay(failed, &err, source.as_ref(), ctx.request());
+        // 修正后开发服务器通知浮层刷新
+        manifest::inject_head(&mut html, &dev::client_script(name));
+        html
+    } else {
+        error_page(engine, status, Some(&id)).unwrap_or_else(|| error::generic_page(&id))
+    };
+    HttpResponse::build(status)
+        .insert_header((ERROR_ID_HEADER, id))
+        .content_type("text/html; charset=utf-8")
+        .body(body)
+}
+
+/// 用错误页面渲染状态码为`status`的页面，没有对应的错误页面或渲染失败时返回`None`
+///
+/// 页面的props为`status`和`message`，500页面还有与日志对应的`error_id`；
+/// 错误信息可能包含内部细节，因此`message`只是状态码的标准描述
+pub(crate) fn error_page(
+    engine: &TemplateEngine,
+    status: StatusCode,
+    error_id: Option<&str>,
+) -> Option<String> {
+    let name = [status.as_str(), ERROR_PAGE]
+        .into_iter()
+        .find(|name| ERROR_PAGES.contains(name) && engine.has_template(name))?;
+    let mut data = json!({
+        "status": status.as_u16(),
+        "message": status.canonical_reason().unwrap_or_default(),
+    });
+    if let Some(id) = error_id {
+        data["error_id"] = json!(id);
+    }
+    match engine.render(name, &data) {
+        Ok(html) => Some(html),
+        Err(err) => {
+            log::error!("render error page {name} error: {err:?}");
+            None
+        }
+    }
+}
+
+/// 状态码为`status`的响应，`html`为页面内容
+fn error_response(status: StatusCode, html: Option<String>) -> HttpResponse {
+    match html {
+        Some(html) => HttpResponse::build(status)
+            .content_type("text/html; charset=utf-8")
+            .body(html),
+        None => HttpResponse::new(status),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(path: &str) -> Route {
+        Route::from_page(Path::new(path), PathBuf::from(path)).unwrap()
+    }
+
+    #[test]
+    fn map_page_to_paths() {
+        let each = route("each.rsx");
+        assert_eq!(each.name, "each");
+        assert_eq!(each.path, "/each");
+        assert_eq!(each.paths, vec!["/each", "/each.html"]);
+
+        let nested = route("users/profile.rsx");
+        assert_eq!(nested.name, "users/profile");
+        assert_eq!(nested.paths, vec!["/users/profile", "/users/profile.html"]);
+    }
+
+    #[test]
+    fn map_index_to_directory_root() {
+        let index = route("index.rsx");
+        assert_eq!(index.name, "index");
+        assert_eq!(index.paths, vec!["/", "/index.html"]);
+
+        let users = route("users/index.rsx");
+        assert_eq!(users.name, "users/index");
+        assert_eq!(users.paths, vec!["/users", "/users/index.html"]);
+
+        let reindex = route("reindex.rsx");
+        assert_eq!(reindex.paths, vec!["/reindex", "/reindex.html"]);
+    }
+
+    #[test]
+    fn map_dynamic_segments() {
+        let user = route("users/[id].rsx");
+        assert_eq!(user.name, "users/[id]");
+        assert_eq!(user.path, "/users/[id]");
+        assert_eq!(user.paths, vec!["/users/{id}.html", "/users/{id}"]);
+        assert!(user.is_dynamic());
+
+        let edit = route("users/[id]/index.rsx");
+        assert_eq!(edit.paths, vec!["/users/{id}/index.html", "/users/{id}"]);
+
+        let docs = route("docs/[...slug].rsx");
+        assert_eq!(docs.path, "/docs/[...slug]");
+        assert_eq!(docs.paths, vec!["/docs/{slug:.+}"]);
+
+        let shop = route("shop/[[...slug]].rsx");
+        assert_eq!(shop.paths, vec!["/shop", "/shop/{slug:.+}"]);
+        assert_eq!(route("[[...all]].rsx").paths, vec!["/", "/{all:.+}"]);
+    }
+
+    #[test]
+    fn fill_params_into_url() {
+        let params = |value: Value| value.as_object().unwrap().clone();
+        assert_eq!(route("index.rsx").url(&Map::new()).unwrap(), "/");
+        assert_eq!(
+            route("users/[id].rsx")
+                .url(&params(json!({"id": 7})))
+                .unwrap(),
+            "/users/7"
+        );
+        assert_eq!(
+            route("docs/[...slug].rsx")
+                .url(&params(json!({"slug": ["a", "b"]})))
+                .unwrap(),
+            "/docs/a/b"
+        );
+        assert_eq!(
+            route("shop/[[...slug]].rsx").url(&Map::new()).unwrap(),
+            "/shop"
+        );
+        let err = route("users/[id].rsx").url(&Map::new()).unwrap_err();
+        assert_eq!(err.to_string(), "missing param `id` for route /users/[id]");
+    }
+
+    #[test]
+    fn encode_url_segments() {
+        let params = |value: Value| value.as_object().unwrap().clone();
+        assert_eq!(
+            route("users/[id].rsx")
+                .url(&params(json!({"id": "a b?#%"})))
+                .unwrap(),
+            "/users/a%20b%3F%23%25"
+        );
+        assert_eq!(
+            route("docs/[...slug].rsx")
+                .url(&params(json!({"slug": "指南/a-b_c.d~"})))
+                .unwrap(),
+            "/docs/%E6%8C%87%E5%8D%97/a-b_c.d~"
+        );
+        let err = route("users/[id].rsx")
+            .url(&params(json!({"id": "a/b"})))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "param `id` for route /users/[id] cannot contain `/`: a/b"
+        );
+    }
+
+    #[test]
+    fn reject_catch_all_in_middle() {
+        let err = Route::from_page(Path::new("[...slug]/edit.rsx"), PathBuf::new()).unwrap_err();
+        assert!(err.to_string().contains("[...slug]"));
+    }
+
+    #[test]
+    fn rank_static_before_dynamic() {
+        let mut routes = [
+            "[[...all]].rsx",
+            "users/[id].rsx",
+            "[slug].rsx",
+            "users/[...rest].rsx",
+            "users/new.rsx",
+            "index.rsx",
+            "about.rsx",
+        ]
+        .map(route);
+        routes.sort_by(Route::cmp_rank);
+        let paths = routes.iter().map(|r| r.path.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec![
+                "/",
+                "/about",
+                "/users/new",
+                "/users/[id]",
+                "/users/[...rest]",
+                "/[slug]",
+                "/[[...all]]",
+            ]
+        );
+    }
+
+    #[test]
+    fn catch_render_panics() {
+        let err = catch_render(|| -> anyhow::Result<()> { panic!("helper broke") }).unwrap_err();
+        assert_eq!(err.to_string(), "render panicked: helper broke");
+        // 渲染阶段的panic定位到<template>而不是props函数
+        let source = "---\npub async fn get_server_props() {}\n---\n<template><p></p></template>";
+        let excerpt = SourceExcerpt::locate("a.rsx", source, &err, Stage::Render).unwrap();
+        assert_eq!(excerpt.line, 4);
+        assert_eq!(catch_render(|| Ok(1)).unwrap(), 1);
+    }
+}
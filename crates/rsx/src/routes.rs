@@ -0,0 +1,443 @@
+//! 路由表
+//!
+//! 汇总页面路由和API路由，由`rsx routes`输出。页面路由来自pages目录的扫描结果；
+//! API路由不需要编译应用，由`Config.api`目录中的Rust源码静态解析得到，支持：
+//!
+//! - `web::scope("/api")`和`web::resource("/x")`调用链上的`.service(...)`、
+//!   `.route(web::get().to(handler))`、`.route("/x", web::post().to(handler))`和`.to(handler)`
+//! - `.service(handler)`注册的`#[get("/x")]`等属性宏处理函数
+//!
+//! 处理函数定位到其定义所在的文件和行，无法解析时为注册所在的位置
+
+use crate::config::Config;
+use crate::router;
+use anyhow::{Context as _, Result};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use syn::spanned::Spanned;
+use syn::visit::{self, Visit};
+use syn::{Expr, ExprMethodCall, Lit};
+use walkdir::WalkDir;
+
+/// actix中按HTTP方法创建路由的函数，同时也是属性宏的名称
+const METHODS: [&str; 7] = ["get", "post", "put", "delete", "patch", "head", "trace"];
+
+/// 不限方法的路由
+const ANY_METHOD: &str = "*";
+
+/// 路由表中的一项
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteEntry {
+    /// HTTP方法，如`GET`，不限方法为`*`
+    pub method: String,
+    /// 路由路径，如`/users/[id]`、`/api/json`
+    pub path: String,
+    /// 源文件，相对项目根目录
+    pub file: PathBuf,
+    /// 处理函数所在的行，页面为`None`
+    pub line: Option<usize>,
+}
+
+/// 页面和API路由表
+#[derive(Debug, Clone, Default)]
+pub struct RouteTable {
+    /// 页面路由，按匹配优先级排序
+    pub pages: Vec<RouteEntry>,
+    /// API路由，按源文件和注册顺序排列
+    pub api: Vec<RouteEntry>,
+}
+
+impl RouteTable {
+    /// 扫描配置中的pages和api目录，目录不存在时对应部分为空
+    pub fn scan(config: &Config) -> Result<Self> {
+        let root = Path::new(config.root());
+        let pages = root.join(&config.pages);
+        let pages = if pages.is_dir() {
+            router::scan(&pages)?
+                .into_iter()
+                .map(|route| RouteEntry {
+                    method: "GET".to_string(),
+                    path: route.path,
+                    file: relative(root, &route.file),
+                    line: None,
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let api = root.join(&config.api);
+        let api = if api.is_dir() {
+            api_routes(&api)?
+                .into_iter()
+                .map(|entry| RouteEntry {
+                    file: relative(root, &entry.file),
+                    ..entry
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        Ok(Self { pages, api })
+    }
+}
+
+impl fmt::Display for RouteTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let entries = || self.pages.iter().chain(&self.api);
+        let method = entries().map(|e| e.method.len()).max().unwrap_or(0);
+        let path = entries().map(|e| e.path.len()).max().unwrap_or(0);
+        for (title, entries) in [("Pages", &self.pages), ("API", &self.api)] {
+            writeln!(f, "{title}:")?;
+            if entries.is_empty() {
+                writeln!(f, "  (none)")?;
+            }
+            for entry in entries {
+                write!(
+                    f,
+                    "  {:method$}  {:path$}  {}",
+                    entry.method,
+                    entry.path,
+                    entry.file.display()
+                )?;
+                if let Some(line) = entry.line {
+                    write!(f, ":{line}")?;
+                }
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 相对`root`的路径，不在`root`下时原样返回
+fn relative(root: &Path, path: &Path) -> PathBuf {
+    path.strip_prefix(root).unwrap_or(path).to_path_buf()
+}
+
+/// 解析API目录中的全部.rs文件，返回其中注册的路由
+pub fn api_routes(dir: &Path) -> Result<Vec<RouteEntry>> {
+    let mut files = Vec::new();
+    for entry in WalkDir::new(dir).sort_by_file_name() {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type().is_file() && path.extension().is_some_and(|ext| ext == "rs") {
+            let source = fs::read_to_string(path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            let ast = syn::parse_file(&source)
+                .with_context(|| format!("failed to parse {}", path.display()))?;
+            files.push((path.to_path_buf(), module_path(dir, path), ast));
+        }
+    }
+    let defs: Vec<FnDef> = files
+        .iter()
+        .flat_map(|(file, module, ast)| {
+            ast.items.iter().filter_map(|item| match item {
+                syn::Item::Fn(f) => Some(FnDef::new(f, file, module)),
+                _ => None,
+            })
+        })
+        .collect();
+    let mut routes = Vec::new();
+    for (file, module, ast) in &files {
+        let mut collector = Collector {
+            defs: &defs,
+            file,
+            module,
+            routes: Vec::new(),
+        };
+        collector.visit_file(ast);
+        routes.extend(collector.routes);
+    }
+    Ok(routes)
+}
+
+/// 文件对应的模块路径，如`json.rs`为`["json"]`，`mod.rs`为`[]`
+fn module_path(dir: &Path, file: &Path) -> Vec<String> {
+    let mut module: Vec<String> = file
+        .strip_prefix(dir)
+        .unwrap_or(file)
+        .with_extension("")
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    if module.last().is_some_and(|last| last == "mod") {
+        module.pop();
+    }
+    module
+}
+
+/// API目录中定义的函数
+struct FnDef {
+    name: String,
+    module: Vec<String>,
+    file: PathBuf,
+    line: usize,
+    /// `#[get("/x")]`等属性宏声明的方法和路径
+    route: Option<(String, String)>,
+}
+
+impl FnDef {
+    fn new(f: &syn::ItemFn, file: &Path, module: &[String]) -> Self {
+        let route = f.attrs.iter().find_map(|attr| {
+            let name = attr.path().segments.last()?.ident.to_string();
+            if !METHODS.contains(&name.as_str()) {
+                return None;
+            }
+            let path = attr.parse_args::<syn::LitStr>().ok()?;
+            Some((name.to_uppercase(), path.value()))
+        });
+        Self {
+            name: f.sig.ident.to_string(),
+            module: module.to_vec(),
+            file: file.to_path_buf(),
+            line: f.sig.ident.span().start().line,
+            route,
+        }
+    }
+}
+
+/// 收集一个文件中注册的路由
+struct Collector<'a> {
+    defs: &'a [FnDef],
+    file: &'a Path,
+    module: &'a [String],
+    routes: Vec<RouteEntry>,
+}
+
+impl<'ast> Visit<'ast> for Collector<'_> {
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        if !self.registration(expr, "") {
+            visit::visit_expr(self, expr);
+        }
+    }
+
+    fn visit_expr_method_call(&mut self, call: &'ast ExprMethodCall) {
+        // `App`或`ServiceConfig`上直接注册的属性宏处理函数
+        if call.method == "service" {
+            for arg in call.args.iter().filter(|arg| matches!(arg, Expr::Path(_))) {
+                self.service(arg, "");
+            }
+        }
+        visit::visit_expr_method_call(self, call);
+    }
+}
+
+impl Collector<'_> {
+    /// 处理`web::scope`或`web::resource`调用链，不是时返回`false`
+    fn registration(&mut self, expr: &Expr, prefix: &str) -> bool {
+        let (base, calls) = unchain(expr);
+        let Some((name, Some(path))) = call_name(base) else {
+            return false;
+        };
+        let path = join(prefix, &path);
+        match name.as_str() {
+            "scope" => {
+                for call in calls {
+                    match (call.method.to_string().as_str(), args(call).as_slice()) {
+                        ("service", [service]) => self.service(service, &path),
+                        ("route", [route_path, route]) => {
+                            if let Some(route_path) = str_lit(route_path) {
+                                self.route(&join(&path, &route_path), route);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            "resource" => {
+                for call in calls {
+                    match (call.method.to_string().as_str(), args(call).as_slice()) {
+                        ("route", [route]) => self.route(&path, route),
+                        ("to", [handler]) => self.push(ANY_METHOD, &path, handler),
+                        _ => {}
+                    }
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// 处理`.service(...)`的参数
+    fn service(&mut self, expr: &Expr, prefix: &str) {
+        if self.registration(expr, prefix) {
+            return;
+        }
+        if let Expr::Path(path) = expr
+            && let Some(def) = self.resolve(&path.path)
+            && let Some((method, route)) = &def.route
+        {
+            self.routes.push(RouteEntry {
+                method: method.clone(),
+                path: join(prefix, route),
+                file: def.file.clone(),
+                line: Some(def.line),
+            });
+        }
+    }
+
+    /// 处理`web::get().to(handler)`这样的路由
+    fn route(&mut self, path: &str, expr: &Expr) {
+        let (base, calls) = unchain(expr);
+        let mut method = match call_name(base) {
+            Some((name, _)) if METHODS.contains(&name.as_str()) => name.to_uppercase(),
+            _ => ANY_METHOD.to_string(),
+        };
+        for call in calls {
+            match (call.method.to_string().as_str(), args(call).as_slice()) {
+                // `web::route().method(Method::GET)`
+                ("method", [Expr::Path(m)]) => {
+                    if let Some(segment) = m.path.segments.last() {
+                        method = segment.ident.to_string().to_uppercase();
+                    }
+                }
+                ("to", [handler]) => self.push(&method, path, handler),
+                _ => {}
+            }
+        }
+    }
+
+    /// 记录路由，处理函数定位到其定义
+    fn push(&mut self, method: &str, path: &str, handler: &Expr) {
+        let def = match handler {
+            Expr::Path(handler) => self.resolve(&handler.path),
+            _ => None,
+        };
+        let (file, line) = def.map_or_else(
+            || (self.file.to_path_buf(), handler.span().start().line),
+            |def| (def.file.clone(), def.line),
+        );
+        self.routes.push(RouteEntry {
+            method: method.to_string(),
+            path: path.to_string(),
+            file,
+            line: Some(line),
+        });
+    }
+
+    /// 按路径查找函数定义，`json::get`匹配模块`json`中的`get`，单段路径匹配当前文件
+    fn resolve(&self, path: &syn::Path) -> Option<&FnDef> {
+        let segments: Vec<String> = path.segments.iter().map(|s| s.ident.to_string()).collect();
+        let (name, modules) = segments.split_last()?;
+        self.defs.iter().find(|def| {
+            &def.name == name
+                && match modules.last().map(String::as_str) {
+                    None | Some("self") => def.module == self.module,
+                    Some(module) => def.module.last().is_some_and(|last| last == module),
+                }
+        })
+    }
+}
+
+/// 展开方法调用链，返回起点表达式和按调用顺序排列的方法调用
+fn unchain(mut expr: &Expr) -> (&Expr, Vec<&ExprMethodCall>) {
+    let mut calls = Vec::new();
+    while let Expr::MethodCall(call) = expr {
+        calls.push(call);
+        expr = &call.receiver;
+    }
+    calls.reverse();
+    (expr, calls)
+}
+
+/// 方法调用的参数
+fn args(call: &ExprMethodCall) -> Vec<&Expr> {
+    call.args.iter().collect()
+}
+
+/// 函数调用的函数名和第一个字符串参数，如`web::scope("/api")`
+fn call_name(expr: &Expr) -> Option<(String, Option<String>)> {
+    let Expr::Call(call) = expr else {
+        return None;
+    };
+    let Expr::Path(func) = &*call.func else {
+        return None;
+    };
+    let name = func.path.segments.last()?.ident.to_string();
+    Some((name, call.args.first().and_then(str_lit)))
+}
+
+/// 字符串字面量的值
+fn str_lit(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Lit(syn::ExprLit {
+            lit: Lit::Str(lit), ..
+        }) => Some(lit.value()),
+        _ => None,
+    }
+}
+
+/// 拼接路由前缀和路径，与actix相同，不以`/`开头的路径补上`/`
+fn join(prefix: &str, path: &str) -> String {
+    let prefix = prefix.trim_end_matches('/');
+    match path {
+        "" if prefix.is_empty() => "/".to_string(),
+        "" => prefix.to_string(),
+        _ if path.starts_with('/') => format!("{prefix}{path}"),
+        _ => format!("{prefix}/{path}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_api_routes() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("mod.rs"),
+            r#"
+mod users;
+
+pub fn scope() -> Scope {
+    web::scope("/api")
+        .service(web::resource("/users").route(web::get().to(users::list)).to(fallback))
+        .service(web::scope("/v2").route("/ping", web::route().method(Method::HEAD).to(|| async {})))
+        .service(users::create)
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(users::create);
+}
+
+async fn fallback() {}
+"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("users.rs"),
+            "pub async fn list() {}\n\n#[post(\"/users\")]\npub async fn create() {}\n",
+        )
+        .unwrap();
+
+        let routes = api_routes(dir.path()).unwrap();
+        let summary: Vec<String> = routes
+            .iter()
+            .map(|r| {
+                let file = r.file.file_name().unwrap().to_string_lossy();
+                format!("{} {} {file}:{}", r.method, r.path, r.line.unwrap())
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                "GET /api/users users.rs:1",
+                "* /api/users mod.rs:15",
+                "HEAD /api/v2/ping mod.rs:7",
+                "POST /api/users users.rs:4",
+                "POST /users users.rs:4",
+            ]
+        );
+    }
+
+    #[test]
+    fn join_paths() {
+        assert_eq!(join("", "/api"), "/api");
+        assert_eq!(join("/api", "/json"), "/api/json");
+        assert_eq!(join("/api/", "json"), "/api/json");
+        assert_eq!(join("/api", ""), "/api");
+        assert_eq!(join("", ""), "/");
+    }
+}
@@ -1,5 +1,355 @@
+use crate::config::Config;
+use crate::context::{Authenticator, User};
+use crate::dev::DevServer;
+use crate::export::{self, ExportedPage};
+use crate::props::PropsRegistry;
+use crate::router::Router;
+use actix_files::Files;
+use actix_web::body::{BodySize, BoxBody, MessageBody};
+use actix_web::dev::{Service, ServiceRequest, fn_service};
+use actix_web::web::Bytes;
+use actix_web::{App, HttpRequest, HttpServer, Scope, middleware::Logger, web};
+use anyhow::Result;
+use futures_util::future::LocalBoxFuture;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// 用户注册的Scope工厂，每个worker都会调用一次
+type ScopeFactory = Arc<dyn Fn() -> Scope + Send + Sync>;
+
+/// 注册共享应用状态的函数，每个worker都会调用一次
+type StateFactory = Arc<dyn Fn(&mut web::ServiceConfig) + Send + Sync>;
+
+/// 服务器停止后执行的异步钩子
+type ShutdownHook = Box<dyn FnOnce() -> LocalBoxFuture<'static, ()> + Send>;
+
+/// rsx服务器，挂载页面路由、用户Scope和public静态目录
 pub struct RsxServer {
-    pub port: u16,
-    pub host: String,
-    pub root: String,
+    config: Config,
+    scopes: Vec<ScopeFactory>,
+    props: PropsRegistry,
+    states: Vec<StateFactory>,
+    authenticator: Option<Authenticator>,
+    shutdown_hooks: Vec<ShutdownHook>,
+    dev: bool,
+}
+
+impl RsxServer {
+    /// 根据配置创建服务器
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            scopes: Vec::new(),
+            props: PropsRegistry::new(),
+            states: Vec::new(),
+            authenticator: None,
+            shutdown_hooks: Vec::new(),
+            dev: false,
+        }
+    }
+
+    /// 挂载用户Scope，如`app::api::router_scope`
+    pub fn scope<F>(mut self, factory: F) -> Self
+    where
+        F: Fn() -> Scope + Send + Sync + 'static,
+    {
+        self.scopes.push(Arc::new(factory));
+        self
+    }
+
+    /// 设置页面的props函数注册表，通常由生成的页面模块提供
+    pub fn props(mut self, props: PropsRegistry) -> Self {
+        self.props = props;
+        self
+    }
+
+    /// 共享应用状态，页面中通过`ctx.state::<T>()`读取，用户Scope中可使用`web::Data<T>`提取
+    pub fn state<T: Send + Sync + 'static>(mut self, data: web::Data<T>) -> Self {
+        self.states
+            .push(Arc::new(move |cfg: &mut web::ServiceConfig| {
+                cfg.app_data(data.clone());
+            }));
+        self
+    }
+
+    /// 设置识别登录用户的函数，结果通过`ctx.user()`和`ctx.get_user_id()`读取
+    ///
+    /// 中间件放入请求扩展的[`User`]优先于该函数
+    pub fn authenticate<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&HttpRequest) -> Option<User> + Send + Sync + 'static,
+    {
+        self.authenticator = Some(Authenticator(Arc::new(f)));
+        self
+    }
+
+    /// 注册停止钩子，在请求排空后按注册顺序执行，如刷新缓存、关闭fetch客户端
+    pub fn on_shutdown<F, Fut>(mut self, hook: F) -> Self
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + 'static,
+    {
+        self.shutdown_hooks.push(Box::new(move || {
+            Box::pin(hook()) as LocalBoxFuture<'static, ()>
+        }));
+        self
+    }
+
+    /// 开发模式：监听页面、组件和public目录，修改后重新编译受影响的页面并通知浏览器刷新，
+    /// 见[`dev`](crate::dev)
+    pub fn dev(mut self) -> Self {
+        self.dev = true;
+        self
+    }
+
+    /// 服务器配置
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// 编译全部页面，返回注册服务的函数，可用于`App::configure`
+    ///
+    /// 注册顺序为用户Scope、页面路由、Vite构建产物、public目录，先注册的优先匹配；
+    /// 开发模式下最先注册开发服务器的WebSocket路由并开始监听文件
+    pub fn configure(&self) -> Result<impl Fn(&mut web::ServiceConfig) + Clone + Send + 'static> {
+        let mut router = self.router()?;
+        if !self.dev {
+            return Ok(self.configure_router(Arc::new(router), None));
+        }
+        router = router.with_dev_client();
+        let router = Arc::new(router);
+        let dev = Arc::new(DevServer::new(&self.config, router.clone()));
+        dev.watch()?;
+        log::info!("rsx dev server watching for changes");
+        Ok(self.configure_router(router, Some(dev)))
+    }
+
+    /// 静态导出：用合成的GET请求渲染每个页面并写入`Config.dist`，返回导出的页面
+    ///
+    /// 页面与Vite构建产物写入同一目录，导出后`dist`可以直接部署，同名文件会被覆盖
+    ///
+    /// 动态路由按页面的`get_static_paths`返回的参数渲染，见[`export`](crate::export)
+    pub async fn export(&self) -> Result<Vec<ExportedPage>> {
+        let router = Arc::new(self.router()?);
+        let dist = Path::new(self.config.root()).join(&self.config.dist);
+        let pages =
+            export::export(&router, self.configure_router(router.clone(), None), &dist).await?;
+        log::info!("{} pages exported to {}", pages.len(), dist.display());
+        Ok(pages)
+    }
+
+    /// 编译全部页面
+    fn router(&self) -> Result<Router> {
+        Ok(Router::new(&self.config)?.with_props(self.props.clone()))
+    }
+
+    /// 返回注册用户Scope、页面路由和静态目录的函数
+    fn configure_router(
+        &self,
+        router: Arc<Router>,
+        dev: Option<Arc<DevServer>>,
+    ) -> impl Fn(&mut web::ServiceConfig) + Clone + Send + 'static {
+        let scopes = self.scopes.clone();
+        let states = self.states.clone();
+        let authenticator = self.authenticator.clone();
+        let public = Path::new(self.config.root()).join(&self.config.public);
+        let public = public.is_dir().then_some(public);
+        // Vite构建产物所在的目录，如dist/assets以`Config.base`下的/assets提供
+        let assets: Vec<(String, PathBuf)> = router
+            .engine()
+            .manifest()
+            .map(|manifest| {
+                manifest
+                    .asset_dirs()
+                    .into_iter()
+                    .map(|dir| (manifest.mount_path(&dir), manifest.dist().join(&dir)))
+                    .filter(|(_, path)| path.is_dir())
+                    .collect()
+            })
+            .unwrap_or_default();
+        log::info!("{} page routes loaded", router.routes().len());
+        move |cfg: &mut web::ServiceConfig| {
+            for state in &states {
+                state(cfg);
+            }
+            if let Some(authenticator) = &authenticator {
+                cfg.app_data(authenticator.clone());
+            }
+            // 用户API通过`web::Data<Isr>`按需再生成页面
+            cfg.app_data(web::Data::from(router.isr().clone()));
+            if let Some(dev) = &dev {
+                dev.configure(cfg);
+            }
+            configure_app(cfg, &router, &scopes, &assets, public.as_deref())
+        }
+    }
+
+    /// 启动HTTP服务器，收到SIGTERM或SIGINT后优雅停止
+    pub async fn run(self) -> Result<()> {
+        self.run_until(shutdown_signal()).await
+    }
+
+    /// 启动HTTP服务器，`shutdown`完成后停止接收新连接，
+    /// 在`shutdown_timeout`内等待处理中的请求完成，然后执行停止钩子
+    pub async fn run_until(self, shutdown: impl Future<Output = ()> + 'static) -> Result<()> {
+        let configure = self.configure()?;
+        let addr = (self.config.host.clone(), self.config.port);
+        let timeout = Duration::from_secs(self.config.shutdown_timeout);
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let counter = in_flight.clone();
+        let server = HttpServer::new(move || {
+            let counter = counter.clone();
+            App::new()
+                .wrap_fn(move |req, srv| {
+                    let guard = InFlight::new(&counter);
+                    let res = srv.call(req);
+                    // 守卫随响应体释放，流式渲染的页面在响应体发送完之前同样计为处理中
+                    async move {
+                        res.await.map(|res| {
+                            res.map_body(|_, body| InFlightBody {
+                                body: body.boxed(),
+                                guard: Some(guard),
+                            })
+                        })
+                    }
+                })
+                .wrap(Logger::default())
+                .configure(configure.clone())
+        })
+        .bind(addr.clone())?
+        // 由rsx处理信号，actix默认收到SIGINT时会强制停止
+        .disable_signals()
+        .shutdown_timeout(timeout.as_secs())
+        .run();
+        log::info!("rsx server listening on http://{}:{}", addr.0, addr.1);
+
+        let handle = server.handle();
+        actix_rt::spawn(async move {
+            shutdown.await;
+            log::info!(
+                "rsx server shutting down, draining requests within {}s",
+                timeout.as_secs()
+            );
+            handle.pause().await;
+            // actix停止accept线程时可能先于worker收到停止消息而断开连接，
+            // 因此先自行等待处理中的请求完成，再停止服务器
+            let deadline = Instant::now() + timeout;
+            while in_flight.load(Ordering::SeqCst) > 0 && Instant::now() < deadline {
+                actix_rt::time::sleep(Duration::from_millis(20)).await;
+            }
+            let remaining = in_flight.load(Ordering::SeqCst);
+            if remaining > 0 {
+                log::warn!("{remaining} requests still in flight after shutdown timeout");
+            }
+            handle.stop(true).await;
+        });
+        server.await?;
+
+        for hook in self.shutdown_hooks {
+            if actix_rt::time::timeout(timeout, hook()).await.is_err() {
+                log::warn!("shutdown hook timed out after {}s", timeout.as_secs());
+            }
+        }
+        log::info!("rsx server stopped");
+        Ok(())
+    }
+}
+
+/// 处理中请求的计数守卫
+struct InFlight(Arc<AtomicUsize>);
+
+impl InFlight {
+    fn new(counter: &Arc<AtomicUsize>) -> Self {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Self(counter.clone())
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// 持有[`InFlight`]守卫的响应体，发送完或连接断开时释放
+struct InFlightBody {
+    body: BoxBody,
+    guard: Option<InFlight>,
+}
+
+impl MessageBody for InFlightBody {
+    type Error = Box<dyn std::error::Error>;
+
+    fn size(&self) -> BodySize {
+        self.body.size()
+    }
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        let next = Pin::new(&mut self.body).poll_next(cx);
+        if let Poll::Ready(None) = next {
+            self.guard = None;
+        }
+        next
+    }
+}
+
+/// 等待SIGTERM或SIGINT信号
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use actix_rt::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                futures_util::future::select(
+                    Box::pin(actix_rt::signal::ctrl_c()),
+                    Box::pin(terminate.recv()),
+                )
+                .await;
+            }
+            Err(err) => {
+                log::error!("failed to listen for SIGTERM: {err}");
+                let _ = actix_rt::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = actix_rt::signal::ctrl_c().await;
+    }
+}
+
+/// 按顺序注册用户Scope、页面路由、构建产物目录和public目录，静态目录中不存在的文件
+/// 同样由[`Router::not_found`]处理
+fn configure_app(
+    cfg: &mut web::ServiceConfig,
+    router: &Arc<Router>,
+    scopes: &[ScopeFactory],
+    assets: &[(String, PathBuf)],
+    public: Option<&Path>,
+) {
+    for scope in scopes {
+        cfg.service(scope());
+    }
+    router.configure(cfg);
+    let not_found = || {
+        let router = router.clone();
+        fn_service(move |req: ServiceRequest| {
+            let response = router.not_found();
+            async move { Ok(req.into_response(response)) }
+        })
+    };
+    for (mount, dir) in assets {
+        cfg.service(Files::new(mount, dir.clone()).default_handler(not_found()));
+    }
+    if let Some(public) = public {
+        cfg.service(Files::new("/", PathBuf::from(public)).default_handler(not_found()));
+    }
 }
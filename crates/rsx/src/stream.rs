@@ -0,0 +1,343 @@
+//! 流式渲染
+//!
+//! 页面的props函数可以通过[`Context::defer`]延迟加载耗时的数据，如慢速的fetch：
+//! props函数先返回页面的其他数据，外壳和`<head>`立即用这些数据渲染并发送，
+//! `<defer>`区块位置输出`slot="fallback"`的占位内容。每个延迟数据完成后写入渲染数据，
+//! `await`属性列出的数据都就绪的区块随即渲染，以`<template>`发送并由内联脚本替换占位内容；
+//! 没有`await`属性的区块等待全部延迟数据。最后发送hydration数据和`</body>`之后的内容
+//!
+//! 外壳发送后无法再修改状态码和响应头，延迟数据返回错误、区块渲染失败或panic时记录日志，
+//! 用错误提示替换该区块的占位内容，其他区块不受影响
+//!
+//! ```ignore
+//! pub async fn get_server_props(req: Request, ctx: Context) -> Response {
+//!     ctx.defer("comments", fetch_comments(req.param::<u64>("id")?));
+//!     Response::json!({ "title": "文章" })
+//! }
+//! ```
+//!
+//! ```html
+//! <defer await="comments">
+//!     <Skeleton slot="fallback" />
+//!     {#each comments as comment}<p>{comment.text}</p>{/each}
+//! </defer>
+//! ```
+
+use crate::context::Context;
+use crate::error::{self, Stage};
+use crate::props;
+use crate::response::{Response, ServerResponse};
+use crate::router::{catch_render, internal_error, panic_message};
+use crate::template::island::Islands;
+use crate::template::{COMPONENTS_KEY, DEFER_ID_PREFIX, SHELL_KEY, TemplateEngine};
+use actix_web::HttpResponse;
+use actix_web::http::header::HeaderMap;
+use actix_web::web::Bytes;
+use anyhow::{Result, anyhow};
+use futures_util::future::LocalBoxFuture;
+use futures_util::stream::{self, FuturesUnordered};
+use futures_util::{FutureExt, StreamExt};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::convert::Infallible;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+
+/// 区块内容所在`<template>`元素的ID前缀，如`__rsx_fill_0`
+pub const FILL_ID_PREFIX: &str = "__rsx_fill_";
+
+/// 请求中通过[`Context::defer`]注册的延迟数据，保存在请求扩展中
+#[derive(Default)]
+pub(crate) struct Deferred(Vec<(String, LocalBoxFuture<'static, Result<Value>>)>);
+
+impl Deferred {
+    /// 添加键为`key`的延迟数据
+    pub(crate) fn push(&mut self, key: String, future: LocalBoxFuture<'static, Result<Value>>) {
+        self.0.push((key, future));
+    }
+
+    /// 取出请求中注册的延迟数据
+    pub(crate) fn take(ctx: &Context) -> Self {
+        ctx.extensions_mut().remove::<Self>().unwrap_or_default()
+    }
+
+    /// 是否没有延迟数据
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// 等待全部延迟数据并写入`data`，用于不流式渲染的页面
+    pub(crate) async fn resolve_into(self, data: &mut Value) -> Result<()> {
+        let values =
+            futures_util::future::join_all(self.0.into_iter().map(|(key, future)| async move {
+                let value = catch_panic(future).await;
+                (key, value)
+            }))
+            .await;
+        for (key, value) in values {
+            let value = value.map_err(|err| err.context(format!("deferred props `{key}`")))?;
+            if let Value::Object(data) = data {
+                data.insert(key, value);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 等待延迟数据，panic转为错误
+async fn catch_panic(future: LocalBoxFuture<'static, Result<Value>>) -> Result<Value> {
+    AssertUnwindSafe(future)
+        .catch_unwind()
+        .await
+        .unwrap_or_else(|panic| Err(anyhow!("panicked: {}", panic_message(panic.as_ref()))))
+}
+
+/// 用区块内容替换占位元素的函数，随外壳发送
+fn fill_runtime() -> String {
+    format!(
+        "<script>function __rsx_fill(i){{var t=document.getElementById('{FILL_ID_PREFIX}'+i),\
+         p=document.getElementById('{DEFER_ID_PREFIX}'+i);\
+         if(t&&p){{p.replaceWith(t.content);t.remove()}}}}</script>"
+    )
+}
+
+/// 流式渲染页面，`data`为props函数和组件props函数的结果，外壳渲染失败时返回错误响应
+pub(crate) fn render_page(
+    engine: Arc<TemplateEngine>,
+    name: &str,
+    data: Value,
+    hydrate: bool,
+    headers: HeaderMap,
+    deferred: Deferred,
+    ctx: &Context,
+) -> HttpResponse {
+    let mut shell = data.clone();
+    if let Value::Object(shell) = &mut shell {
+        shell.insert(SHELL_KEY.to_string(), Value::Bool(true));
+    }
+    let mut islands = Islands::default();
+    let mut html = match catch_render(|| engine.render_islands(name, &shell, &mut islands)) {
+        Ok(html) => html,
+        Err(err) => return internal_error(&engine, name, None, Stage::Render, err, ctx),
+    };
+    // 只有外壳中的岛能预加载，区块中的岛由加载脚本按需导入
+    engine.inject_assets(name, &mut html, &islands);
+    let (mut head, tail) = split_tail(html);
+    head.push_str(&fill_runtime());
+
+    let mut waiting = HashSet::new();
+    let pending: FuturesUnordered<_> = deferred
+        .0
+        .into_iter()
+        .map(|(key, future)| {
+            waiting.insert(key.clone());
+            async move {
+                let value = catch_panic(future).await;
+                (key, value)
+            }
+            .boxed_local()
+        })
+        .collect();
+    let regions = engine
+        .region_awaits(name)
+        .iter()
+        .map(|awaits| Some(awaits.clone()))
+        .collect();
+    let filler = Filler {
+        engine,
+        name: name.to_string(),
+        data,
+        hydrate,
+        islands,
+        regions,
+        waiting,
+        failed: HashSet::new(),
+        pending,
+        tail: Some(tail),
+    };
+    let body = stream::once(async move { Ok::<_, Infallible>(Bytes::from(head)) }).chain(
+        stream::unfold(filler, |mut filler| async move {
+            let chunk = filler.next_chunk().await?;
+            Some((Ok::<_, Infallible>(Bytes::from(chunk)), filler))
+        }),
+    );
+    let mut response = ServerResponse::html_stream(body);
+    for (key, value) in headers {
+        response = response.append_header(key, value);
+    }
+    Response::from(response).into()
+}
+
+/// 外壳发送后逐个填充区块的状态
+struct Filler {
+    engine: Arc<TemplateEngine>,
+    name: String,
+    /// 渲染数据，延迟数据完成后写入
+    data: Value,
+    hydrate: bool,
+    /// 外壳和已填充区块中的岛
+    islands: Islands,
+    /// 未填充区块等待的延迟数据，已填充的区块为`None`
+    regions: Vec<Option<Vec<String>>>,
+    /// 未完成的延迟数据的键
+    waiting: HashSet<String>,
+    /// 失败的延迟数据的键
+    failed: HashSet<String>,
+    /// 未完成的延迟数据
+    pending: FuturesUnordered<LocalBoxFuture<'static, (String, Result<Value>)>>,
+    /// `</body>`及之后的内容，发送后为`None`
+    tail: Option<String>,
+}
+
+impl Filler {
+    /// 下一段输出：就绪的区块，全部区块填充后为hydration数据和`</body>`之后的内容
+    async fn next_chunk(&mut self) -> Option<String> {
+        loop {
+            let chunk = self.fill_ready();
+            if !chunk.is_empty() {
+                return Some(chunk);
+            }
+            match self.pending.next().await {
+                Some((key, value)) => {
+                    self.waiting.remove(&key);
+                    match value {
+                        Ok(value) => {
+                            if let Value::Object(data) = &mut self.data {
+                                data.insert(key, value);
+                            }
+                        }
+                        Err(err) => {
+                            log::error!(
+                                "deferred props `{key}` of page {} error: {err:?}",
+                                self.name
+                            );
+                            self.failed.insert(key);
+                        }
+                    }
+                }
+                None => return self.finish(),
+            }
+        }
+    }
+
+    /// 渲染等待的延迟数据都已完成的区块
+    fn fill_ready(&mut self) -> String {
+        let mut out = String::new();
+        for index in 0..self.regions.len() {
+            let Some(awaits) = self.regions[index].clone() else {
+                continue;
+            };
+            let ready = if awaits.is_empty() {
+                self.waiting.is_empty()
+            } else {
+                awaits.iter().all(|key| !self.waiting.contains(key))
+            };
+            if !ready {
+                continue;
+            }
+            let failed = if awaits.is_empty() {
+                self.failed.iter().next().cloned()
+            } else {
+                awaits.into_iter().find(|key| self.failed.contains(key))
+            };
+            let result = match failed {
+                Some(key) => Err(anyhow!("deferred props `{key}` failed")),
+                None => self.render_region(index),
+            };
+            self.regions[index] = None;
+            match result {
+                Ok(html) => out.push_str(&fill_chunk(index, &html)),
+                Err(err) => out.push_str(&self.fill_error(index, &err)),
+            }
+        }
+        out
+    }
+
+    /// 渲染区块，panic转为错误
+    fn render_region(&mut self, index: usize) -> Result<String> {
+        let (engine, name, data, islands) =
+            (&self.engine, &self.name, &self.data, &mut self.islands);
+        catch_render(|| engine.render_region(name, index, data, islands))
+    }
+
+    /// 区块失败时替换占位内容的错误提示，开发模式下显示错误信息
+    fn fill_error(&self, index: usize, err: &anyhow::Error) -> String {
+        let id = error::error_id();
+        log::error!(
+            "render <defer> {index} of page {} error [{id}]: {err:?}",
+            self.name
+        );
+        let message = if self.engine.is_dev() {
+            handlebars::html_escape(&format!("{err:#}"))
+        } else {
+            "Failed to load".to_string()
+        };
+        fill_chunk(
+            index,
+            &format!("<div data-rsx-error=\"{id}\" role=\"alert\">{message}</div>"),
+        )
+    }
+
+    /// 外壳和区块中的岛都在填充之后加载
+    fn finish(&mut self) -> Option<String> {
+        let mut out = String::new();
+        let hydration = self.hydrate.then(|| {
+            let mut hydration = self.data.clone();
+            if let Value::Object(hydration) = &mut hydration {
+                hydration.remove(COMPONENTS_KEY);
+            }
+            hydration
+        });
+        match props::server_props_script(hydration.as_ref(), &self.islands) {
+            Ok(script) => out.push_str(&script),
+            Err(err) => log::error!("serialize props of page {} error: {err:?}", self.name),
+        }
+        out.push_str(&self.tail.take()?);
+        Some(out)
+    }
+}
+
+/// 拆分出最后一个`</body>`及之后的内容，区块和hydration数据在它之前发送
+fn split_tail(mut html: String) -> (String, String) {
+    match html.rfind("</body>") {
+        Some(index) => {
+            let tail = html.split_off(index);
+            (html, tail)
+        }
+        None => (html, String::new()),
+    }
+}
+
+/// 区块内容放在`<template>`中，解析后立即替换占位元素
+fn fill_chunk(index: usize, html: &str) -> String {
+    format!(
+        "<template id=\"{FILL_ID_PREFIX}{index}\">{html}</template><script>__rsx_fill({index})</script>"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_before_body_end() {
+        let (head, tail) = split_tail("<html><body><p></p></body></html>".to_string());
+        assert_eq!(head, "<html><body><p></p>");
+        assert_eq!(tail, "</body></html>");
+        assert_eq!(split_tail("<p></p>".to_string()).1, "");
+    }
+
+    #[test]
+    fn fill_deferred_regions() {
+        assert_eq!(
+            fill_runtime(),
+            "<script>function __rsx_fill(i){var t=document.getElementById('__rsx_fill_'+i),\
+             p=document.getElementById('__rsx_defer_'+i);\
+             if(t&&p){p.replaceWith(t.content);t.remove()}}</script>"
+        );
+        assert_eq!(
+            fill_chunk(1, "<b>x</b>"),
+            "<template id=\"__rsx_fill_1\"><b>x</b></template><script>__rsx_fill(1)</script>"
+        );
+    }
+}
@@ -0,0 +1,793 @@
+//! 模板代码生成
+//!
+//! 将模板节点树转换为Handlebars模板源码，生成结果与原模板保持相同的换行
+//!
+//! .rsx组件在编译时内联展开到`{{#rsx_component}}`块中，组件内的变量只能访问组件的props，
+//! 调用方传入的子节点按插槽编译，在调用方的作用域中求值。内联的组件模板中的换行替换为空格，
+//! 以保持页面模板的行号不变
+//!
+//! 页面的布局按从外到内的顺序作为组件嵌套在页面模板外层，页面内容传入最内层布局的插槽
+//!
+//! 页面中的`<defer>`区块编译为`{{#rsx_defer}}`块，区块内容同时单独编译为区块模板，
+//! 流式渲染时先输出`slot="fallback"`的占位内容，`await`属性列出的延迟数据就绪后再渲染
+//! 区块模板填充
+//!
+//! 有Scoped样式的模板中的元素加上样式的作用域属性，插槽内容使用调用方的作用域
+//!
+//! `<head>`中的`<title>`、`<meta>`等标签编译为`{{#rsx_head}}`块，渲染时按键去重后在文档
+//! `<head>`的末尾统一输出。`<html>`中的`<head>`和页面模板最外层的`<head>`为文档`<head>`，
+//! 其他`<head>`元素本身不输出，只收集其中的标签
+
+use super::expr::{Expr, PathSegment};
+use super::head::{self, HEAD_HELPER, HEAD_OUTLET, HEAD_TAGS, KEY_ATTR};
+use super::island::{CLIENT_DIRECTIVES, ISLAND_HELPER};
+use super::node::{Attr, AttrPart, AttrValue, Element, Expression, Node};
+use super::style::Style;
+use crate::parser::{ParseError, Position};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// 表达式求值helper的名称
+pub const EXPR_HELPER: &str = "rsx_expr";
+
+/// 组件块helper的名称，用法为`{{#rsx_component "id" title=title}}...{{/rsx_component}}`
+pub const COMPONENT_HELPER: &str = "rsx_component";
+
+/// 默认插槽的名称
+pub const DEFAULT_SLOT: &str = "default";
+
+/// 延迟区块helper的名称，用法为`{{#rsx_defer 0}}内容{{else}}占位{{/rsx_defer}}`
+pub const DEFER_HELPER: &str = "rsx_defer";
+
+/// 延迟区块的占位插槽名称
+pub const FALLBACK_SLOT: &str = "fallback";
+
+/// 延迟区块等待的数据，如`<defer await="comments, likes">`
+pub const AWAIT_ATTR: &str = "await";
+
+/// 布局组件块helper的第二个参数，布局可以读取页面的渲染数据
+pub const LAYOUT_FLAG: &str = "layout";
+
+/// 布局在页面帧中的组件名称前缀，不是合法的标签名，不会与导入的组件冲突
+const LAYOUT_PREFIX: &str = "Layout#";
+
+/// 不显示的元素，不加样式的作用域属性
+const UNSTYLED_ELEMENTS: [&str; 8] = [
+    "head", "title", "meta", "link", "base", "script", "style", "template",
+];
+
+/// 编译出的`<defer>`区块
+#[derive(Debug, Clone, PartialEq)]
+pub struct Region {
+    /// 区块模板
+    pub template: String,
+    /// 区块等待的延迟数据的键，为空时等待全部延迟数据
+    pub awaits: Vec<String>,
+}
+
+/// 模板中引用的组件
+#[derive(Debug, Clone, PartialEq)]
+pub enum Component {
+    /// .rsx组件，编译时内联展开
+    Rsx(Arc<ComponentTemplate>),
+    /// 客户端组件，值为组件模块的地址，带`client:*`指令时渲染为岛
+    Client(String),
+}
+
+/// 已解析的.rsx组件模板
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComponentTemplate {
+    /// 组件ID，如`src_components_header`，也是组件props函数的注册名称
+    pub id: String,
+    /// 组件文件
+    pub path: PathBuf,
+    /// 模板节点
+    pub nodes: Vec<Node>,
+    /// 组件模板中可用的组件
+    pub components: HashMap<String, Component>,
+    /// 组件的样式
+    pub style: Option<Style>,
+}
+
+impl ComponentTemplate {
+    /// 组件及其依赖的全部组件ID，按首次出现的顺序排列
+    pub fn collect_ids(components: &HashMap<String, Component>, out: &mut Vec<String>) {
+        let mut components: Vec<_> = components.iter().collect();
+        components.sort_by(|a, b| a.0.cmp(b.0));
+        for (_, component) in components {
+            if let Component::Rsx(template) = component
+                && !out.contains(&template.id)
+            {
+                out.push(template.id.clone());
+                Self::collect_ids(&template.components, out);
+            }
+        }
+    }
+}
+
+/// 一层块作用域，`{#each}`和组件各占一层Handlebars上下文
+struct Scope {
+    /// `{#each}`的块参数
+    params: Vec<String>,
+    /// 所属的模板帧
+    frame: usize,
+}
+
+/// 模板帧，页面模板为第0帧，每次内联组件创建新的帧
+struct Frame {
+    /// 帧开始时的作用域层数，变量相对该层的上下文求值
+    base: usize,
+    /// 调用方的帧和传入的插槽内容，页面模板没有调用方
+    caller: Option<(usize, HashMap<String, Vec<Node>>)>,
+    /// 帧内可用的组件
+    components: HashMap<String, Component>,
+    /// 组件文件，页面模板的文件由调用方设置
+    file: Option<PathBuf>,
+    /// 样式的作用域属性，加在帧内的元素上
+    scope: Option<String>,
+}
+
+/// 代码生成器
+pub struct Codegen {
+    scopes: Vec<Scope>,
+    frames: Vec<Frame>,
+    /// 当前帧
+    frame: usize,
+    /// 页面的布局，从外到内排列
+    layouts: Vec<Arc<ComponentTemplate>>,
+    /// 已编译的延迟区块
+    regions: Vec<Region>,
+    /// 当前元素的祖先元素名称
+    parents: Vec<String>,
+    out: String,
+}
+
+impl Codegen {
+    pub fn new(components: &HashMap<String, Component>) -> Self {
+        Self {
+            scopes: Vec::new(),
+            frames: vec![Frame {
+                base: 0,
+                caller: None,
+                components: components.clone(),
+                file: None,
+                scope: None,
+            }],
+            frame: 0,
+            layouts: Vec::new(),
+            regions: Vec::new(),
+            parents: Vec::new(),
+            out: String::new(),
+        }
+    }
+
+    /// 设置页面的布局，从外到内排列
+    pub fn with_layouts(mut self, layouts: &[Arc<ComponentTemplate>]) -> Self {
+        self.layouts = layouts.to_vec();
+        self
+    }
+
+    /// 设置页面模板的样式作用域属性
+    pub fn with_scope(mut self, scope: Option<String>) -> Self {
+        self.frames[0].scope = scope;
+        self
+    }
+
+    /// 生成Handlebars模板
+    pub fn generate(self, nodes: &[Node]) -> Result<String, ParseError> {
+        Ok(self.generate_page(nodes)?.0)
+    }
+
+    /// 生成页面模板及其中`<defer>`区块的模板
+    pub fn generate_page(mut self, nodes: &[Node]) -> Result<(String, Vec<Region>), ParseError> {
+        let mut nodes = nodes.to_vec();
+        for (index, layout) in self.layouts.iter().enumerate().rev() {
+            let name = format!("{LAYOUT_PREFIX}{index}");
+            self.frames[0]
+                .components
+                .insert(name.clone(), Component::Rsx(layout.clone()));
+            nodes = vec![Node::Element(Element {
+                name,
+                attrs: Vec::new(),
+                children: nodes,
+                self_closing: false,
+                trailing: String::new(),
+                position: Position::default(),
+            })];
+        }
+        if self.is_document(&nodes) {
+            self.out.push_str("<!DOCTYPE html>");
+        }
+        self.nodes(&nodes)?;
+        Ok((self.out, self.regions))
+    }
+
+    /// 第一个元素是否为`<html>`，最外层为布局时检查布局的模板
+    fn is_document(&self, nodes: &[Node]) -> bool {
+        let Some(Node::Element(element)) = nodes.iter().find(|node| {
+            !matches!(node, Node::Text(text) if text.trim().is_empty())
+                && !matches!(node, Node::Comment(_))
+        }) else {
+            return false;
+        };
+        if let Some(index) = element.name.strip_prefix(LAYOUT_PREFIX)
+            && let Some(layout) = index.parse().ok().and_then(|i: usize| self.layouts.get(i))
+        {
+            return self.is_document(&layout.nodes);
+        }
+        element.name.eq_ignore_ascii_case("html")
+    }
+
+    fn nodes(&mut self, nodes: &[Node]) -> Result<(), ParseError> {
+        for node in nodes {
+            self.node(node)?;
+        }
+        Ok(())
+    }
+
+    fn node(&mut self, node: &Node) -> Result<(), ParseError> {
+        match node {
+            Node::Text(text) | Node::Comment(text) | Node::Handlebars(text) => self.push_text(text),
+            Node::Expr(expr) => {
+                let value = self.value(expr)?;
+                self.out
+                    .push_str(&format!("{{{{{}}}}}", strip_parens(&value)));
+            }
+            Node::Html(expr) => {
+                let value = self.value(expr)?;
+                self.out
+                    .push_str(&format!("{{{{{{{}}}}}}}", strip_parens(&value)));
+            }
+            Node::If {
+                branches,
+                otherwise,
+            } => self.if_block(branches, otherwise.as_deref())?,
+            Node::Each {
+                items,
+                item,
+                index,
+                body,
+                otherwise,
+            } => {
+                let items = self.value(items)?;
+                let params = match index {
+                    Some(index) => format!("{item} {index}"),
+                    None => item.clone(),
+                };
+                self.out
+                    .push_str(&format!("{{{{#each {items} as |{params}|}}}}"));
+                let mut params = vec![item.clone()];
+                params.extend(index.clone());
+                self.scopes.push(Scope {
+                    params,
+                    frame: self.frame,
+                });
+                let result = self.nodes(body);
+                self.scopes.pop();
+                result?;
+                if let Some(otherwise) = otherwise {
+                    self.out.push_str("{{else}}");
+                    self.nodes(otherwise)?;
+                }
+                self.out.push_str("{{/each}}");
+            }
+            Node::Element(element) if element.is_component() => self.component(element)?,
+            Node::Element(element) if element.name == "slot" => self.slot(element)?,
+            Node::Element(element) if element.name == "defer" => self.defer(element)?,
+            Node::Element(element) => self.element(element)?,
+        }
+        Ok(())
+    }
+
+    /// `{:else if}`编译为嵌套的`{{#if}}`
+    fn if_block(
+        &mut self,
+        branches: &[(Expression, Vec<Node>)],
+        otherwise: Option<&[Node]>,
+    ) -> Result<(), ParseError> {
+        let Some(((condition, body), rest)) = branches.split_first() else {
+            if let Some(otherwise) = otherwise {
+                self.nodes(otherwise)?;
+            }
+            return Ok(());
+        };
+        let condition = self.value(condition)?;
+        self.out.push_str(&format!("{{{{#if {condition}}}}}"));
+        self.nodes(body)?;
+        if !rest.is_empty() || otherwise.is_some() {
+            self.out.push_str("{{else}}");
+            self.if_block(rest, otherwise)?;
+        }
+        self.out.push_str("{{/if}}");
+        Ok(())
+    }
+
+    fn element(&mut self, element: &Element) -> Result<(), ParseError> {
+        let name = element.name.to_ascii_lowercase();
+        let parent = self.parents.last().map(String::as_str);
+        if parent == Some("head") && HEAD_TAGS.contains(&name.as_str()) {
+            return self.head_tag(element, &name);
+        }
+        // 没有`<html>`的页面直接写`<head>`和`<body>`，最外层的`<head>`同样是文档`<head>`
+        let document_head =
+            name == "head" && (parent == Some("html") || parent.is_none() && self.frame == 0);
+        if name == "head" && !document_head {
+            // 不属于文档的`<head>`只收集其中的标签
+            for attr in &element.attrs {
+                self.push_newlines(&attr.leading);
+            }
+            self.push_newlines(&element.trailing);
+            return self.children(element, &name);
+        }
+        self.out.push('<');
+        self.out.push_str(&element.name);
+        if let Some(scope) = &self.frames[self.frame].scope
+            && !UNSTYLED_ELEMENTS.contains(&name.as_str())
+        {
+            self.out.push(' ');
+            self.out.push_str(scope);
+        }
+        for attr in &element.attrs {
+            self.attr(attr)?;
+        }
+        self.push_text(&element.trailing);
+        if element.self_closing {
+            self.out.push_str("/>");
+            return Ok(());
+        }
+        self.out.push('>');
+        if element.is_void() {
+            return Ok(());
+        }
+        self.children(element, &name)?;
+        if document_head {
+            self.out.push_str(HEAD_OUTLET);
+        }
+        self.out.push_str(&format!("</{}>", element.name));
+        Ok(())
+    }
+
+    fn children(&mut self, element: &Element, name: &str) -> Result<(), ParseError> {
+        self.parents.push(name.to_string());
+        let result = self.nodes(&element.children);
+        self.parents.pop();
+        result
+    }
+
+    /// `<head>`中的标签编译为`{{#rsx_head "key"}}`块，`key`属性不输出
+    fn head_tag(&mut self, element: &Element, name: &str) -> Result<(), ParseError> {
+        let texts: Vec<(String, String)> = element
+            .attrs
+            .iter()
+            .filter_map(|attr| Some((attr.name.to_ascii_lowercase(), attr.text()?)))
+            .collect();
+        let key = head::tag_key(name, |attr| {
+            texts
+                .iter()
+                .find(|(name, _)| name == attr)
+                .map(|(_, value)| value.as_str())
+        });
+        self.out.push_str(&format!(
+            "{{{{#{HEAD_HELPER} \"{}\"}}}}<{}",
+            escape_string(&key),
+            element.name
+        ));
+        for attr in &element.attrs {
+            if attr.name == KEY_ATTR {
+                self.push_newlines(&attr.leading);
+            } else {
+                self.attr(attr)?;
+            }
+        }
+        self.push_text(&element.trailing);
+        if element.self_closing {
+            self.out.push_str("/>");
+        } else {
+            self.out.push('>');
+            if !element.is_void() {
+                self.children(element, name)?;
+                self.out.push_str(&format!("</{}>", element.name));
+            }
+        }
+        self.out.push_str(&format!("{{{{/{HEAD_HELPER}}}}}"));
+        Ok(())
+    }
+
+    fn attr(&mut self, attr: &Attr) -> Result<(), ParseError> {
+        let name = match attr.name.as_str() {
+            name if is_client_only(name) => None,
+            name => Some(
+                name.strip_prefix("bind:")
+                    .or_else(|| name.strip_prefix(':'))
+                    .unwrap_or(name),
+            ),
+        };
+        let Some(name) = name else {
+            self.push_newlines(&attr.leading);
+            return Ok(());
+        };
+        self.push_text(&attr.leading);
+        self.out.push_str(name);
+        match &attr.value {
+            AttrValue::Empty => {}
+            AttrValue::Expr(expr) => {
+                let value = self.value(expr)?;
+                self.out
+                    .push_str(&format!("=\"{{{{{}}}}}\"", strip_parens(&value)));
+            }
+            AttrValue::Parts(parts) => {
+                self.out.push_str("=\"");
+                for part in parts {
+                    match part {
+                        AttrPart::Text(text) => self.out.push_str(&text.replace('"', "&quot;")),
+                        AttrPart::Handlebars(text) => self.out.push_str(text),
+                        AttrPart::Expr(expr) => {
+                            let value = self.value(expr)?;
+                            self.out
+                                .push_str(&format!("{{{{{}}}}}", strip_parens(&value)));
+                        }
+                    }
+                }
+                self.out.push('"');
+            }
+        }
+        Ok(())
+    }
+
+    fn component(&mut self, element: &Element) -> Result<(), ParseError> {
+        let component = self.frames[self.frame]
+            .components
+            .get(&element.name)
+            .cloned()
+            .ok_or_else(|| ParseError {
+                message: format!(
+                    "unknown component <{}>, import it in the <script> block",
+                    element.name
+                ),
+                line: element.position.line,
+                column: element.position.column,
+                file: self.frames[self.frame].file.clone(),
+            })?;
+        let template = match component {
+            Component::Rsx(template) => template,
+            Component::Client(src) => return self.island(element, &src),
+        };
+
+        let mut hash = String::new();
+        for attr in &element.attrs {
+            if is_client_only(&attr.name) {
+                continue;
+            }
+            if self.frame == 0 {
+                hash.push_str(&attr.leading.replace(|c: char| c != '\n', ""));
+            }
+            hash.push(' ');
+            hash.push_str(&attr.name);
+            hash.push('=');
+            hash.push_str(&self.attr_value(attr)?);
+        }
+        let flag = if self.frame == 0 && element.name.starts_with(LAYOUT_PREFIX) {
+            format!(" \"{LAYOUT_FLAG}\"")
+        } else {
+            String::new()
+        };
+        self.out.push_str(&format!(
+            "{{{{#{COMPONENT_HELPER} \"{}\"{flag}{hash}}}}}",
+            escape_string(&template.id)
+        ));
+
+        let slots = split_slots(&element.children);
+        let caller = self.frame;
+        self.scopes.push(Scope {
+            params: Vec::new(),
+            frame: caller,
+        });
+        self.frames.push(Frame {
+            base: self.scopes.len(),
+            caller: Some((caller, slots)),
+            components: template.components.clone(),
+            file: Some(template.path.clone()),
+            scope: template
+                .style
+                .as_ref()
+                .and_then(|style| style.scope.clone()),
+        });
+        self.frame = self.frames.len() - 1;
+        let result = self.nodes(&template.nodes);
+        let frame = self.frame;
+        self.frame = caller;
+        self.scopes.pop();
+        result?;
+        self.out.push_str(&format!("{{{{/{COMPONENT_HELPER}}}}}"));
+
+        // 未被组件使用的插槽内容不输出，只保留换行
+        if let Some((_, slots)) = self.frames[frame].caller.take() {
+            for nodes in slots.values() {
+                for node in nodes {
+                    self.push_newlines_of_node(node);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 带`client:*`指令的客户端组件输出为岛，其他属性作为组件props，没有指令时不渲染
+    fn island(&mut self, element: &Element, src: &str) -> Result<(), ParseError> {
+        let directive = element.attrs.iter().find_map(|attr| {
+            let directive = attr.name.strip_prefix("client:")?;
+            CLIENT_DIRECTIVES
+                .contains(&directive)
+                .then(|| (directive, attr.text().unwrap_or_default()))
+        });
+        if let Some((directive, value)) = directive {
+            let mut hash = String::new();
+            for attr in element
+                .attrs
+                .iter()
+                .filter(|attr| !is_client_only(&attr.name))
+            {
+                hash.push_str(&format!(" {}={}", attr.name, self.attr_value(attr)?));
+            }
+            self.out.push_str(&format!(
+                "{{{{{ISLAND_HELPER} \"{}\" \"{}\" \"{directive}\" \"{}\"{hash}}}}}",
+                escape_string(&element.name),
+                escape_string(src),
+                escape_string(&value)
+            ));
+        }
+        self.push_newlines_of(element);
+        Ok(())
+    }
+
+    /// `<slot name="x">fallback</slot>`替换为调用方传入的插槽内容，在调用方的作用域中编译
+    fn slot(&mut self, element: &Element) -> Result<(), ParseError> {
+        let name = element
+            .attr("name")
+            .and_then(Attr::text)
+            .unwrap_or_else(|| DEFAULT_SLOT.to_string());
+        let frame = self.frame;
+        let content = self.frames[frame]
+            .caller
+            .as_mut()
+            .and_then(|(caller, slots)| Some((*caller, slots.remove(&name)?)));
+        let Some((caller, nodes)) = content else {
+            return self.nodes(&element.children);
+        };
+        self.frame = caller;
+        let result = self.nodes(&nodes);
+        self.frame = frame;
+        result
+    }
+
+    /// `<defer>`区块，内容按页面的根上下文单独编译，因此不能位于组件模板或`{#each}`中
+    fn defer(&mut self, element: &Element) -> Result<(), ParseError> {
+        let error = |message: &str| ParseError {
+            message: message.to_string(),
+            line: element.position.line,
+            column: element.position.column,
+            file: self.frames[self.frame].file.clone(),
+        };
+        if self.frame != 0 {
+            return Err(error("<defer> can only be used in page templates"));
+        }
+        if self
+            .scopes
+            .iter()
+            .any(|scope| scope.frame == 0 && !scope.params.is_empty())
+        {
+            return Err(error("<defer> cannot be used inside {#each}"));
+        }
+        let mut slots = split_slots(&element.children);
+        let fallback = slots.remove(FALLBACK_SLOT).unwrap_or_default();
+        let content = slots.remove(DEFAULT_SLOT).unwrap_or_default();
+
+        let mut region =
+            Codegen::new(&self.frames[0].components).with_scope(self.frames[0].scope.clone());
+        region.nodes(&content)?;
+        if !region.regions.is_empty() {
+            return Err(error("<defer> cannot be nested"));
+        }
+        let index = self.regions.len();
+        let awaits = element
+            .attrs
+            .iter()
+            .find(|attr| attr.name == AWAIT_ATTR)
+            .map(|attr| {
+                attr.text()
+                    .ok_or_else(|| error("<defer await> must be a plain text list of keys"))
+            })
+            .transpose()?
+            .unwrap_or_default();
+        self.regions.push(Region {
+            template: region.out,
+            awaits: awaits
+                .split(',')
+                .map(str::trim)
+                .filter(|key| !key.is_empty())
+                .map(str::to_string)
+                .collect(),
+        });
+
+        self.out
+            .push_str(&format!("{{{{#{DEFER_HELPER} {index}}}}}"));
+        self.nodes(&content)?;
+        self.out.push_str("{{else}}");
+        self.nodes(&fallback)?;
+        self.out.push_str(&format!("{{{{/{DEFER_HELPER}}}}}"));
+        for attr in &element.attrs {
+            self.push_newlines(&attr.leading);
+        }
+        self.push_newlines(&element.trailing);
+        for nodes in slots.values() {
+            for node in nodes {
+                self.push_newlines_of_node(node);
+            }
+        }
+        Ok(())
+    }
+
+    /// 组件属性转换为组件helper的hash参数
+    fn attr_value(&self, attr: &Attr) -> Result<String, ParseError> {
+        Ok(match &attr.value {
+            AttrValue::Empty => "true".to_string(),
+            AttrValue::Expr(expr) => self.value(expr)?,
+            AttrValue::Parts(parts) => match parts.as_slice() {
+                [AttrPart::Expr(expr)] => self.value(expr)?,
+                _ => {
+                    let text = attr.text().unwrap_or_default();
+                    format!("\"{}\"", escape_string(&text))
+                }
+            },
+        })
+    }
+
+    /// 将表达式转换为Handlebars参数，简单路径直接引用，其他表达式交给helper求值
+    fn value(&self, expression: &Expression) -> Result<String, ParseError> {
+        let expr = Expr::parse(&expression.source).map_err(|message| ParseError {
+            message,
+            line: expression.position.line,
+            column: expression.position.column,
+            file: self.frames[self.frame].file.clone(),
+        })?;
+        if let Some((root, path)) = expr.as_path() {
+            return Ok(self.path(&root, &path));
+        }
+        let mut out = format!("({EXPR_HELPER} \"{}\"", escape_string(&expression.source));
+        for root in expr.roots() {
+            out.push_str(&format!(" {root}={}", self.path(&root, &[])));
+        }
+        out.push(')');
+        Ok(out)
+    }
+
+    /// 生成Handlebars路径，非块参数的变量需要跳出`{{#each}}`和组件的上下文，
+    /// 回到当前帧的上下文
+    fn path(&self, root: &str, path: &[PathSegment]) -> String {
+        let is_param = self
+            .scopes
+            .iter()
+            .any(|scope| scope.frame == self.frame && scope.params.iter().any(|p| p == root));
+        let mut out = if is_param {
+            String::new()
+        } else {
+            "../".repeat(self.scopes.len() - self.frames[self.frame].base)
+        };
+        out.push_str(root);
+        for segment in path {
+            match segment {
+                PathSegment::Key(key) if is_identifier(key) => {
+                    out.push('.');
+                    out.push_str(key);
+                }
+                PathSegment::Key(key) => out.push_str(&format!(".[{key}]")),
+                PathSegment::Index(index) => out.push_str(&format!(".[{index}]")),
+            }
+        }
+        out
+    }
+
+    /// 输出文本，内联的组件模板中的换行替换为空格
+    fn push_text(&mut self, text: &str) {
+        if self.frame == 0 {
+            self.out.push_str(text);
+        } else {
+            self.out.push_str(&text.replace('\n', " "));
+        }
+    }
+
+    fn push_newlines(&mut self, text: &str) {
+        if self.frame != 0 {
+            return;
+        }
+        for _ in text.matches('\n') {
+            self.out.push('\n');
+        }
+    }
+
+    fn push_newlines_of(&mut self, element: &Element) {
+        for attr in &element.attrs {
+            self.push_newlines(&attr.leading);
+        }
+        for child in &element.children {
+            self.push_newlines_of_node(child);
+        }
+    }
+
+    fn push_newlines_of_node(&mut self, node: &Node) {
+        match node {
+            Node::Text(text) => self.push_newlines(text),
+            Node::Element(element) => self.push_newlines_of(element),
+            _ => {}
+        }
+    }
+}
+
+/// 按`slot`属性拆分组件的子节点，`<template slot="x">`只传入其子节点，
+/// 其他带`slot`属性的元素整体传入，其余节点属于默认插槽
+fn split_slots(children: &[Node]) -> HashMap<String, Vec<Node>> {
+    let mut slots: HashMap<String, Vec<Node>> = HashMap::new();
+    for child in children {
+        let named = match child {
+            Node::Element(element) => element
+                .attr("slot")
+                .and_then(Attr::text)
+                .map(|name| (name, element)),
+            _ => None,
+        };
+        match named {
+            Some((name, element)) if element.name == "template" => {
+                slots
+                    .entry(name)
+                    .or_default()
+                    .extend(element.children.clone());
+            }
+            Some((name, element)) => {
+                let mut element = element.clone();
+                element.attrs.retain(|attr| attr.name != "slot");
+                slots.entry(name).or_default().push(Node::Element(element));
+            }
+            None => slots
+                .entry(DEFAULT_SLOT.to_string())
+                .or_default()
+                .push(child.clone()),
+        }
+    }
+    // 只有空白和注释的默认插槽视为未传入，使用组件的默认内容
+    if slots.get(DEFAULT_SLOT).is_some_and(|nodes| {
+        nodes.iter().all(|node| match node {
+            Node::Text(text) => text.trim().is_empty(),
+            Node::Comment(_) => true,
+            _ => false,
+        })
+    }) && let Some(nodes) = slots.remove(DEFAULT_SLOT)
+    {
+        // 保留默认插槽中的换行
+        slots.insert(String::new(), nodes);
+    }
+    slots
+}
+
+/// 只在客户端生效的属性，服务端渲染时忽略
+fn is_client_only(name: &str) -> bool {
+    matches!(name, ":key" | "bind:key") || name.starts_with("on:") || name.starts_with("client:")
+}
+
+fn is_identifier(key: &str) -> bool {
+    key.chars()
+        .all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+        && !key.starts_with(|c: char| c.is_ascii_digit())
+}
+
+/// 表达式直接输出时不需要子表达式的括号
+fn strip_parens(value: &str) -> &str {
+    value
+        .strip_prefix('(')
+        .and_then(|value| value.strip_suffix(')'))
+        .unwrap_or(value)
+}
+
+/// 转义Handlebars字符串字面量
+pub fn escape_string(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,578 @@
+//! 模板表达式
+//!
+//! 支持模板中常用的JavaScript表达式子集：字面量、属性访问、算术、比较、逻辑和三元运算
+
+use serde_json::{Number, Value};
+use std::collections::HashMap;
+
+/// 属性访问路径中的一段
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// 表达式语法树
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Value),
+    Ident(String),
+    Member(Box<Expr>, PathSegment),
+    Computed(Box<Expr>, Box<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOp {
+    Not,
+    Neg,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    StrictEq,
+    StrictNe,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+    Coalesce,
+}
+
+impl BinaryOp {
+    fn precedence(&self) -> u8 {
+        match self {
+            BinaryOp::Coalesce => 1,
+            BinaryOp::Or => 2,
+            BinaryOp::And => 3,
+            BinaryOp::Eq | BinaryOp::Ne | BinaryOp::StrictEq | BinaryOp::StrictNe => 4,
+            BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => 5,
+            BinaryOp::Add | BinaryOp::Sub => 6,
+            BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => 7,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Str(String),
+    Ident(String),
+    Punct(&'static str),
+}
+
+const PUNCTS: [&str; 24] = [
+    "===", "!==", "==", "!=", "<=", ">=", "&&", "||", "??", "<", ">", "+", "-", "*", "/", "%", "!",
+    "?", ":", ".", "(", ")", "[", "]",
+];
+
+impl Expr {
+    /// 解析表达式
+    pub fn parse(source: &str) -> Result<Expr, String> {
+        let tokens = tokenize(source)?;
+        let mut parser = ExprParser { tokens, pos: 0 };
+        let expr = parser.parse_conditional()?;
+        if let Some(token) = parser.tokens.get(parser.pos) {
+            return Err(format!(
+                "unexpected token {token:?} in expression `{source}`"
+            ));
+        }
+        Ok(expr)
+    }
+
+    /// 如果表达式是简单的属性访问路径，返回根标识符和路径
+    pub fn as_path(&self) -> Option<(String, Vec<PathSegment>)> {
+        match self {
+            Expr::Ident(name) => Some((name.clone(), Vec::new())),
+            Expr::Member(object, segment) => {
+                let (root, mut path) = object.as_path()?;
+                path.push(segment.clone());
+                Some((root, path))
+            }
+            Expr::Computed(object, property) => {
+                let (root, mut path) = object.as_path()?;
+                match property.as_ref() {
+                    Expr::Literal(Value::Number(n)) => {
+                        path.push(PathSegment::Index(n.as_u64()? as usize))
+                    }
+                    Expr::Literal(Value::String(key)) => path.push(PathSegment::Key(key.clone())),
+                    _ => return None,
+                }
+                Some((root, path))
+            }
+            _ => None,
+        }
+    }
+
+    /// 收集表达式引用的根标识符
+    pub fn roots(&self) -> Vec<String> {
+        let mut roots = Vec::new();
+        self.collect_roots(&mut roots);
+        roots
+    }
+
+    fn collect_roots(&self, roots: &mut Vec<String>) {
+        match self {
+            Expr::Literal(_) => {}
+            Expr::Ident(name) => {
+                if !roots.contains(name) {
+                    roots.push(name.clone());
+                }
+            }
+            Expr::Member(object, _) => object.collect_roots(roots),
+            Expr::Computed(object, property) => {
+                object.collect_roots(roots);
+                property.collect_roots(roots);
+            }
+            Expr::Unary(_, operand) => operand.collect_roots(roots),
+            Expr::Binary(_, left, right) => {
+                left.collect_roots(roots);
+                right.collect_roots(roots);
+            }
+            Expr::Conditional(test, consequent, alternate) => {
+                test.collect_roots(roots);
+                consequent.collect_roots(roots);
+                alternate.collect_roots(roots);
+            }
+        }
+    }
+
+    /// 在给定作用域中求值
+    pub fn eval(&self, scope: &HashMap<String, Value>) -> Value {
+        match self {
+            Expr::Literal(value) => value.clone(),
+            Expr::Ident(name) => scope.get(name).cloned().unwrap_or(Value::Null),
+            Expr::Member(object, segment) => member(&object.eval(scope), segment),
+            Expr::Computed(object, property) => {
+                let segment = match property.eval(scope) {
+                    Value::Number(n) => match n.as_u64() {
+                        Some(index) => PathSegment::Index(index as usize),
+                        None => return Value::Null,
+                    },
+                    Value::String(key) => PathSegment::Key(key),
+                    _ => return Value::Null,
+                };
+                member(&object.eval(scope), &segment)
+            }
+            Expr::Unary(UnaryOp::Not, operand) => Value::Bool(!truthy(&operand.eval(scope))),
+            Expr::Unary(UnaryOp::Neg, operand) => number(-to_number(&operand.eval(scope))),
+            Expr::Binary(op, left, right) => {
+                let left = left.eval(scope);
+                match op {
+                    BinaryOp::And if !truthy(&left) => left,
+                    BinaryOp::Or if truthy(&left) => left,
+                    BinaryOp::Coalesce if !left.is_null() => left,
+                    BinaryOp::And | BinaryOp::Or | BinaryOp::Coalesce => right.eval(scope),
+                    _ => binary(*op, &left, &right.eval(scope)),
+                }
+            }
+            Expr::Conditional(test, consequent, alternate) => {
+                if truthy(&test.eval(scope)) {
+                    consequent.eval(scope)
+                } else {
+                    alternate.eval(scope)
+                }
+            }
+        }
+    }
+}
+
+/// 按JavaScript规则判断真值
+pub fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64().is_some_and(|n| n != 0.0 && !n.is_nan()),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(_) | Value::Object(_) => true,
+    }
+}
+
+fn member(object: &Value, segment: &PathSegment) -> Value {
+    match (object, segment) {
+        (Value::Array(items), PathSegment::Key(key)) if key == "length" => Value::from(items.len()),
+        (Value::String(s), PathSegment::Key(key)) if key == "length" => {
+            Value::from(s.chars().count())
+        }
+        (Value::Array(items), PathSegment::Index(index)) => {
+            items.get(*index).cloned().unwrap_or(Value::Null)
+        }
+        (Value::Object(map), PathSegment::Key(key)) => map.get(key).cloned().unwrap_or(Value::Null),
+        (Value::Object(map), PathSegment::Index(index)) => {
+            map.get(&index.to_string()).cloned().unwrap_or(Value::Null)
+        }
+        _ => Value::Null,
+    }
+}
+
+fn to_number(value: &Value) -> f64 {
+    match value {
+        Value::Null => 0.0,
+        Value::Bool(b) => f64::from(u8::from(*b)),
+        Value::Number(n) => n.as_f64().unwrap_or(f64::NAN),
+        Value::String(s) if s.trim().is_empty() => 0.0,
+        Value::String(s) => s.trim().parse().unwrap_or(f64::NAN),
+        _ => f64::NAN,
+    }
+}
+
+fn number(n: f64) -> Value {
+    if n.fract() == 0.0 && n.abs() < 9_007_199_254_740_992.0 {
+        Value::from(n as i64)
+    } else {
+        Number::from_f64(n)
+            .map(Value::Number)
+            .unwrap_or(Value::Null)
+    }
+}
+
+fn to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => "null".to_string(),
+        other => other.to_string(),
+    }
+}
+
+fn loose_eq(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Number(_), Value::String(_)) | (Value::String(_), Value::Number(_)) => {
+            to_number(left) == to_number(right)
+        }
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        _ => left == right,
+    }
+}
+
+/// 严格相等，类型不同时不做转换；数字按数值比较，与JavaScript中`1 === 1.0`一致
+fn strict_eq(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        _ => left == right,
+    }
+}
+
+fn binary(op: BinaryOp, left: &Value, right: &Value) -> Value {
+    match op {
+        BinaryOp::Add if left.is_string() || right.is_string() => {
+            Value::String(to_string(left) + &to_string(right))
+        }
+        BinaryOp::Add => number(to_number(left) + to_number(right)),
+        BinaryOp::Sub => number(to_number(left) - to_number(right)),
+        BinaryOp::Mul => number(to_number(left) * to_number(right)),
+        BinaryOp::Div => number(to_number(left) / to_number(right)),
+        BinaryOp::Rem => number(to_number(left) % to_number(right)),
+        BinaryOp::Eq => Value::Bool(loose_eq(left, right)),
+        BinaryOp::Ne => Value::Bool(!loose_eq(left, right)),
+        BinaryOp::StrictEq => Value::Bool(strict_eq(left, right)),
+        BinaryOp::StrictNe => Value::Bool(!strict_eq(left, right)),
+        BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => {
+            let ordering = match (left, right) {
+                (Value::String(a), Value::String(b)) => a.partial_cmp(b),
+                _ => to_number(left).partial_cmp(&to_number(right)),
+            };
+            Value::Bool(ordering.is_some_and(|ordering| match op {
+                BinaryOp::Lt => ordering.is_lt(),
+                BinaryOp::Le => ordering.is_le(),
+                BinaryOp::Gt => ordering.is_gt(),
+                _ => ordering.is_ge(),
+            }))
+        }
+        BinaryOp::And | BinaryOp::Or | BinaryOp::Coalesce => unreachable!(),
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let n = text
+                .parse()
+                .map_err(|_| format!("invalid number `{text}` in expression `{source}`"))?;
+            tokens.push(Token::Number(n));
+        } else if c == '\'' || c == '"' || c == '`' {
+            let mut value = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err(format!("unterminated string in expression `{source}`")),
+                    Some(ch) if *ch == c => break,
+                    Some('\\') => {
+                        i += 1;
+                        match chars.get(i) {
+                            Some('n') => value.push('\n'),
+                            Some('t') => value.push('\t'),
+                            Some(ch) => value.push(*ch),
+                            None => {}
+                        }
+                    }
+                    Some(ch) => value.push(*ch),
+                }
+                i += 1;
+            }
+            i += 1;
+            tokens.push(Token::Str(value));
+        } else if c.is_alphabetic() || c == '_' || c == '$' {
+            let start = i;
+            while i < chars.len()
+                && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$')
+            {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else {
+            let rest: String = chars[i..chars.len().min(i + 3)].iter().collect();
+            let punct = PUNCTS
+                .iter()
+                .find(|p| rest.starts_with(**p))
+                .ok_or_else(|| format!("unexpected character `{c}` in expression `{source}`"))?;
+            i += punct.chars().count();
+            tokens.push(Token::Punct(punct));
+        }
+    }
+    Ok(tokens)
+}
+
+struct ExprParser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl ExprParser {
+    fn peek_punct(&self) -> Option<&'static str> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Punct(p)) => Some(p),
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<(), String> {
+        if self.peek_punct() == Some(punct) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!("expected `{punct}` in expression"))
+        }
+    }
+
+    fn parse_conditional(&mut self) -> Result<Expr, String> {
+        let test = self.parse_binary(0)?;
+        if self.peek_punct() != Some("?") {
+            return Ok(test);
+        }
+        self.pos += 1;
+        let consequent = self.parse_conditional()?;
+        self.expect(":")?;
+        let alternate = self.parse_conditional()?;
+        Ok(Expr::Conditional(
+            Box::new(test),
+            Box::new(consequent),
+            Box::new(alternate),
+        ))
+    }
+
+    fn binary_op(&self) -> Option<BinaryOp> {
+        Some(match self.peek_punct()? {
+            "+" => BinaryOp::Add,
+            "-" => BinaryOp::Sub,
+            "*" => BinaryOp::Mul,
+            "/" => BinaryOp::Div,
+            "%" => BinaryOp::Rem,
+            "==" => BinaryOp::Eq,
+            "!=" => BinaryOp::Ne,
+            "===" => BinaryOp::StrictEq,
+            "!==" => BinaryOp::StrictNe,
+            "<" => BinaryOp::Lt,
+            "<=" => BinaryOp::Le,
+            ">" => BinaryOp::Gt,
+            ">=" => BinaryOp::Ge,
+            "&&" => BinaryOp::And,
+            "||" => BinaryOp::Or,
+            "??" => BinaryOp::Coalesce,
+            _ => return None,
+        })
+    }
+
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Expr, String> {
+        let mut left = self.parse_unary()?;
+        while let Some(op) = self.binary_op() {
+            if op.precedence() <= min_precedence {
+                break;
+            }
+            self.pos += 1;
+            let right = self.parse_binary(op.precedence())?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, String> {
+        match self.peek_punct() {
+            Some("!") => {
+                self.pos += 1;
+                Ok(Expr::Unary(UnaryOp::Not, Box::new(self.parse_unary()?)))
+            }
+            Some("-") => {
+                self.pos += 1;
+                Ok(Expr::Unary(UnaryOp::Neg, Box::new(self.parse_unary()?)))
+            }
+            _ => self.parse_postfix(),
+        }
+    }
+
+    fn parse_postfix(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_primary()?;
+        loop {
+            match self.peek_punct() {
+                Some(".") => {
+                    self.pos += 1;
+                    match self.tokens.get(self.pos) {
+                        Some(Token::Ident(name)) => {
+                            expr = Expr::Member(Box::new(expr), PathSegment::Key(name.clone()));
+                            self.pos += 1;
+                        }
+                        _ => return Err("expected property name after `.`".to_string()),
+                    }
+                }
+                Some("[") => {
+                    self.pos += 1;
+                    let property = self.parse_conditional()?;
+                    self.expect("]")?;
+                    expr = match property {
+                        Expr::Literal(Value::Number(n)) if n.is_u64() => Expr::Member(
+                            Box::new(expr),
+                            PathSegment::Index(n.as_u64().unwrap_or_default() as usize),
+                        ),
+                        property => Expr::Computed(Box::new(expr), Box::new(property)),
+                    };
+                }
+                _ => return Ok(expr),
+            }
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, String> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| "unexpected end of expression".to_string())?;
+        self.pos += 1;
+        match token {
+            Token::Number(n) => Ok(Expr::Literal(number(n))),
+            Token::Str(s) => Ok(Expr::Literal(Value::String(s))),
+            Token::Ident(name) => Ok(match name.as_str() {
+                "true" => Expr::Literal(Value::Bool(true)),
+                "false" => Expr::Literal(Value::Bool(false)),
+                "null" | "undefined" => Expr::Literal(Value::Null),
+                _ => Expr::Ident(name),
+            }),
+            Token::Punct("(") => {
+                let expr = self.parse_conditional()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Token::Punct(p) => Err(format!("unexpected `{p}` in expression")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn eval(source: &str, scope: Value) -> Value {
+        let scope: HashMap<String, Value> = serde_json::from_value(scope).unwrap();
+        Expr::parse(source).unwrap().eval(&scope)
+    }
+
+    #[test]
+    fn eval_arithmetic_and_comparison() {
+        assert_eq!(eval("item % 2 == 0", json!({"item": 4})), json!(true));
+        assert_eq!(eval("item % 2 == 0", json!({"item": 3})), json!(false));
+        assert_eq!(eval("1 + 2 * 3", json!({})), json!(7));
+        assert_eq!(eval("(1 + 2) * 3 >= 9", json!({})), json!(true));
+        assert_eq!(eval("'a' + n", json!({"n": 1})), json!("a1"));
+        assert_eq!(eval("-n", json!({"n": 1.5})), json!(-1.5));
+    }
+
+    #[test]
+    fn eval_strict_equality() {
+        let scope = json!({"id": "1", "n": 1, "flag": true});
+        assert_eq!(eval("id == 1", scope.clone()), json!(true));
+        assert_eq!(eval("id === 1", scope.clone()), json!(false));
+        assert_eq!(eval("id !== 1", scope.clone()), json!(true));
+        assert_eq!(eval("id === '1'", scope.clone()), json!(true));
+        assert_eq!(eval("n === 1.0", scope.clone()), json!(true));
+        assert_eq!(eval("flag === 1", scope.clone()), json!(false));
+        assert_eq!(eval("missing === null", scope), json!(true));
+    }
+
+    #[test]
+    fn eval_member_access() {
+        let scope = json!({"user": {"name": "jack", "tags": ["a", "b"]}});
+        assert_eq!(eval("user.name", scope.clone()), json!("jack"));
+        assert_eq!(eval("user.tags[1]", scope.clone()), json!("b"));
+        assert_eq!(eval("user['name']", scope.clone()), json!("jack"));
+        assert_eq!(eval("user.tags.length", scope.clone()), json!(2));
+        assert_eq!(eval("user.missing.name", scope), Value::Null);
+    }
+
+    #[test]
+    fn eval_logical_and_conditional() {
+        let scope = json!({"isActive": true, "name": ""});
+        assert_eq!(
+            eval("isActive ? 'active' : 'inactive'", scope.clone()),
+            json!("active")
+        );
+        assert_eq!(eval("name || 'guest'", scope.clone()), json!("guest"));
+        assert_eq!(eval("name ?? 'guest'", scope.clone()), json!(""));
+        assert_eq!(eval("!isActive && name", scope), json!(false));
+    }
+
+    #[test]
+    fn expr_paths_and_roots() {
+        let expr = Expr::parse("user.tags[0]").unwrap();
+        let (root, path) = expr.as_path().unwrap();
+        assert_eq!(root, "user");
+        assert_eq!(
+            path,
+            vec![PathSegment::Key("tags".to_string()), PathSegment::Index(0)]
+        );
+        assert!(Expr::parse("a + b").unwrap().as_path().is_none());
+        assert_eq!(
+            Expr::parse("a.x > b ? a : c").unwrap().roots(),
+            vec!["a", "b", "c"]
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(Expr::parse("a +").is_err());
+        assert!(Expr::parse("a b").is_err());
+        assert!(Expr::parse("'abc").is_err());
+        assert!(Expr::parse("a # b").is_err());
+    }
+}
@@ -0,0 +1,209 @@
+//! `<head>`管理
+//!
+//! 文档`<head>`中的`<title>`、`<meta>`、`<link>`、`<base>`和`<script>`不直接输出，
+//! 渲染时按键收集，最后在`</head>`之前统一输出。文档`<head>`为`<html>`中的`<head>`，
+//! 以及页面模板最外层的`<head>`。组件模板中不属于文档的`<head>`元素
+//! 本身不输出，其中的标签同样收集到文档`<head>`中
+//!
+//! 相同键的标签只保留一个：位置为第一次出现的位置，内容为最后一次出现的内容，
+//! 因此页面的标签覆盖布局和组件的同类标签。标签的键默认为：
+//!
+//! - `<title>`、`<base>`：标签名
+//! - `<meta>`：`charset`，或`name`、`property`、`http-equiv`、`itemprop`属性及其值
+//! - `<link>`：`canonical`和`manifest`为rel，其他为rel和href
+//! - `<script>`：src，内联脚本不去重
+//!
+//! 标签上的`key="..."`属性可以指定键，该属性不会输出
+
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, HelperResult, Output, RenderContext, Renderable,
+};
+use std::cell::RefCell;
+
+/// 收集head标签的helper的名称，用法为`{{#rsx_head "title"}}<title>...</title>{{/rsx_head}}`
+pub const HEAD_HELPER: &str = "rsx_head";
+
+/// 文档`<head>`中合并标签的输出位置
+pub const HEAD_OUTLET: &str = "<!--rsx-head-->";
+
+/// 由head管理的标签
+pub const HEAD_TAGS: [&str; 5] = ["title", "meta", "link", "base", "script"];
+
+/// 指定标签键的属性
+pub const KEY_ATTR: &str = "key";
+
+/// 在文档中只应出现一次的link rel
+const UNIQUE_LINK_RELS: [&str; 2] = ["canonical", "manifest"];
+
+/// meta标签中作为键的属性
+const META_KEY_ATTRS: [&str; 4] = ["name", "property", "http-equiv", "itemprop"];
+
+thread_local! {
+    /// 当前线程正在渲染的模板收集到的head标签
+    static COLLECTOR: RefCell<Option<Head>> = const { RefCell::new(None) };
+}
+
+/// 一次渲染中收集到的head标签
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Head {
+    /// 键和标签，键为空的标签不去重
+    tags: Vec<(String, String)>,
+}
+
+impl Head {
+    /// 是否没有标签
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+    }
+
+    /// 添加标签，已有相同键的标签时替换其内容
+    pub fn push(&mut self, key: &str, tag: String) {
+        if !key.is_empty()
+            && let Some((_, existing)) = self.tags.iter_mut().find(|(k, _)| k == key)
+        {
+            *existing = tag;
+            return;
+        }
+        self.tags.push((key.to_string(), tag));
+    }
+
+    /// 在`f`执行期间收集渲染出的head标签
+    pub fn collect<R>(&mut self, f: impl FnOnce() -> R) -> R {
+        let previous = COLLECTOR.with(|c| c.replace(Some(std::mem::take(self))));
+        let result = f();
+        *self = COLLECTOR.with(|c| c.replace(previous)).unwrap_or_default();
+        result
+    }
+
+    /// 合并后的标签
+    pub fn html(&self) -> String {
+        self.tags.iter().map(|(_, tag)| tag.as_str()).collect()
+    }
+
+    /// 用合并后的标签替换输出位置，没有输出位置时不插入
+    pub fn inject(&self, html: &mut String) {
+        if let Some(index) = html.find(HEAD_OUTLET) {
+            html.replace_range(index..index + HEAD_OUTLET.len(), &self.html());
+        }
+    }
+}
+
+/// 根据标签名和纯文本属性计算标签的键，`attr`查找属性的纯文本值
+pub fn tag_key<'a>(name: &str, attr: impl Fn(&str) -> Option<&'a str>) -> String {
+    if let Some(key) = attr(KEY_ATTR) {
+        return key.to_string();
+    }
+    match name {
+        "title" | "base" => name.to_string(),
+        "meta" if attr("charset").is_some() => "charset".to_string(),
+        "meta" => META_KEY_ATTRS
+            .iter()
+            .find_map(|key| attr(key).map(|value| format!("meta:{key}:{value}")))
+            .unwrap_or_default(),
+        "link" => match attr("rel").map(str::to_ascii_lowercase) {
+            Some(rel) if UNIQUE_LINK_RELS.contains(&rel.as_str()) => format!("link:{rel}"),
+            Some(rel) => attr("href")
+                .map(|href| format!("link:{rel}:{href}"))
+                .unwrap_or_default(),
+            None => String::new(),
+        },
+        "script" => attr("src")
+            .map(|src| format!("script:{src}"))
+            .unwrap_or_default(),
+        _ => String::new(),
+    }
+}
+
+/// 渲染块内的标签并收集，没有进行收集时原样输出
+pub(crate) struct HeadHelper;
+
+impl HelperDef for HeadHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let Some(template) = h.template() else {
+            return Ok(());
+        };
+        let key = h
+            .param(0)
+            .and_then(|param| param.value().as_str())
+            .unwrap_or_default();
+        let tag = template.renders(r, ctx, rc)?;
+        let tag = COLLECTOR.with(|c| match c.borrow_mut().as_mut() {
+            Some(head) => {
+                head.push(key, tag);
+                None
+            }
+            None => Some(tag),
+        });
+        if let Some(tag) = tag {
+            out.write(&tag)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(name: &str, attrs: &[(&'static str, &'static str)]) -> String {
+        tag_key(name, |attr| {
+            attrs
+                .iter()
+                .find(|(key, _)| *key == attr)
+                .map(|(_, value)| *value)
+        })
+    }
+
+    #[test]
+    fn tag_keys() {
+        assert_eq!(key("title", &[]), "title");
+        assert_eq!(key("meta", &[("charset", "UTF-8")]), "charset");
+        assert_eq!(
+            key("meta", &[("name", "viewport"), ("content", "x")]),
+            "meta:name:viewport"
+        );
+        assert_eq!(
+            key("link", &[("rel", "canonical"), ("href", "/a")]),
+            "link:canonical"
+        );
+        assert_eq!(
+            key("link", &[("rel", "icon"), ("href", "/a.svg")]),
+            "link:icon:/a.svg"
+        );
+        assert_eq!(
+            key("link", &[("rel", "stylesheet"), ("href", "/a.css")]),
+            "link:stylesheet:/a.css"
+        );
+        assert_eq!(key("script", &[]), "");
+        assert_eq!(key("script", &[("key", "analytics")]), "analytics");
+    }
+
+    #[test]
+    fn dedupe_by_key() {
+        let mut head = Head::default();
+        head.collect(|| {
+            COLLECTOR.with(|c| {
+                let mut c = c.borrow_mut();
+                let head = c.as_mut().unwrap();
+                head.push("charset", "<meta charset=\"utf-8\">".to_string());
+                head.push("title", "<title>布局</title>".to_string());
+                head.push("", "<script>a</script>".to_string());
+                head.push("", "<script>a</script>".to_string());
+                head.push("title", "<title>页面</title>".to_string());
+            })
+        });
+        let mut html = format!("<head>{HEAD_OUTLET}</head>");
+        head.inject(&mut html);
+        assert_eq!(
+            html,
+            "<head><meta charset=\"utf-8\"><title>页面</title><script>a</script><script>a</script></head>"
+        );
+    }
+}
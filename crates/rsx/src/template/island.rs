@@ -0,0 +1,258 @@
+//! 客户端组件岛
+//!
+//! 带有`client:*`指令的客户端组件在服务端渲染为`<rsx-island>`占位元素，组件的属性在渲染时
+//! 收集起来，按岛ID保存在服务端数据脚本的`__rsx_islands`下（见[`props::server_props_script`]），
+//! 内联的加载脚本按指令加载组件模块：
+//!
+//! - `client:load`：页面加载后立即加载
+//! - `client:idle`：浏览器空闲时加载
+//! - `client:visible`：进入视口时加载
+//! - `client:media="(max-width: 600px)"`：媒体查询匹配时加载
+//! - `client:only="react"`：只在客户端渲染，值为使用的框架
+//!
+//! 组件模块导出`mount(el, props, hydrate)`时直接调用，`hydrate`在`client:only`时为false；
+//! rsx-plugin-vite为.rsx中导入的只有默认导出的React组件生成`mount`，通过`rsx/react`
+//! 以`createRoot`或`hydrateRoot`渲染。没有`mount`时使用`window.__rsx_renderers[框架]`
+//! 渲染默认导出的组件，可用于接入其他框架；设置了Vite清单时模块地址替换为构建后的文件
+
+use crate::manifest::Manifest;
+#[cfg(doc)]
+use crate::props;
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, HelperResult, Output, RenderContext, RenderErrorReason,
+    html_escape,
+};
+use serde_json::{Map, Value};
+use std::cell::RefCell;
+use std::sync::Arc;
+
+/// 客户端组件岛helper的名称，用法为`{{rsx_island "App" "/src/app.tsx" "load" "" title=title}}`
+pub const ISLAND_HELPER: &str = "rsx_island";
+
+/// 占位元素的标签名
+pub const ISLAND_TAG: &str = "rsx-island";
+
+/// 客户端组件指令，与编译器的`CLIENT_DIRECTIVE`一致
+pub const CLIENT_DIRECTIVES: [&str; 5] = ["load", "idle", "visible", "media", "only"];
+
+/// 按指令加载岛组件的脚本，从服务端数据脚本中读取岛的props
+pub(crate) const LOADER: &str = "<script type=\"module\">\
+const s=document.getElementById('__rsx_script__'),p=(s&&JSON.parse(s.textContent).__rsx_islands)||{};\
+const h=async e=>{if(e.dataset.hydrated)return;e.dataset.hydrated='1';\
+const m=await import(e.dataset.src),d=e.dataset,r=(window.__rsx_renderers||{})[d.directive==='only'?d.value:'default'],o=p[d.island]||{};\
+if(typeof m.mount==='function')m.mount(e,o,d.directive!=='only');else if(r)r(m.default,o,e,d.directive!=='only');\
+else console.warn('[rsx] no renderer for island',d.component)};\
+for(const e of document.querySelectorAll('rsx-island')){const d=e.dataset.directive;\
+if(d==='idle')(window.requestIdleCallback||setTimeout)(()=>h(e));\
+else if(d==='visible'){const o=new IntersectionObserver(x=>{if(x.some(i=>i.isIntersecting)){o.disconnect();h(e)}});o.observe(e)}\
+else if(d==='media'){const q=matchMedia(e.dataset.value);if(q.matches)h(e);else q.addEventListener('change',()=>q.matches&&h(e),{once:true})}\
+else h(e)}\
+</script>";
+
+thread_local! {
+    /// 当前线程正在渲染的模板收集到的岛
+    static COLLECTOR: RefCell<Option<Islands>> = const { RefCell::new(None) };
+}
+
+/// 一次渲染中收集到的岛props，按岛ID保存
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Islands {
+    props: Map<String, Value>,
+    /// 岛组件的源文件，按出现顺序去重
+    entries: Vec<String>,
+}
+
+impl Islands {
+    /// 是否没有岛
+    pub fn is_empty(&self) -> bool {
+        self.props.is_empty()
+    }
+
+    /// 岛的数量
+    pub fn len(&self) -> usize {
+        self.props.len()
+    }
+
+    /// 岛ID到props的映射
+    pub fn props(&self) -> &Map<String, Value> {
+        &self.props
+    }
+
+    /// 岛组件的源文件，如`/src/react/news.app.tsx`
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    /// 在`f`执行期间收集渲染出的岛，岛ID接着已有的岛编号
+    pub fn collect<R>(&mut self, f: impl FnOnce() -> R) -> R {
+        let previous = COLLECTOR.with(|c| c.replace(Some(std::mem::take(self))));
+        let result = f();
+        *self = COLLECTOR.with(|c| c.replace(previous)).unwrap_or_default();
+        result
+    }
+
+    fn push(&mut self, src: &str, props: Value) -> String {
+        if !self.entries.iter().any(|entry| entry == src) {
+            self.entries.push(src.to_string());
+        }
+        let id = self.props.len().to_string();
+        self.props.insert(id.clone(), props);
+        id
+    }
+}
+
+/// 输出岛的占位元素并收集props
+#[derive(Default)]
+pub(crate) struct IslandHelper {
+    /// Vite构建清单，用于将源文件解析为构建后的模块地址
+    pub(crate) manifest: Option<Arc<Manifest>>,
+}
+
+impl HelperDef for IslandHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let param = |index: usize| {
+            h.param(index)
+                .and_then(|param| param.value().as_str())
+                .ok_or(RenderErrorReason::ParamNotFoundForIndex(
+                    ISLAND_HELPER,
+                    index,
+                ))
+        };
+        let (name, src, directive, value) = (param(0)?, param(1)?, param(2)?, param(3)?);
+        let props: Map<String, Value> = h
+            .hash()
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.value().clone()))
+            .collect();
+        let id = COLLECTOR
+            .with(|c| {
+                c.borrow_mut()
+                    .as_mut()
+                    .map(|islands| islands.push(src, props.into()))
+            })
+            .unwrap_or_default();
+        let url = self
+            .manifest
+            .as_ref()
+            .and_then(|manifest| manifest.url(src));
+        out.write(&format!(
+            "<{ISLAND_TAG} data-island=\"{id}\" data-component=\"{}\" data-src=\"{}\" data-directive=\"{}\"",
+            html_escape(name),
+            html_escape(url.as_deref().unwrap_or(src)),
+            html_escape(directive)
+        ))?;
+        if !value.is_empty() {
+            out.write(&format!(" data-value=\"{}\"", html_escape(value)))?;
+        }
+        out.write(&format!("></{ISLAND_TAG}>"))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn collect_nested_renders() {
+        let mut islands = Islands::default();
+        islands.collect(|| {
+            COLLECTOR.with(|c| {
+                c.borrow_mut()
+                    .as_mut()
+                    .unwrap()
+                    .push("/a.tsx", json!({"a": 1}))
+            });
+            // 嵌套收集互不影响
+            let mut inner = Islands::default();
+            inner.collect(|| {
+                COLLECTOR.with(|c| c.borrow_mut().as_mut().unwrap().push("/a.tsx", json!({})));
+            });
+            assert_eq!(inner.len(), 1);
+        });
+        islands.collect(|| {
+            COLLECTOR.with(|c| {
+                c.borrow_mut()
+                    .as_mut()
+                    .unwrap()
+                    .push("/a.tsx", json!({"b": "</script>"}))
+            });
+        });
+        assert_eq!(islands.props().keys().collect::<Vec<_>>(), ["0", "1"]);
+        assert_eq!(islands.entries(), ["/a.tsx"]);
+
+        let mut html = "<body></body>".to_string();
+        crate::props::inject_server_props(&mut html, None, &islands).unwrap();
+        assert!(html.starts_with(
+            "<body><script id=\"__rsx_script__\" type=\"application/json\">\
+             {\"__rsx_islands\":{\"0\":{\"a\":1},\"1\":{\"b\":\"\\u003c/script\\u003e\"}}}</script><script type=\"module\">"
+        ));
+        assert!(html.ends_with("</script></body>"));
+    }
+
+    #[test]
+    #[ignore = "requires node, run with `cargo test -- --ignored`"]
+    fn loader_mounts_islands() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        // 与rsx-plugin-vite生成的mount一致的模块，以及只有默认导出、由注册的渲染器渲染的模块
+        let mounted = dir.join("news.app.mjs");
+        std::fs::write(
+            &mounted,
+            "export default function NewsApp() {}\n\
+             export function mount(el, props, hydrate) { globalThis.calls.push(['mount', el.dataset.island, props, hydrate]) }\n",
+        )
+        .unwrap();
+        let default_only = dir.join("csr.app.mjs");
+        std::fs::write(&default_only, "export default function CsrApp() {}\n").unwrap();
+        let url = |path: &std::path::Path| format!("file://{}", path.display());
+        let harness = format!(
+            "globalThis.window = globalThis;\n\
+             globalThis.calls = [];\n\
+             window.__rsx_renderers = {{ react: (C, props, el, hydrate) => calls.push([C.name, el.dataset.island, props, hydrate]) }};\n\
+             const island = (island, directive, src, value) => ({{ dataset: {{ island, directive, src, value, component: 'C' }} }});\n\
+             const islands = [island('0', 'load', '{}'), island('1', 'only', '{}', 'react'), island('2', 'only', '{}', 'vue')];\n\
+             const data = {{ __rsx_islands: {{ 0: {{ title: 'a' }}, 1: {{ title: 'b' }} }} }};\n\
+             globalThis.document = {{\n\
+                 getElementById: (id) => (id === '__rsx_script__' ? {{ textContent: JSON.stringify(data) }} : null),\n\
+                 querySelectorAll: () => islands,\n\
+             }};\n\
+             console.warn = (...args) => calls.push(['warn', args[1]]);\n\
+             {}\n\
+             setTimeout(() => console.log(JSON.stringify(calls)), 100);\n",
+            url(&mounted),
+            url(&default_only),
+            url(&default_only),
+            LOADER
+                .trim_start_matches("<script type=\"module\">")
+                .trim_end_matches("</script>")
+        );
+        let output = std::process::Command::new("node")
+            .args(["--input-type=module", "-e", &harness])
+            .output()
+            .expect("failed to run node");
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let mut calls: Vec<Value> = serde_json::from_slice(&output.stdout).unwrap();
+        calls.sort_by_key(|call| call.to_string());
+        assert_eq!(
+            calls,
+            [
+                json!(["CsrApp", "1", {"title": "b"}, false]),
+                json!(["mount", "0", {"title": "a"}, true]),
+                json!(["warn", "C"]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,961 @@
+//! rsx模板编译
+//!
+//! 将`<template>`区块中的rsx模板语法编译为Handlebars模板，组件在编译时内联展开
+
+mod codegen;
+pub mod expr;
+pub mod head;
+pub mod island;
+pub mod node;
+pub mod style;
+
+pub use codegen::{
+    AWAIT_ATTR, COMPONENT_HELPER, Codegen, Component, ComponentTemplate, DEFAULT_SLOT,
+    DEFER_HELPER, EXPR_HELPER, FALLBACK_SLOT, LAYOUT_FLAG, Region,
+};
+
+use crate::manifest::{self, Manifest};
+use crate::parser::{self, RsxFile};
+use crate::props;
+use anyhow::{Context as _, Result, bail};
+use handlebars::{
+    BlockContext, Context, Handlebars, Helper, HelperDef, HelperResult, Output, RenderContext,
+    RenderError, RenderErrorReason, Renderable, ScopedJson,
+};
+use head::{HEAD_HELPER, Head, HeadHelper};
+use island::{ISLAND_HELPER, IslandHelper, Islands};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::path::{Component as PathComponent, Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use style::Style;
+
+/// 渲染上下文中存放组件props函数结果的键，值为组件ID到props的映射
+pub const COMPONENTS_KEY: &str = "__rsx_components";
+
+/// 渲染上下文中标记流式渲染外壳的键，值为`true`时延迟区块只输出占位内容
+pub const SHELL_KEY: &str = "__rsx_shell";
+
+/// 延迟区块占位元素的ID前缀，如`__rsx_defer_0`
+pub const DEFER_ID_PREFIX: &str = "__rsx_defer_";
+
+/// 目录布局文件名，对所在目录及子目录中的页面生效
+pub const LAYOUT_FILE: &str = "_layout.rsx";
+
+/// `<template layout="none">`表示页面不使用布局
+pub const NO_LAYOUT: &str = "none";
+
+/// 编译.rsx文件的`<template>`区块，`components`为模板中可用的组件
+pub fn compile(
+    file: &RsxFile,
+    components: &HashMap<String, Component>,
+) -> Result<String, parser::ParseError> {
+    Ok(compile_page(file, components, &[])?.0)
+}
+
+/// 编译页面模板及其延迟区块，`layouts`为从外到内排列的布局
+fn compile_page(
+    file: &RsxFile,
+    components: &HashMap<String, Component>,
+    layouts: &[Arc<ComponentTemplate>],
+) -> Result<(String, Vec<Region>), parser::ParseError> {
+    let Some(template) = &file.template else {
+        return Ok(Default::default());
+    };
+    let nodes = node::parse(&template.content, template.span.start).map_err(|mut err| {
+        err.file = file.path.clone();
+        err
+    })?;
+    let scope = file
+        .style
+        .as_ref()
+        .and_then(Style::from_block)
+        .and_then(|style| style.scope);
+    Codegen::new(components)
+        .with_scope(scope)
+        .with_layouts(layouts)
+        .generate_page(&nodes)
+        .map_err(|mut err| {
+            if err.file.is_none() {
+                err.file = file.path.clone();
+            }
+            err
+        })
+}
+
+/// 根据组件相对`root`的路径生成组件ID，如`src/components/header.rsx`生成`src_components_header`
+pub fn component_id(root: &Path, path: &Path) -> String {
+    let root = normalize(root);
+    let path = normalize(path);
+    let relative = path.strip_prefix(&root).unwrap_or(&path);
+    let name: String = relative
+        .with_extension("")
+        .to_string_lossy()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    name.trim_start_matches('_').to_string()
+}
+
+/// 查找页面的布局文件，从外到内排列
+///
+/// 默认使用`pages`到页面所在目录之间每一级的`_layout.rsx`，目录布局声明了
+/// `<template layout="none">`时不再继承上级目录的布局；页面的`<template layout="name">`
+/// 改用`layouts`目录中的`name.rsx`，`layout="none"`不使用布局
+pub fn layout_files(
+    pages: &Path,
+    layouts: &Path,
+    page: &Path,
+    file: &RsxFile,
+) -> Result<Vec<PathBuf>> {
+    match file
+        .template
+        .as_ref()
+        .and_then(|template| template.attr("layout"))
+    {
+        Some(NO_LAYOUT) => return Ok(Vec::new()),
+        Some(name) => {
+            let path = layouts.join(format!("{name}.rsx"));
+            if !path.is_file() {
+                bail!(
+                    "layout `{name}` used by {} not found: {}",
+                    page.display(),
+                    path.display()
+                );
+            }
+            return Ok(vec![normalize(&path)]);
+        }
+        None => {}
+    }
+    let pages = normalize(pages);
+    let page = normalize(page);
+    let Some(dir) = page.parent().filter(|dir| dir.starts_with(&pages)) else {
+        return Ok(Vec::new());
+    };
+    let mut files = Vec::new();
+    for dir in dir.ancestors().take_while(|dir| dir.starts_with(&pages)) {
+        let path = dir.join(LAYOUT_FILE);
+        if path == page || !path.is_file() {
+            continue;
+        }
+        let layout = parser::parse_file(&path)?;
+        files.push(path);
+        if layout
+            .template
+            .as_ref()
+            .is_some_and(|template| template.attr("layout") == Some(NO_LAYOUT))
+        {
+            break;
+        }
+    }
+    files.reverse();
+    Ok(files)
+}
+
+/// 模板引擎，负责编译.rsx文件并注册为Handlebars模板
+///
+/// 开发模式下修改文件后复制一份引擎重新编译受影响的页面，再整体替换
+#[derive(Clone)]
+pub struct TemplateEngine {
+    handlebars: Handlebars<'static>,
+    root: PathBuf,
+    /// pages目录和命名布局目录，设置后页面按目录应用布局
+    layouts: Option<(PathBuf, PathBuf)>,
+    /// 已解析的组件，按文件路径缓存
+    components: HashMap<PathBuf, Arc<ComponentTemplate>>,
+    /// 页面用到的全部组件ID
+    page_components: HashMap<String, Vec<String>>,
+    /// 页面中每个`<defer>`区块等待的延迟数据
+    regions: HashMap<String, Vec<Vec<String>>>,
+    /// 页面用到的全部样式，已按作用域去重合并
+    styles: HashMap<String, String>,
+    /// 页面源文件相对项目根目录的路径，用于在Vite清单中查找页面入口
+    sources: HashMap<String, String>,
+    /// Vite构建清单
+    manifest: Option<Arc<Manifest>>,
+    /// 开发模式，插入客户端脚本，渲染错误时显示错误浮层
+    dev: bool,
+}
+
+impl TemplateEngine {
+    /// 创建模板引擎，`root`用于生成组件ID
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        let mut handlebars = Handlebars::new();
+        handlebars.register_helper(EXPR_HELPER, Box::new(ExprHelper::default()));
+        handlebars.register_helper(COMPONENT_HELPER, Box::new(ComponentHelper));
+        handlebars.register_helper(DEFER_HELPER, Box::new(DeferHelper));
+        handlebars.register_helper(ISLAND_HELPER, Box::new(IslandHelper::default()));
+        handlebars.register_helper(HEAD_HELPER, Box::new(HeadHelper));
+        Self {
+            handlebars,
+            root: normalize(&root.into()),
+            layouts: None,
+            components: HashMap::new(),
+            page_components: HashMap::new(),
+            regions: HashMap::new(),
+            styles: HashMap::new(),
+            sources: HashMap::new(),
+            manifest: None,
+            dev: false,
+        }
+    }
+
+    /// 使用Vite构建清单，岛组件加载构建后的模块，`<head>`中插入页面和岛需要的资源
+    pub fn with_manifest(mut self, manifest: Manifest) -> Self {
+        let manifest = Arc::new(manifest);
+        self.handlebars.register_helper(
+            ISLAND_HELPER,
+            Box::new(IslandHelper {
+                manifest: Some(manifest.clone()),
+            }),
+        );
+        self.manifest = Some(manifest);
+        self
+    }
+
+    /// Vite构建清单
+    pub fn manifest(&self) -> Option<&Manifest> {
+        self.manifest.as_deref()
+    }
+
+    /// 开发模式，渲染的页面插入连接开发服务器的客户端脚本，见[`dev`](crate::dev)
+    pub fn with_dev_client(mut self) -> Self {
+        self.dev = true;
+        self
+    }
+
+    /// 是否为开发模式
+    pub fn is_dev(&self) -> bool {
+        self.dev
+    }
+
+    /// 页面或组件ID对应的源文件
+    pub fn source_path(&self, name: &str) -> Option<PathBuf> {
+        if let Some(source) = self.sources.get(name) {
+            return Some(self.root.join(source));
+        }
+        self.components
+            .values()
+            .find(|component| component.id == name)
+            .map(|component| component.path.clone())
+    }
+
+    /// 项目根目录
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// 启用布局，`pages`中的`_layout.rsx`按目录嵌套，`layouts`为命名布局所在目录
+    pub fn with_layouts(mut self, pages: impl AsRef<Path>, layouts: impl AsRef<Path>) -> Self {
+        self.layouts = Some((normalize(pages.as_ref()), normalize(layouts.as_ref())));
+        self
+    }
+
+    /// 编译页面并注册为名为`name`的模板
+    pub fn register_page(&mut self, name: &str, path: impl AsRef<Path>) -> Result<()> {
+        let file = parser::parse_file(path.as_ref())?;
+        let components = self.resolve_imports(&file, &mut Vec::new())?;
+        let layouts = match &self.layouts {
+            Some((pages, layouts)) => layout_files(pages, layouts, path.as_ref(), &file)?,
+            None => Vec::new(),
+        };
+        let layouts = layouts
+            .iter()
+            .map(|layout| self.load_component(layout, &mut Vec::new()))
+            .collect::<Result<Vec<_>>>()?;
+        let (source, regions) = compile_page(&file, &components, &layouts)?;
+        self.handlebars
+            .register_template_string(name, source)
+            .with_context(|| format!("failed to register template {}", path.as_ref().display()))?;
+        for (index, region) in regions.iter().enumerate() {
+            self.handlebars
+                .register_template_string(&region_name(name, index), &region.template)
+                .with_context(|| {
+                    format!("failed to register <defer> in {}", path.as_ref().display())
+                })?;
+        }
+        self.regions.insert(
+            name.to_string(),
+            regions.into_iter().map(|region| region.awaits).collect(),
+        );
+        let source = normalize(path.as_ref());
+        let source = source.strip_prefix(&self.root).unwrap_or(&source);
+        self.sources.insert(
+            name.to_string(),
+            source.to_string_lossy().replace('\\', "/"),
+        );
+        let mut ids = Vec::new();
+        for layout in &layouts {
+            if !ids.contains(&layout.id) {
+                ids.push(layout.id.clone());
+            }
+            ComponentTemplate::collect_ids(&layout.components, &mut ids);
+        }
+        ComponentTemplate::collect_ids(&components, &mut ids);
+        // 组件同时注册为独立模板，便于单独渲染
+        let paths: Vec<PathBuf> = self
+            .components
+            .values()
+            .filter(|component| ids.contains(&component.id))
+            .map(|component| component.path.clone())
+            .collect();
+        for path in paths {
+            self.register_component(path)?;
+        }
+        // 布局和组件的样式在前，页面自身的样式最后，可以覆盖组件样式
+        let page_style = file.style.as_ref().and_then(Style::from_block);
+        let styles: Vec<Style> = ids
+            .iter()
+            .filter_map(|id| {
+                self.components
+                    .values()
+                    .find(|component| &component.id == id)
+                    .and_then(|component| component.style.clone())
+            })
+            .chain(page_style)
+            .collect();
+        self.styles.insert(name.to_string(), style::merge(&styles));
+        self.page_components.insert(name.to_string(), ids);
+        Ok(())
+    }
+
+    /// 丢弃`paths`中的组件及直接或间接导入了它们的组件，返回丢弃的组件ID，
+    /// 之后重新注册的页面会重新解析这些组件
+    pub fn forget(&mut self, paths: &[PathBuf]) -> Vec<String> {
+        let paths: Vec<PathBuf> = paths.iter().map(|path| normalize(path)).collect();
+        let changed: Vec<String> = self
+            .components
+            .values()
+            .filter(|component| paths.contains(&component.path))
+            .map(|component| component.id.clone())
+            .collect();
+        let mut removed = Vec::new();
+        self.components.retain(|_, component| {
+            let mut ids = vec![component.id.clone()];
+            ComponentTemplate::collect_ids(&component.components, &mut ids);
+            let stale = ids.iter().any(|id| changed.contains(id));
+            if stale {
+                removed.push(component.id.clone());
+            }
+            !stale
+        });
+        for id in &removed {
+            self.handlebars.unregister_template(id);
+        }
+        removed
+    }
+
+    /// 页面及其`<defer>`区块编译出的模板是否与`other`中的相同
+    pub fn same_markup(&self, other: &TemplateEngine, name: &str) -> bool {
+        let regions = self.deferred_regions(name);
+        self.region_awaits(name) == other.region_awaits(name)
+            && std::iter::once(name.to_string())
+                .chain((0..regions).map(|index| region_name(name, index)))
+                .all(|template| {
+                    self.handlebars.get_template(&template)
+                        == other.handlebars.get_template(&template)
+                })
+    }
+
+    /// 单独编译组件并注册为以组件ID命名的模板，返回组件ID
+    pub fn register_component(&mut self, path: impl AsRef<Path>) -> Result<String> {
+        let component = self.load_component(path.as_ref(), &mut Vec::new())?;
+        if !self.handlebars.has_template(&component.id) {
+            let source = Codegen::new(&component.components).generate(&component.nodes)?;
+            self.handlebars
+                .register_template_string(&component.id, source)
+                .with_context(|| {
+                    format!("failed to register component {}", component.path.display())
+                })?;
+        }
+        Ok(component.id.clone())
+    }
+
+    /// 解析导入的组件并编译模板
+    pub fn compile_file(&mut self, file: &RsxFile) -> Result<String> {
+        let components = self.resolve_imports(file, &mut Vec::new())?;
+        Ok(compile(file, &components)?)
+    }
+
+    /// 页面用到的全部组件ID，包括组件内嵌套使用的组件
+    pub fn page_components(&self, name: &str) -> &[String] {
+        self.page_components
+            .get(name)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// 页面用到的全部样式，已按作用域去重合并
+    pub fn page_styles(&self, name: &str) -> &str {
+        self.styles
+            .get(name)
+            .map(String::as_str)
+            .unwrap_or_default()
+    }
+
+    /// 页面中`<defer>`区块的数量
+    pub fn deferred_regions(&self, name: &str) -> usize {
+        self.region_awaits(name).len()
+    }
+
+    /// 页面中每个`<defer>`区块等待的延迟数据的键，为空时等待全部延迟数据
+    pub fn region_awaits(&self, name: &str) -> &[Vec<String>] {
+        self.regions
+            .get(name)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// 渲染页面的第`index`个`<defer>`区块，区块中的岛收集到`islands`
+    pub fn render_region<T: Serialize>(
+        &self,
+        name: &str,
+        index: usize,
+        data: &T,
+        islands: &mut Islands,
+    ) -> Result<String> {
+        self.render_islands(&region_name(name, index), data, islands)
+    }
+
+    /// 解析`<script>`中导入的组件，`stack`为正在解析的组件文件，用于检测循环引用
+    fn resolve_imports(
+        &mut self,
+        file: &RsxFile,
+        stack: &mut Vec<PathBuf>,
+    ) -> Result<HashMap<String, Component>> {
+        let dir = file
+            .path
+            .as_deref()
+            .and_then(Path::parent)
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| self.root.clone());
+        let mut components = HashMap::new();
+        for import in &file.imports {
+            let component = if import.is_rsx() {
+                Component::Rsx(self.load_component(&dir.join(&import.source), stack)?)
+            } else if import.source.starts_with('.') {
+                // 相对路径转换为相对项目根目录的地址，如`/src/react/news.app.tsx`
+                let path = normalize(&dir.join(&import.source));
+                let path = path.strip_prefix(&self.root).unwrap_or(&path);
+                Component::Client(format!("/{}", path.to_string_lossy().replace('\\', "/")))
+            } else {
+                Component::Client(import.source.clone())
+            };
+            components.insert(import.name.clone(), component);
+        }
+        Ok(components)
+    }
+
+    /// 解析组件文件及其导入的组件
+    fn load_component(
+        &mut self,
+        path: &Path,
+        stack: &mut Vec<PathBuf>,
+    ) -> Result<Arc<ComponentTemplate>> {
+        let path = normalize(path);
+        if let Some(component) = self.components.get(&path) {
+            return Ok(component.clone());
+        }
+        if let Some(index) = stack.iter().position(|p| p == &path) {
+            let chain: Vec<String> = stack[index..]
+                .iter()
+                .chain([&path])
+                .map(|p| {
+                    p.strip_prefix(&self.root)
+                        .unwrap_or(p)
+                        .display()
+                        .to_string()
+                })
+                .collect();
+            bail!("component cycle detected: {}", chain.join(" -> "));
+        }
+        let file = parser::parse_file(&path)?;
+        stack.push(path.clone());
+        let components = self.resolve_imports(&file, stack);
+        stack.pop();
+        let components = components?;
+        let nodes = match &file.template {
+            Some(template) => {
+                node::parse(&template.content, template.span.start).map_err(|mut err| {
+                    err.file = Some(path.clone());
+                    err
+                })?
+            }
+            None => Vec::new(),
+        };
+        let component = Arc::new(ComponentTemplate {
+            id: component_id(&self.root, &path),
+            path: path.clone(),
+            nodes,
+            components,
+            style: file.style.as_ref().and_then(Style::from_block),
+        });
+        self.components.insert(path, component.clone());
+        Ok(component)
+    }
+
+    /// 渲染已注册的模板，模板中有岛时在`</body>`之前插入岛的props和加载脚本
+    pub fn render<T: Serialize>(&self, name: &str, data: &T) -> Result<String> {
+        self.render_hydrated(name, data, None)
+    }
+
+    /// 渲染已注册的模板，`hydration`为页面的hydration数据，与岛的props一起插入到
+    /// `</body>`之前的服务端数据脚本中，见[`props::server_props_script`]
+    pub fn render_hydrated<T: Serialize>(
+        &self,
+        name: &str,
+        data: &T,
+        hydration: Option<&Value>,
+    ) -> Result<String> {
+        let mut islands = Islands::default();
+        let mut html = self.render_islands(name, data, &mut islands)?;
+        self.inject_assets(name, &mut html, &islands);
+        props::inject_server_props(&mut html, hydration, &islands)?;
+        Ok(html)
+    }
+
+    /// 在`</head>`之前插入页面的合并样式，有Vite清单时再插入页面入口脚本，
+    /// 以及岛组件的CSS和预加载链接，没有`<head>`时插入到`<body>`开头或最前面；
+    /// 开发模式下插入客户端脚本，没有`<head>`时追加在末尾
+    pub fn inject_assets(&self, name: &str, html: &mut String, islands: &Islands) {
+        let mut tags = style::style_tag(self.page_styles(name));
+        if let Some(manifest) = &self.manifest {
+            let pages: Vec<&str> = self
+                .sources
+                .get(name)
+                .map(String::as_str)
+                .into_iter()
+                .collect();
+            let entries: Vec<&str> = islands.entries().iter().map(String::as_str).collect();
+            tags.push_str(&manifest.assets(&pages, &entries).tags());
+        }
+        manifest::inject_page(html, &tags);
+        // 没有<head>的页面片段也需要连接开发服务器
+        if self.dev {
+            let script = crate::dev::client_script(name);
+            if !manifest::inject_head(html, &script) {
+                html.push_str(&script);
+            }
+        }
+    }
+
+    /// 渲染已注册的模板，模板中的岛收集到`islands`，不插入脚本
+    ///
+    /// 收集到的head标签合并输出到文档`<head>`中，模板没有文档`<head>`时丢弃，
+    /// 因此`<defer>`区块中的head标签不生效
+    pub fn render_islands<T: Serialize>(
+        &self,
+        name: &str,
+        data: &T,
+        islands: &mut Islands,
+    ) -> Result<String> {
+        let mut head = Head::default();
+        let mut html = head.collect(|| islands.collect(|| self.handlebars.render(name, data)))?;
+        head.inject(&mut html);
+        Ok(html)
+    }
+
+    /// 是否已注册模板
+    pub fn has_template(&self, name: &str) -> bool {
+        self.handlebars.has_template(name)
+    }
+
+    /// 获取底层的Handlebars实例
+    pub fn handlebars(&self) -> &Handlebars<'static> {
+        &self.handlebars
+    }
+}
+
+/// 按字面规则规范化路径，处理`.`和`..`
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            PathComponent::CurDir => {}
+            PathComponent::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+/// 延迟区块的模板名称
+fn region_name(name: &str, index: usize) -> String {
+    format!("{name}#defer-{index}")
+}
+
+/// 延迟区块helper，渲染流式外壳时输出带ID的占位内容，否则直接渲染区块内容
+struct DeferHelper;
+
+impl HelperDef for DeferHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let index = h
+            .param(0)
+            .and_then(|param| param.value().as_u64())
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex(DEFER_HELPER, 0))?;
+        if ctx.data().get(SHELL_KEY) != Some(&Value::Bool(true)) {
+            return match h.template() {
+                Some(template) => template.render(r, ctx, rc, out),
+                None => Ok(()),
+            };
+        }
+        out.write(&format!(
+            "<div id=\"{DEFER_ID_PREFIX}{index}\" style=\"display:contents\">"
+        ))?;
+        if let Some(fallback) = h.inverse() {
+            fallback.render(r, ctx, rc, out)?;
+        }
+        out.write("</div>")?;
+        Ok(())
+    }
+}
+
+/// 组件块helper，以组件props函数的结果和组件属性作为块内的上下文
+struct ComponentHelper;
+
+impl HelperDef for ComponentHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let id = h.param(0).and_then(|param| param.value().as_str()).ok_or(
+            RenderErrorReason::ParamNotFoundForIndex(COMPONENT_HELPER, 0),
+        )?;
+        // 布局以页面的渲染数据为基础，再合并布局自己的props
+        let mut props = match (h.param(1).and_then(|p| p.value().as_str()), ctx.data()) {
+            (Some(LAYOUT_FLAG), Value::Object(data)) => {
+                let mut data = data.clone();
+                data.remove(COMPONENTS_KEY);
+                data
+            }
+            _ => Map::new(),
+        };
+        if let Some(Value::Object(own)) = ctx.data().get(COMPONENTS_KEY).and_then(|c| c.get(id)) {
+            props.extend(own.clone());
+        }
+        // 属性覆盖组件props函数的同名字段，值为null的属性不覆盖
+        for (key, value) in h.hash() {
+            if !value.value().is_null() || !props.contains_key(*key) {
+                props.insert(key.to_string(), value.value().clone());
+            }
+        }
+        let mut block = BlockContext::new();
+        block.set_base_value(Value::Object(props));
+        rc.push_block(block);
+        let result = match h.template() {
+            Some(template) => template.render(r, ctx, rc, out),
+            None => Ok(()),
+        };
+        rc.pop_block();
+        result
+    }
+}
+
+/// 计算模板表达式的helper，用法为`(rsx_expr "a + b" a=a b=b)`
+#[derive(Default)]
+struct ExprHelper {
+    cache: RwLock<HashMap<String, expr::Expr>>,
+}
+
+impl ExprHelper {
+    fn eval(&self, source: &str, scope: &HashMap<String, Value>) -> Result<Value, RenderError> {
+        if let Some(expr) = self.cache.read().ok().and_then(|c| c.get(source).cloned()) {
+            return Ok(expr.eval(scope));
+        }
+        let expr = expr::Expr::parse(source).map_err(RenderErrorReason::Other)?;
+        let value = expr.eval(scope);
+        if let Ok(mut cache) = self.cache.write() {
+            cache.insert(source.to_string(), expr);
+        }
+        Ok(value)
+    }
+}
+
+impl HelperDef for ExprHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        let source = h
+            .param(0)
+            .and_then(|param| param.value().as_str())
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex(EXPR_HELPER, 0))?;
+        let scope = h
+            .hash()
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.value().clone()))
+            .collect();
+        Ok(ScopedJson::Derived(self.eval(source, &scope)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn component(id: &str, template: &str) -> Component {
+        Component::Rsx(Arc::new(ComponentTemplate {
+            id: id.to_string(),
+            path: PathBuf::from(format!("{id}.rsx")),
+            nodes: node::parse(template, Default::default()).unwrap(),
+            components: HashMap::new(),
+            style: None,
+        }))
+    }
+
+    fn render(template: &str, data: Value) -> String {
+        let file = parser::parse(&format!("<template>{template}</template>")).unwrap();
+        let mut components = HashMap::new();
+        components.insert(
+            "Card".to_string(),
+            component("card", "<card>{title}:<slot></slot></card>"),
+        );
+        components.insert(
+            "Layout".to_string(),
+            component(
+                "layout",
+                "<header><slot name=\"header\">无标题</slot></header>{#each items as item}<main><slot>空</slot></main>{/each}",
+            ),
+        );
+        components.insert(
+            "App".to_string(),
+            Component::Client("./app.tsx".to_string()),
+        );
+        let source = compile(&file, &components).unwrap();
+        let mut engine = TemplateEngine::new(".");
+        engine
+            .handlebars
+            .register_template_string("test", source)
+            .unwrap();
+        engine.render("test", &data).unwrap()
+    }
+
+    #[test]
+    fn compile_interpolation() {
+        assert_eq!(
+            render("<h1>{title}</h1>", json!({"title": "<b>"})),
+            "<h1>&lt;b&gt;</h1>"
+        );
+        assert_eq!(
+            render("<p>{user.tags[1]}</p>", json!({"user": {"tags": [1, 2]}})),
+            "<p>2</p>"
+        );
+        assert_eq!(render("{count + 1}", json!({"count": 1})), "2");
+        assert_eq!(
+            render("{@html content}", json!({"content": "<i>x</i>"})),
+            "<i>x</i>"
+        );
+        assert_eq!(render("{{ name }}", json!({"name": "rsx"})), "rsx");
+    }
+
+    #[test]
+    fn compile_if_blocks() {
+        let template = "{#if n > 1}big{:else if n == 1}one{:else}zero{/if}";
+        assert_eq!(render(template, json!({"n": 2})), "big");
+        assert_eq!(render(template, json!({"n": 1})), "one");
+        assert_eq!(render(template, json!({"n": 0})), "zero");
+        assert_eq!(
+            render("{#if ok}yes{else}no{/if}", json!({"ok": false})),
+            "no"
+        );
+    }
+
+    #[test]
+    fn compile_each_blocks() {
+        let template = "{#each items as item, index}<li>{index}:{item}:{title}</li>{/each}";
+        assert_eq!(
+            render(template, json!({"items": ["a", "b"], "title": "t"})),
+            "<li>0:a:t</li><li>1:b:t</li>"
+        );
+        let template = "{#each items as item}{#if item % 2 == 0}{item}{/if}{:else}empty{/each}";
+        assert_eq!(render(template, json!({"items": [1, 2, 3, 4]})), "24");
+        assert_eq!(render(template, json!({"items": []})), "empty");
+    }
+
+    #[test]
+    fn compile_attributes() {
+        assert_eq!(
+            render(
+                r#"<li :key={index} bind:value={v} class="item-{v}" on:click={f}>x</li>"#,
+                json!({"v": 1})
+            ),
+            r#"<li value="1" class="item-1">x</li>"#
+        );
+        assert_eq!(
+            render("<img src={{src}} />", json!({"src": "/a.png"})),
+            r#"<img src="/a.png" />"#
+        );
+        // 表达式中的引号不结束属性值
+        assert_eq!(
+            render(
+                r#"<p class="{ok ? "a" : 'b'} item" title='{ok ? 'x' : "y"}'>x</p>"#,
+                json!({"ok": true})
+            ),
+            r#"<p class="a item" title="x">x</p>"#
+        );
+    }
+
+    #[test]
+    fn compile_components() {
+        assert_eq!(
+            render("<Card title={t}>body</Card>", json!({"t": "T"})),
+            "<card>T:body</card>"
+        );
+        assert_eq!(
+            render("<Card title=\"lit\" />", json!({})),
+            "<card>lit:</card>"
+        );
+        // 没有指令的客户端组件不渲染，带指令的渲染为岛
+        assert_eq!(render("<div><App></App></div>", json!({})), "<div></div>");
+        let html = render(
+            "<div><App client:media=\"(max-width: 600px)\" title={t} on:click={go} :key={t}>x</App></div>",
+            json!({"t": "T"}),
+        );
+        assert!(
+            html.starts_with(
+                "<div><rsx-island data-island=\"0\" data-component=\"App\" data-src=\"./app.tsx\" \
+                 data-directive=\"media\" data-value=\"(max-width: 600px)\"></rsx-island></div>\
+                 <script id=\"__rsx_script__\" type=\"application/json\">{\"__rsx_islands\":{\"0\":{\"title\":\"T\"}}}</script>"
+            ),
+            "{html}"
+        );
+        // 组件只能访问自己的props
+        assert_eq!(
+            render("<Card />", json!({"title": "page"})),
+            "<card>:</card>"
+        );
+        let data = json!({
+            "title": "page",
+            "__rsx_components": {"card": {"title": "props"}}
+        });
+        assert_eq!(
+            render("<Card>{title}</Card>", data),
+            "<card>props:page</card>"
+        );
+    }
+
+    #[test]
+    fn compile_slots() {
+        let data = json!({"items": [1, 2], "list": ["a", "b"], "name": "n"});
+        assert_eq!(
+            render("<Layout items={items} />", data.clone()),
+            "<header>无标题</header><main>空</main><main>空</main>"
+        );
+        // 插槽内容在调用方的作用域中求值
+        assert_eq!(
+            render(
+                "{#each list as x}<Layout items={items}><h1 slot=\"header\">{x}</h1>{name}</Layout>{/each}",
+                data.clone()
+            ),
+            "<header><h1>a</h1></header><main>n</main><main>n</main>\
+             <header><h1>b</h1></header><main>n</main><main>n</main>"
+        );
+        assert_eq!(
+            render(
+                "<Layout items={items}><template slot=\"header\"><Card title={name} /></template></Layout>",
+                data
+            ),
+            "<header><card>n:</card></header><main>空</main><main>空</main>"
+        );
+    }
+
+    #[test]
+    fn compile_keeps_line_numbers() {
+        let file = parser::parse(
+            "<template>\n<ul>\n{#each items as item}\n<li\n  :key={item}\n>{item}</li>\n{/each}\n</ul>\n</template>",
+        )
+        .unwrap();
+        let source = compile(&file, &HashMap::new()).unwrap();
+        assert_eq!(
+            source.matches('\n').count(),
+            file.template.unwrap().content.matches('\n').count()
+        );
+    }
+
+    #[test]
+    fn compile_errors_have_source_positions() {
+        let file = parser::parse("<template>\n<div>\n  {#if a}\n</div>\n</template>").unwrap();
+        let err = compile(&file, &HashMap::new()).unwrap_err();
+        assert_eq!((err.line, err.column), (4, 1));
+
+        let file = parser::parse("<template>\n  <Unknown />\n</template>").unwrap();
+        let err = compile(&file, &HashMap::new()).unwrap_err();
+        assert_eq!((err.line, err.column), (2, 3));
+
+        let file = parser::parse("<template>\n<p>{a +}</p></template>").unwrap();
+        let err = compile(&file, &HashMap::new()).unwrap_err();
+        assert_eq!((err.line, err.column), (2, 5));
+
+        let file =
+            parser::parse("<template>\n<p class=\"{ok ? \"a\" : \"b\"></p>\n</template>").unwrap();
+        let err = compile(&file, &HashMap::new()).unwrap_err();
+        assert_eq!((err.line, err.column), (2, 11));
+        assert_eq!(err.message, "unterminated `{`");
+    }
+
+    #[test]
+    fn compile_defer_regions() {
+        let file = parser::parse(
+            "<template><defer><Card slot=\"fallback\" title=\"加载中\" /><p>{title}</p></defer></template>",
+        )
+        .unwrap();
+        let mut components = HashMap::new();
+        components.insert(
+            "Card".to_string(),
+            component("card", "<card>{title}</card>"),
+        );
+        let (source, regions) = compile_page(&file, &components, &[]).unwrap();
+        assert_eq!(
+            regions,
+            vec![Region {
+                template: "<p>{{title}}</p>".to_string(),
+                awaits: Vec::new()
+            }]
+        );
+        let file = parser::parse(
+            "<template><defer await=\"comments, likes\">{comments}</defer></template>",
+        )
+        .unwrap();
+        let (_, regions) = compile_page(&file, &HashMap::new(), &[]).unwrap();
+        assert_eq!(regions[0].awaits, ["comments", "likes"]);
+
+        let mut engine = TemplateEngine::new(".");
+        engine
+            .handlebars
+            .register_template_string("test", source)
+            .unwrap();
+        let data = json!({"title": "新闻"});
+        assert_eq!(engine.render("test", &data).unwrap(), "<p>新闻</p>");
+        let shell = json!({"title": "新闻", SHELL_KEY: true});
+        assert_eq!(
+            engine.render("test", &shell).unwrap(),
+            "<div id=\"__rsx_defer_0\" style=\"display:contents\"><card>加载中</card></div>"
+        );
+
+        for template in [
+            "{#each list as x}<defer>{x}</defer>{/each}",
+            "<defer><defer></defer></defer>",
+        ] {
+            let file = parser::parse(&format!("<template>{template}</template>")).unwrap();
+            assert!(compile(&file, &HashMap::new()).is_err(), "{template}");
+        }
+    }
+}
@@ -0,0 +1,688 @@
+//! 模板语法树
+//!
+//! 将`<template>`区块解析为元素、文本、插值和控制块组成的节点树
+
+use crate::parser::{ParseError, Position};
+
+/// 没有子节点的HTML空元素
+const VOID_ELEMENTS: [&str; 14] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+/// 内容按原文处理的元素
+const RAW_TEXT_ELEMENTS: [&str; 3] = ["script", "style", "textarea"];
+
+/// 模板节点
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    /// 纯文本
+    Text(String),
+    /// HTML注释，原样输出
+    Comment(String),
+    /// 原样保留的Handlebars片段，如`{{ name }}`
+    Handlebars(String),
+    /// `{expr}`插值
+    Expr(Expression),
+    /// `{@html expr}`原始HTML插值
+    Html(Expression),
+    /// `{#if}...{:else if}...{:else}...{/if}`
+    If {
+        branches: Vec<(Expression, Vec<Node>)>,
+        otherwise: Option<Vec<Node>>,
+    },
+    /// `{#each items as item, index}...{:else}...{/each}`
+    Each {
+        items: Expression,
+        item: String,
+        index: Option<String>,
+        body: Vec<Node>,
+        otherwise: Option<Vec<Node>>,
+    },
+    /// HTML元素或组件
+    Element(Element),
+}
+
+/// 模板中的表达式及其位置
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression {
+    pub source: String,
+    pub position: Position,
+}
+
+/// 元素属性值
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttrValue {
+    /// 无值的布尔属性
+    Empty,
+    /// 由文本和插值组成的引号属性值
+    Parts(Vec<AttrPart>),
+    /// `name={expr}`
+    Expr(Expression),
+}
+
+/// 引号属性值中的一段
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttrPart {
+    Text(String),
+    Handlebars(String),
+    Expr(Expression),
+}
+
+/// 元素属性，`leading`保存属性前的空白以保持行号不变
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attr {
+    pub name: String,
+    pub value: AttrValue,
+    pub leading: String,
+}
+
+impl Attr {
+    /// 获取纯文本属性值
+    pub fn text(&self) -> Option<String> {
+        match &self.value {
+            AttrValue::Parts(parts) => parts
+                .iter()
+                .map(|part| match part {
+                    AttrPart::Text(text) => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect(),
+            _ => None,
+        }
+    }
+}
+
+/// HTML元素或组件
+#[derive(Debug, Clone, PartialEq)]
+pub struct Element {
+    pub name: String,
+    pub attrs: Vec<Attr>,
+    pub children: Vec<Node>,
+    pub self_closing: bool,
+    /// 开始标签`>`之前的空白
+    pub trailing: String,
+    pub position: Position,
+}
+
+impl Element {
+    /// 是否为组件（标签名以大写字母开头）
+    pub fn is_component(&self) -> bool {
+        self.name.starts_with(|c: char| c.is_ascii_uppercase())
+    }
+
+    /// 是否为HTML空元素
+    pub fn is_void(&self) -> bool {
+        !self.is_component() && VOID_ELEMENTS.contains(&self.name.to_ascii_lowercase().as_str())
+    }
+
+    /// 按名称查找属性
+    pub fn attr(&self, name: &str) -> Option<&Attr> {
+        self.attrs.iter().find(|attr| attr.name == name)
+    }
+}
+
+/// 解析模板源码，`base`为模板内容在.rsx文件中的起始位置
+pub fn parse(source: &str, base: Position) -> Result<Vec<Node>, ParseError> {
+    TemplateParser::new(source, base).parse()
+}
+
+enum Frame {
+    Root,
+    Element(Element),
+    If {
+        branches: Vec<(Expression, Vec<Node>)>,
+        condition: Expression,
+        otherwise: bool,
+    },
+    Each {
+        items: Expression,
+        item: String,
+        index: Option<String>,
+        body: Option<Vec<Node>>,
+    },
+}
+
+struct TemplateParser<'a> {
+    source: &'a str,
+    pos: usize,
+    base: Position,
+    line_starts: Vec<usize>,
+    stack: Vec<(Frame, Vec<Node>, usize)>,
+}
+
+impl<'a> TemplateParser<'a> {
+    fn new(source: &'a str, base: Position) -> Self {
+        let mut line_starts = vec![0];
+        line_starts.extend(source.match_indices('\n').map(|(i, _)| i + 1));
+        Self {
+            source,
+            pos: 0,
+            base,
+            line_starts,
+            stack: vec![(Frame::Root, Vec::new(), 0)],
+        }
+    }
+
+    fn parse(mut self) -> Result<Vec<Node>, ParseError> {
+        let mut text_start = 0;
+        while self.pos < self.source.len() {
+            let rest = &self.source[self.pos..];
+            let c = rest.chars().next().unwrap_or_default();
+            let is_tag = c == '<'
+                && rest[1..].starts_with(|c: char| c.is_ascii_alphabetic() || c == '/' || c == '!');
+            if !is_tag && c != '{' {
+                self.pos += c.len_utf8();
+                continue;
+            }
+            self.flush_text(text_start);
+            if is_tag {
+                self.parse_tag()?;
+            } else {
+                self.parse_brace()?;
+            }
+            text_start = self.pos;
+        }
+        self.flush_text(text_start);
+
+        let (frame, children, start) = self.stack.pop().unwrap_or((Frame::Root, Vec::new(), 0));
+        match frame {
+            Frame::Root => Ok(children),
+            Frame::Element(element) => {
+                Err(self.error_at(start, format!("unclosed element <{}>", element.name)))
+            }
+            Frame::If { .. } => Err(self.error_at(start, "unclosed {#if} block")),
+            Frame::Each { .. } => Err(self.error_at(start, "unclosed {#each} block")),
+        }
+    }
+
+    fn flush_text(&mut self, start: usize) {
+        if start < self.pos {
+            let text = self.source[start..self.pos].to_string();
+            self.push(Node::Text(text));
+        }
+    }
+
+    fn push(&mut self, node: Node) {
+        if let Some((_, children, _)) = self.stack.last_mut() {
+            children.push(node);
+        }
+    }
+
+    fn parse_tag(&mut self) -> Result<(), ParseError> {
+        let start = self.pos;
+        let rest = &self.source[self.pos..];
+        if rest.starts_with("<!--") {
+            let end = rest
+                .find("-->")
+                .ok_or_else(|| self.error_at(start, "unterminated comment"))?;
+            self.push(Node::Comment(rest[..end + 3].to_string()));
+            self.pos += end + 3;
+            return Ok(());
+        }
+        if rest.starts_with("<!") {
+            let end = rest
+                .find('>')
+                .ok_or_else(|| self.error_at(start, "unterminated declaration"))?;
+            self.push(Node::Text(rest[..end + 1].to_string()));
+            self.pos += end + 1;
+            return Ok(());
+        }
+        if let Some(rest) = rest.strip_prefix("</") {
+            let end = rest
+                .find('>')
+                .ok_or_else(|| self.error_at(start, "unterminated closing tag"))?;
+            let name = rest[..end].trim().to_string();
+            self.pos += end + 3;
+            return self.close_element(&name, start);
+        }
+
+        self.pos += 1;
+        let name =
+            self.take_while(|c| c.is_ascii_alphanumeric() || c == '-' || c == ':' || c == '.');
+        let position = self.position(start);
+        let mut attrs = Vec::new();
+        let (self_closing, trailing) = loop {
+            let ws_start = self.pos;
+            self.skip_whitespace();
+            let leading = self.source[ws_start..self.pos].to_string();
+            let rest = &self.source[self.pos..];
+            if rest.is_empty() {
+                return Err(self.error_at(start, format!("unterminated tag <{name}>")));
+            }
+            if rest.starts_with("/>") {
+                self.pos += 2;
+                break (true, leading);
+            }
+            if rest.starts_with('>') {
+                self.pos += 1;
+                break (false, leading);
+            }
+            attrs.push(self.parse_attr(leading)?);
+        };
+
+        let element = Element {
+            name,
+            attrs,
+            children: Vec::new(),
+            self_closing,
+            trailing,
+            position,
+        };
+        if self_closing || element.is_void() {
+            self.push(Node::Element(element));
+        } else if RAW_TEXT_ELEMENTS.contains(&element.name.as_str()) {
+            let close = format!("</{}", element.name);
+            let end = self.source[self.pos..].find(&close).ok_or_else(|| {
+                self.error_at(start, format!("unclosed element <{}>", element.name))
+            })?;
+            let mut element = element;
+            let text = &self.source[self.pos..self.pos + end];
+            if !text.is_empty() {
+                element.children.push(Node::Text(text.to_string()));
+            }
+            self.pos += end;
+            let gt = self.source[self.pos..].find('>').unwrap_or_default();
+            self.pos += gt + 1;
+            self.push(Node::Element(element));
+        } else {
+            self.stack
+                .push((Frame::Element(element), Vec::new(), start));
+        }
+        Ok(())
+    }
+
+    fn close_element(&mut self, name: &str, start: usize) -> Result<(), ParseError> {
+        match self.stack.last() {
+            Some((Frame::Element(element), _, _)) if element.name == name => {}
+            Some((Frame::Element(element), _, _)) => {
+                return Err(self.error_at(
+                    start,
+                    format!("unexpected </{name}>, expected </{}>", element.name),
+                ));
+            }
+            Some((Frame::If { .. }, _, open)) => {
+                let open = *open;
+                let position = self.position(open);
+                return Err(self.error_at(
+                    start,
+                    format!(
+                        "unexpected </{name}>, {{#if}} opened at {}:{} is not closed",
+                        position.line, position.column
+                    ),
+                ));
+            }
+            Some((Frame::Each { .. }, _, open)) => {
+                let open = *open;
+                let position = self.position(open);
+                return Err(self.error_at(
+                    start,
+                    format!(
+                        "unexpected </{name}>, {{#each}} opened at {}:{} is not closed",
+                        position.line, position.column
+                    ),
+                ));
+            }
+            _ => return Err(self.error_at(start, format!("unexpected closing tag </{name}>"))),
+        }
+        if let Some((Frame::Element(mut element), children, _)) = self.stack.pop() {
+            element.children = children;
+            self.push(Node::Element(element));
+        }
+        Ok(())
+    }
+
+    fn parse_attr(&mut self, leading: String) -> Result<Attr, ParseError> {
+        let start = self.pos;
+        let name = self.take_while(|c| !c.is_whitespace() && c != '=' && c != '>' && c != '/');
+        if name.is_empty() {
+            return Err(self.error_at(start, "invalid attribute"));
+        }
+        let before_eq = self.pos;
+        self.skip_whitespace();
+        if !self.source[self.pos..].starts_with('=') {
+            self.pos = before_eq;
+            return Ok(Attr {
+                name,
+                value: AttrValue::Empty,
+                leading,
+            });
+        }
+        self.pos += 1;
+        self.skip_whitespace();
+        let value_start = self.pos;
+        let rest = &self.source[self.pos..];
+        let value = match rest.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let end = self.find_quote_end(value_start, quote)?;
+                let raw = &self.source[value_start + 1..end];
+                self.pos = end + 1;
+                AttrValue::Parts(self.parse_attr_parts(raw, value_start + 1)?)
+            }
+            Some('{') if rest.starts_with("{{") => {
+                let end = rest
+                    .find("}}")
+                    .ok_or_else(|| self.error_at(value_start, "unterminated {{ in attribute"))?;
+                self.pos += end + 2;
+                AttrValue::Parts(vec![AttrPart::Handlebars(rest[..end + 2].to_string())])
+            }
+            Some('{') => {
+                let end = self.find_brace_end(value_start)?;
+                self.pos = end + 1;
+                AttrValue::Expr(self.expression(value_start + 1, end)?)
+            }
+            _ => {
+                let text = self.take_while(|c| !c.is_whitespace() && c != '>');
+                AttrValue::Parts(vec![AttrPart::Text(text)])
+            }
+        };
+        Ok(Attr {
+            name,
+            value,
+            leading,
+        })
+    }
+
+    fn parse_attr_parts(&self, raw: &str, offset: usize) -> Result<Vec<AttrPart>, ParseError> {
+        let mut parts = Vec::new();
+        let mut text_start = 0;
+        let mut i = 0;
+        while let Some(found) = raw[i..].find('{') {
+            let at = i + found;
+            if at > text_start {
+                parts.push(AttrPart::Text(raw[text_start..at].to_string()));
+            }
+            if raw[at..].starts_with("{{") {
+                let end = raw[at..]
+                    .find("}}")
+                    .ok_or_else(|| self.error_at(offset + at, "unterminated {{ in attribute"))?;
+                parts.push(AttrPart::Handlebars(raw[at..at + end + 2].to_string()));
+                i = at + end + 2;
+            } else {
+                let end = self.find_brace_end(offset + at)? - offset;
+                parts.push(AttrPart::Expr(
+                    self.expression(offset + at + 1, offset + end)?,
+                ));
+                i = end + 1;
+            }
+            text_start = i;
+        }
+        if text_start < raw.len() {
+            parts.push(AttrPart::Text(raw[text_start..].to_string()));
+        }
+        Ok(parts)
+    }
+
+    fn parse_brace(&mut self) -> Result<(), ParseError> {
+        let start = self.pos;
+        let rest = &self.source[self.pos..];
+        if rest.starts_with("{{") {
+            let close = if rest.starts_with("{{{") { "}}}" } else { "}}" };
+            let end = rest
+                .find(close)
+                .ok_or_else(|| self.error_at(start, "unterminated {{"))?;
+            self.push(Node::Handlebars(rest[..end + close.len()].to_string()));
+            self.pos += end + close.len();
+            return Ok(());
+        }
+
+        let end = self.find_brace_end(start)?;
+        self.pos = end + 1;
+        let inner = &self.source[start + 1..end];
+        let trimmed = inner.trim_start();
+        let inner_start = start + 1 + (inner.len() - trimmed.len());
+        let trimmed = trimmed.trim_end();
+        let inner_end = inner_start + trimmed.len();
+
+        if let Some(condition) = trimmed.strip_prefix("#if") {
+            let condition = self.expression(inner_end - condition.len(), inner_end)?;
+            self.stack.push((
+                Frame::If {
+                    branches: Vec::new(),
+                    condition,
+                    otherwise: false,
+                },
+                Vec::new(),
+                start,
+            ));
+        } else if let Some(each) = trimmed.strip_prefix("#each") {
+            self.open_each(each, inner_end - each.len(), start)?;
+        } else if trimmed == ":else" || trimmed == "else" {
+            self.open_else(None, start)?;
+        } else if let Some(condition) = trimmed
+            .strip_prefix(":else if")
+            .or_else(|| trimmed.strip_prefix("else if"))
+        {
+            let condition = self.expression(inner_end - condition.len(), inner_end)?;
+            self.open_else(Some(condition), start)?;
+        } else if trimmed == "/if" {
+            self.close_if(start)?;
+        } else if trimmed == "/each" {
+            self.close_each(start)?;
+        } else if let Some(html) = trimmed.strip_prefix("@html") {
+            let expr = self.expression(inner_end - html.len(), inner_end)?;
+            self.push(Node::Html(expr));
+        } else if trimmed.starts_with(['#', '/', ':', '@']) {
+            return Err(self.error_at(start, format!("unknown template block `{{{trimmed}}}`")));
+        } else {
+            let expr = self.expression(inner_start, inner_end)?;
+            self.push(Node::Expr(expr));
+        }
+        Ok(())
+    }
+
+    fn open_each(&mut self, each: &str, offset: usize, start: usize) -> Result<(), ParseError> {
+        let (items, binding) = each
+            .rsplit_once(" as ")
+            .ok_or_else(|| self.error_at(start, "expected `{#each items as item}`"))?;
+        let mut names = binding.split(',').map(str::trim);
+        let item = names.next().unwrap_or_default().to_string();
+        let index = names.next().map(String::from);
+        let valid = |name: &str| {
+            !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+        };
+        if !valid(&item) || index.as_deref().is_some_and(|index| !valid(index)) {
+            return Err(self.error_at(
+                start,
+                format!("invalid {{#each}} binding `{}`", binding.trim()),
+            ));
+        }
+        let items = self.expression(offset, offset + items.len())?;
+        self.stack.push((
+            Frame::Each {
+                items,
+                item,
+                index,
+                body: None,
+            },
+            Vec::new(),
+            start,
+        ));
+        Ok(())
+    }
+
+    fn open_else(&mut self, condition: Option<Expression>, start: usize) -> Result<(), ParseError> {
+        let Some((frame, children, _)) = self.stack.last_mut() else {
+            return Err(self.error_at(start, "unexpected {:else}"));
+        };
+        let children = std::mem::take(children);
+        match frame {
+            Frame::If {
+                branches,
+                condition: current,
+                otherwise,
+            } if !*otherwise => {
+                let next = condition.clone();
+                match next {
+                    Some(next) => branches.push((std::mem::replace(current, next), children)),
+                    None => {
+                        branches.push((current.clone(), children));
+                        *otherwise = true;
+                    }
+                }
+                Ok(())
+            }
+            Frame::Each { body, .. } if body.is_none() && condition.is_none() => {
+                *body = Some(children);
+                Ok(())
+            }
+            _ => Err(self.error_at(start, "unexpected {:else}")),
+        }
+    }
+
+    fn close_if(&mut self, start: usize) -> Result<(), ParseError> {
+        if !matches!(self.stack.last(), Some((Frame::If { .. }, _, _))) {
+            return Err(self.error_at(start, "unexpected {/if}"));
+        }
+        if let Some((
+            Frame::If {
+                mut branches,
+                condition,
+                otherwise,
+            },
+            children,
+            _,
+        )) = self.stack.pop()
+        {
+            let otherwise = if otherwise {
+                Some(children)
+            } else {
+                branches.push((condition, children));
+                None
+            };
+            self.push(Node::If {
+                branches,
+                otherwise,
+            });
+        }
+        Ok(())
+    }
+
+    fn close_each(&mut self, start: usize) -> Result<(), ParseError> {
+        if !matches!(self.stack.last(), Some((Frame::Each { .. }, _, _))) {
+            return Err(self.error_at(start, "unexpected {/each}"));
+        }
+        if let Some((
+            Frame::Each {
+                items,
+                item,
+                index,
+                body,
+            },
+            children,
+            _,
+        )) = self.stack.pop()
+        {
+            let (body, otherwise) = match body {
+                Some(body) => (body, Some(children)),
+                None => (children, None),
+            };
+            self.push(Node::Each {
+                items,
+                item,
+                index,
+                body,
+                otherwise,
+            });
+        }
+        Ok(())
+    }
+
+    /// 查找与`{`匹配的`}`，忽略字符串中的括号
+    /// 属性值的结束引号，跳过`{…}`和`{{…}}`中的引号，如`class="{ok ? "a" : "b"}"`
+    fn find_quote_end(&self, start: usize, quote: char) -> Result<usize, ParseError> {
+        let mut i = start + 1;
+        while let Some(c) = self.source[i..].chars().next() {
+            if c == quote {
+                return Ok(i);
+            }
+            if self.source[i..].starts_with("{{") {
+                let end = self.source[i..]
+                    .find("}}")
+                    .ok_or_else(|| self.error_at(i, "unterminated {{ in attribute"))?;
+                i += end + 2;
+            } else if c == '{' {
+                i = self.find_brace_end(i)? + 1;
+            } else {
+                i += c.len_utf8();
+            }
+        }
+        Err(self.error_at(start, "unterminated attribute value"))
+    }
+
+    fn find_brace_end(&self, start: usize) -> Result<usize, ParseError> {
+        let mut depth = 0usize;
+        let mut quote = None;
+        for (i, c) in self.source[start..].char_indices() {
+            match (quote, c) {
+                (Some(q), c) if c == q => quote = None,
+                (Some(_), _) => {}
+                (None, '\'' | '"' | '`') => quote = Some(c),
+                (None, '{') => depth += 1,
+                (None, '}') => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(start + i);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Err(self.error_at(start, "unterminated `{`"))
+    }
+
+    fn expression(&self, start: usize, end: usize) -> Result<Expression, ParseError> {
+        let raw = &self.source[start..end];
+        let source = raw.trim();
+        if source.is_empty() {
+            return Err(self.error_at(start, "empty expression"));
+        }
+        let offset = start + (raw.len() - raw.trim_start().len());
+        super::expr::Expr::parse(source).map_err(|message| self.error_at(offset, message))?;
+        Ok(Expression {
+            source: source.to_string(),
+            position: self.position(offset),
+        })
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> String {
+        let rest = &self.source[self.pos..];
+        let len = rest.find(|c| !predicate(c)).unwrap_or(rest.len());
+        self.pos += len;
+        rest[..len].to_string()
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.source[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    /// 将模板内偏移换算为.rsx文件中的位置
+    fn position(&self, offset: usize) -> Position {
+        let line = self.line_starts.partition_point(|start| *start <= offset);
+        let line_start = self.line_starts[line - 1];
+        let column = self.source[line_start..offset].chars().count() + 1;
+        Position {
+            offset: self.base.offset + offset,
+            line: self.base.line + line - 1,
+            column: if line == 1 {
+                self.base.column + column - 1
+            } else {
+                column
+            },
+        }
+    }
+
+    fn error_at(&self, offset: usize, message: impl Into<String>) -> ParseError {
+        let position = self.position(offset);
+        ParseError {
+            message: message.into(),
+            line: position.line,
+            column: position.column,
+            file: None,
+        }
+    }
+}
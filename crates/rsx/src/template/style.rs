@@ -0,0 +1,350 @@
+//! 组件样式作用域
+//!
+//! `<style scoped>`区块为Scoped样式：按样式内容的hash生成作用域属性，如`data-rsx-1b2f3c4d`，
+//! 规则中每个选择器的最后一个复合选择器加上`[data-rsx-1b2f3c4d]`，同一文件模板中的元素
+//! 加上该属性，`:global(.x)`中的选择器不加作用域。没有`scoped`属性的`<style>`为全局样式
+//!
+//! `@media`、`@supports`等条件规则中的规则同样处理，`@keyframes`、`@font-face`等
+//! 规则的内容原样保留。页面用到的全部样式按作用域去重后合并为一个`<style>`插入`<head>`
+
+use crate::parser::Block;
+
+/// 作用域属性的前缀
+pub const SCOPE_ATTR_PREFIX: &str = "data-rsx-";
+
+/// 合并后的`<style>`元素的ID
+pub const STYLE_ID: &str = "__rsx_style__";
+
+/// 不加作用域的伪类
+const GLOBAL_PSEUDO: &str = ":global(";
+
+/// 内容为规则列表的at规则，其中的选择器需要加作用域
+const NESTED_AT_RULES: [&str; 5] = ["@media", "@supports", "@container", "@layer", "@document"];
+
+/// 处理后的`<style>`区块
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Style {
+    /// 作用域属性名，全局样式为`None`
+    pub scope: Option<String>,
+    /// 处理后的CSS
+    pub css: String,
+}
+
+impl Style {
+    /// 处理.rsx文件的`<style>`区块，内容为空时返回`None`
+    pub fn from_block(block: &Block) -> Option<Self> {
+        let css = block.content.trim();
+        if css.is_empty() {
+            return None;
+        }
+        if !block.has_attr("scoped") {
+            return Some(Self {
+                scope: None,
+                css: css.to_string(),
+            });
+        }
+        let scope = scope_attr(css);
+        Some(Self {
+            css: scope_css(css, &scope),
+            scope: Some(scope),
+        })
+    }
+
+    /// 去重用的键，Scoped样式为作用域属性，全局样式为内容
+    fn key(&self) -> &str {
+        self.scope.as_deref().unwrap_or(&self.css)
+    }
+}
+
+/// 按样式内容生成作用域属性名
+pub fn scope_attr(css: &str) -> String {
+    // FNV-1a，结果只需在构建之间保持稳定
+    let hash = css.bytes().fold(0x811c9dc5u32, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x01000193)
+    });
+    format!("{SCOPE_ATTR_PREFIX}{hash:08x}")
+}
+
+/// 按出现顺序合并样式，相同作用域的样式只保留一次
+pub fn merge<'a>(styles: impl IntoIterator<Item = &'a Style>) -> String {
+    let mut seen: Vec<&str> = Vec::new();
+    let mut out = String::new();
+    for style in styles {
+        if seen.contains(&style.key()) {
+            continue;
+        }
+        seen.push(style.key());
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(&style.css);
+    }
+    out
+}
+
+/// 生成插入`<head>`的`<style>`元素，没有样式时为空
+pub fn style_tag(css: &str) -> String {
+    if css.is_empty() {
+        return String::new();
+    }
+    format!(
+        "<style id=\"{STYLE_ID}\">{}</style>",
+        css.replace("</style", "<\\/style")
+    )
+}
+
+/// 给CSS中的选择器加上作用域属性`scope`
+pub fn scope_css(css: &str, scope: &str) -> String {
+    let mut out = String::with_capacity(css.len());
+    let mut rest = css;
+    while !rest.is_empty() {
+        let Some(end) = find_top_level(rest, &['{', ';', '}']) else {
+            out.push_str(rest);
+            break;
+        };
+        let (prelude, delimiter) = (&rest[..end], rest.as_bytes()[end]);
+        if delimiter != b'{' {
+            // `@import`等语句或多余的`}`
+            out.push_str(&rest[..=end]);
+            rest = &rest[end + 1..];
+            continue;
+        }
+        let body_end = matching_brace(rest, end);
+        let body = &rest[end + 1..body_end];
+        let trimmed = prelude.trim_start();
+        let at_rule = trimmed
+            .split(|c: char| c.is_whitespace() || c == '(' || c == '{')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        if trimmed.starts_with('@') {
+            out.push_str(prelude);
+            out.push('{');
+            if NESTED_AT_RULES.contains(&at_rule.as_str()) {
+                out.push_str(&scope_css(body, scope));
+            } else {
+                out.push_str(body);
+            }
+        } else {
+            let leading = &prelude[..prelude.len() - trimmed.len()];
+            let selectors = trimmed.trim_end();
+            out.push_str(leading);
+            out.push_str(&scope_selectors(selectors, scope));
+            out.push_str(&trimmed[selectors.len()..]);
+            out.push('{');
+            out.push_str(body);
+        }
+        if body_end < rest.len() {
+            out.push('}');
+            rest = &rest[body_end + 1..];
+        } else {
+            rest = "";
+        }
+    }
+    out
+}
+
+/// 给逗号分隔的选择器列表加上作用域
+fn scope_selectors(selectors: &str, scope: &str) -> String {
+    split_top_level(selectors, ',')
+        .into_iter()
+        .map(|selector| {
+            let trimmed = selector.trim();
+            let leading = &selector[..selector.len() - selector.trim_start().len()];
+            format!("{leading}{}", scope_selector(trimmed, scope))
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// 在最后一个复合选择器的伪元素之前加上`[scope]`，`:global(x)`替换为`x`
+fn scope_selector(selector: &str, scope: &str) -> String {
+    if selector.is_empty() {
+        return String::new();
+    }
+    if let Some(start) = selector.find(GLOBAL_PSEUDO) {
+        let inner = start + GLOBAL_PSEUDO.len();
+        let end =
+            find_top_level(&selector[inner..], &[')']).map_or(selector.len(), |end| inner + end);
+        let before = selector[..start].trim_end();
+        let global = &selector[inner..end];
+        let after = selector.get(end + 1..).unwrap_or_default();
+        // `:global`之前的部分仍然加作用域，之后的部分原样保留
+        let mut out = if before.is_empty() {
+            String::new()
+        } else {
+            format!("{} ", scope_selector(before, scope))
+        };
+        out.push_str(global);
+        out.push_str(after);
+        return out;
+    }
+    // 最后一个复合选择器的起点：顶层的空白或组合符之后
+    let mut compound = 0;
+    let mut depth = 0usize;
+    let mut quote = None;
+    for (index, c) in selector.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '(' | '[') => depth += 1,
+            (None, ')' | ']') => depth = depth.saturating_sub(1),
+            (None, c) if depth == 0 && (c.is_whitespace() || matches!(c, '>' | '+' | '~')) => {
+                compound = index + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    let last = &selector[compound..];
+    let insert = last
+        .find("::")
+        .or_else(|| {
+            [":before", ":after", ":first-line", ":first-letter"]
+                .iter()
+                .filter_map(|pseudo| last.find(pseudo))
+                .min()
+        })
+        .unwrap_or(last.len());
+    let at = compound + insert;
+    format!("{}[{scope}]{}", &selector[..at], &selector[at..])
+}
+
+/// 查找不在字符串、注释、括号中的第一个分隔符
+fn find_top_level(css: &str, delimiters: &[char]) -> Option<usize> {
+    let bytes = css.as_bytes();
+    let mut depth = 0usize;
+    let mut quote = None;
+    let mut index = 0;
+    while index < bytes.len() {
+        let c = bytes[index] as char;
+        match quote {
+            Some(q) => {
+                if c == '\\' {
+                    index += 1;
+                } else if c == q {
+                    quote = None;
+                }
+            }
+            None => match c {
+                '/' if bytes.get(index + 1) == Some(&b'*') => {
+                    index = css[index + 2..]
+                        .find("*/")
+                        .map_or(bytes.len(), |end| index + 2 + end + 1);
+                }
+                '"' | '\'' => quote = Some(c),
+                '(' | '[' => depth += 1,
+                ')' | ']' if depth > 0 => depth -= 1,
+                c if depth == 0 && delimiters.contains(&c) => return Some(index),
+                _ => {}
+            },
+        }
+        index += 1;
+    }
+    None
+}
+
+/// `{`对应的`}`的位置，没有闭合时为末尾
+fn matching_brace(css: &str, open: usize) -> usize {
+    let mut depth = 0usize;
+    let mut offset = open;
+    while let Some(index) = find_top_level(&css[offset..], &['{', '}']) {
+        let index = offset + index;
+        if css.as_bytes()[index] == b'{' {
+            depth += 1;
+        } else {
+            depth -= 1;
+            if depth == 0 {
+                return index;
+            }
+        }
+        offset = index + 1;
+    }
+    css.len()
+}
+
+/// 按不在字符串和括号中的分隔符拆分
+fn split_top_level(text: &str, delimiter: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut rest = text;
+    while let Some(index) = find_top_level(rest, &[delimiter]) {
+        parts.push(&rest[..index]);
+        rest = &rest[index + 1..];
+    }
+    parts.push(rest);
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCOPE: &str = "data-rsx-1";
+
+    #[test]
+    fn scope_selectors() {
+        let css = scope_css(
+            "/* a, b { } */\n.a, ul > li.b:hover, p::before { color: red; }\n\
+             .x :global(.y .z), :global(body) {}\na[href=\"a b\"] + i:after {}",
+            SCOPE,
+        );
+        assert_eq!(
+            css,
+            "/* a, b { } */\n.a[data-rsx-1], ul > li.b:hover[data-rsx-1], p[data-rsx-1]::before { color: red; }\n\
+             .x[data-rsx-1] .y .z, body {}\na[href=\"a b\"] + i[data-rsx-1]:after {}"
+        );
+    }
+
+    #[test]
+    fn scope_at_rules() {
+        let css = scope_css(
+            "@import url(\"a.css\");\n@media (max-width: 600px) { .a { b: c } }\n\
+             @keyframes fade { from { opacity: 0 } to { opacity: 1 } }\n.b { animation: fade 1s }",
+            SCOPE,
+        );
+        assert_eq!(
+            css,
+            "@import url(\"a.css\");\n@media (max-width: 600px) { .a[data-rsx-1] { b: c } }\n\
+             @keyframes fade { from { opacity: 0 } to { opacity: 1 } }\n.b[data-rsx-1] { animation: fade 1s }"
+        );
+    }
+
+    #[test]
+    fn scope_only_scoped_blocks() {
+        let file = crate::parser::parse(
+            "<template><p></p></template><style scoped>.a { color: red; }</style>",
+        )
+        .unwrap();
+        let style = Style::from_block(file.style.as_ref().unwrap()).unwrap();
+        assert_eq!(
+            style.scope.as_deref(),
+            Some(scope_attr(".a { color: red; }").as_str())
+        );
+
+        let file =
+            crate::parser::parse("<template><p></p></template><style>.a {}</style>").unwrap();
+        let style = Style::from_block(file.style.as_ref().unwrap()).unwrap();
+        assert_eq!(style.scope, None);
+        assert_eq!(style.css, ".a {}");
+    }
+
+    #[test]
+    fn merge_styles() {
+        let a = Style {
+            scope: Some(scope_attr(".a{}")),
+            css: ".a{}".to_string(),
+        };
+        let global = Style {
+            scope: None,
+            css: "body{}".to_string(),
+        };
+        assert_eq!(scope_attr(".a{}"), scope_attr(".a{}"));
+        assert_ne!(scope_attr(".a{}"), scope_attr(".b{}"));
+        assert_eq!(merge([&a, &global, &a, &global]), ".a{}\nbody{}");
+        assert_eq!(
+            style_tag("i{}</style>"),
+            "<style id=\"__rsx_style__\">i{}<\\/style></style>"
+        );
+        assert_eq!(style_tag(""), "");
+    }
+}
@@ -0,0 +1,332 @@
+//! 根据Rust类型定义生成TypeScript类型
+//!
+//! 在代码生成阶段读取页面frontmatter中`#[derive(Props)]`的结构体，按serde的序列化规则
+//! 生成对应的TypeScript类型，frontmatter之外定义的类型生成为`unknown`
+
+use std::collections::HashMap;
+use syn::{Attribute, Fields, GenericArgument, Item, LitStr, PathArguments, Type};
+
+/// frontmatter中定义的类型
+pub struct TypeScope<'a> {
+    items: HashMap<String, &'a Item>,
+}
+
+impl<'a> TypeScope<'a> {
+    /// 收集frontmatter中的结构体和枚举
+    pub fn new(file: &'a syn::File) -> Self {
+        let items = file
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Struct(s) => Some((s.ident.to_string(), item)),
+                Item::Enum(e) => Some((e.ident.to_string(), item)),
+                _ => None,
+            })
+            .collect();
+        Self { items }
+    }
+
+    /// 生成名为`name`的类型定义，如`{ id: number; name: string | null }`
+    pub fn declare(&self, name: &str) -> Option<String> {
+        self.items
+            .get(name)
+            .map(|item| self.item(item, &mut vec![name.to_string()]))
+    }
+
+    /// `stack`为正在展开的类型，递归引用生成为`unknown`
+    fn item(&self, item: &Item, stack: &mut Vec<String>) -> String {
+        match item {
+            Item::Struct(s) => match &s.fields {
+                Fields::Named(fields) => {
+                    let rename_all = serde_attr(&s.attrs, "rename_all");
+                    let fields: Vec<String> = fields
+                        .named
+                        .iter()
+                        .filter(|field| !has_serde_flag(&field.attrs, "skip"))
+                        .filter(|field| !has_serde_flag(&field.attrs, "skip_serializing"))
+                        .map(|field| {
+                            let ident = field.ident.as_ref().map(ToString::to_string);
+                            let name = serde_attr(&field.attrs, "rename").unwrap_or_else(|| {
+                                rename(&ident.unwrap_or_default(), rename_all.as_deref())
+                            });
+                            let optional =
+                                if serde_attr(&field.attrs, "skip_serializing_if").is_some() {
+                                    "?"
+                                } else {
+                                    ""
+                                };
+                            format!(
+                                "{}{optional}: {}",
+                                property(&name),
+                                self.ty(&field.ty, stack)
+                            )
+                        })
+                        .collect();
+                    if fields.is_empty() {
+                        "{}".to_string()
+                    } else {
+                        format!("{{ {} }}", fields.join("; "))
+                    }
+                }
+                Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                    self.ty(&fields.unnamed[0].ty, stack)
+                }
+                Fields::Unnamed(fields) => format!(
+                    "[{}]",
+                    fields
+                        .unnamed
+                        .iter()
+                        .map(|field| self.ty(&field.ty, stack))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                Fields::Unit => "null".to_string(),
+            },
+            // 只支持全部为单元变体的枚举，序列化为字符串
+            Item::Enum(e) if e.variants.iter().all(|v| matches!(v.fields, Fields::Unit)) => {
+                let rename_all = serde_attr(&e.attrs, "rename_all");
+                e.variants
+                    .iter()
+                    .map(|variant| {
+                        let name = serde_attr(&variant.attrs, "rename").unwrap_or_else(|| {
+                            rename(&variant.ident.to_string(), rename_all.as_deref())
+                        });
+                        format!("{name:?}")
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" | ")
+            }
+            _ => "unknown".to_string(),
+        }
+    }
+
+    fn ty(&self, ty: &Type, stack: &mut Vec<String>) -> String {
+        match ty {
+            Type::Reference(r) => self.ty(&r.elem, stack),
+            Type::Paren(p) => self.ty(&p.elem, stack),
+            Type::Group(g) => self.ty(&g.elem, stack),
+            Type::Slice(s) => array(self.ty(&s.elem, stack)),
+            Type::Array(a) => array(self.ty(&a.elem, stack)),
+            Type::Tuple(t) if t.elems.is_empty() => "null".to_string(),
+            Type::Tuple(t) => format!(
+                "[{}]",
+                t.elems
+                    .iter()
+                    .map(|elem| self.ty(elem, stack))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Type::Path(p) if p.qself.is_none() => {
+                let Some(segment) = p.path.segments.last() else {
+                    return "unknown".to_string();
+                };
+                let args: Vec<&Type> = match &segment.arguments {
+                    PathArguments::AngleBracketed(args) => args
+                        .args
+                        .iter()
+                        .filter_map(|arg| match arg {
+                            GenericArgument::Type(ty) => Some(ty),
+                            _ => None,
+                        })
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                let ident = segment.ident.to_string();
+                match (ident.as_str(), args.as_slice()) {
+                    (
+                        "i8" | "i16" | "i32" | "i64" | "i128" | "isize" | "u8" | "u16" | "u32"
+                        | "u64" | "u128" | "usize" | "f32" | "f64",
+                        [],
+                    ) => "number".to_string(),
+                    ("String" | "str" | "char" | "PathBuf", []) => "string".to_string(),
+                    ("bool", []) => "boolean".to_string(),
+                    ("Value", []) => "unknown".to_string(),
+                    ("Option", [inner]) => format!("{} | null", self.ty(inner, stack)),
+                    ("Box" | "Rc" | "Arc" | "Cow", [.., inner]) => self.ty(inner, stack),
+                    ("Vec" | "VecDeque" | "HashSet" | "BTreeSet", [inner]) => {
+                        array(self.ty(inner, stack))
+                    }
+                    ("HashMap" | "BTreeMap" | "Map", [_, value]) => {
+                        format!("Record<string, {}>", self.ty(value, stack))
+                    }
+                    (name, []) if p.path.segments.len() == 1 => match self.items.get(name) {
+                        Some(item) if !stack.iter().any(|n| n == name) => {
+                            stack.push(name.to_string());
+                            let ty = self.item(item, stack);
+                            stack.pop();
+                            ty
+                        }
+                        _ => "unknown".to_string(),
+                    },
+                    _ => "unknown".to_string(),
+                }
+            }
+            _ => "unknown".to_string(),
+        }
+    }
+}
+
+/// 数组类型，联合类型需要加括号
+fn array(inner: String) -> String {
+    if inner.contains(' ') && !inner.starts_with('{') {
+        format!("({inner})[]")
+    } else {
+        format!("{inner}[]")
+    }
+}
+
+/// 属性名不是合法标识符时加引号
+fn property(name: &str) -> String {
+    let valid = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+    if valid {
+        name.to_string()
+    } else {
+        format!("{name:?}")
+    }
+}
+
+/// 读取`#[serde(key = "value")]`
+fn serde_attr(attrs: &[Attribute], key: &str) -> Option<String> {
+    let mut value = None;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+        let _ = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident(key) {
+                if let Ok(v) = meta.value() {
+                    value = Some(v.parse::<LitStr>()?.value());
+                } else {
+                    value = Some(String::new());
+                }
+            } else if meta.input.peek(syn::Token![=]) {
+                // 跳过其他`key = value`形式的参数
+                meta.value()?.parse::<syn::Expr>()?;
+            }
+            Ok(())
+        });
+    }
+    value
+}
+
+/// 是否有`#[serde(flag)]`
+fn has_serde_flag(attrs: &[Attribute], flag: &str) -> bool {
+    serde_attr(attrs, flag).is_some()
+}
+
+/// 按serde的`rename_all`规则转换名称
+fn rename(name: &str, rule: Option<&str>) -> String {
+    let words = || -> Vec<String> {
+        // 字段名为snake_case，变体名为PascalCase
+        let mut words = Vec::new();
+        let mut word = String::new();
+        for c in name.chars() {
+            if c == '_' {
+                words.push(std::mem::take(&mut word));
+            } else if c.is_uppercase() && !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+                word.push(c);
+            } else {
+                word.push(c);
+            }
+        }
+        words.push(word);
+        words
+            .into_iter()
+            .filter(|w| !w.is_empty())
+            .map(|w| w.to_lowercase())
+            .collect()
+    };
+    let capitalize = |w: &String| -> String {
+        let mut chars = w.chars();
+        chars
+            .next()
+            .map(|c| c.to_uppercase().chain(chars).collect())
+            .unwrap_or_default()
+    };
+    match rule {
+        Some("lowercase") => name.to_lowercase(),
+        Some("UPPERCASE") => name.to_uppercase(),
+        Some("camelCase") => {
+            let words = words();
+            let mut out = words.first().cloned().unwrap_or_default();
+            out.extend(words.iter().skip(1).map(capitalize));
+            out
+        }
+        Some("PascalCase") => words().iter().map(capitalize).collect(),
+        Some("snake_case") => words().join("_"),
+        Some("SCREAMING_SNAKE_CASE") => words().join("_").to_uppercase(),
+        Some("kebab-case") => words().join("-"),
+        Some("SCREAMING-KEBAB-CASE") => words().join("-").to_uppercase(),
+        _ => name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn declare(source: &str, name: &str) -> String {
+        let file = syn::parse_file(source).unwrap();
+        TypeScope::new(&file).declare(name).unwrap()
+    }
+
+    #[test]
+    fn map_struct_fields() {
+        let source = r#"
+            #[derive(Serialize, Props)]
+            #[serde(rename_all = "camelCase")]
+            struct UserProps {
+                user_id: u64,
+                display_name: Option<String>,
+                tags: Vec<&'static str>,
+                scores: HashMap<String, f32>,
+                #[serde(rename = "data-role")]
+                role: Role,
+                #[serde(skip)]
+                secret: String,
+                #[serde(skip_serializing_if = "Option::is_none")]
+                avatar: Option<Box<Avatar>>,
+                extra: serde_json::Value,
+                external: crate::api::user::UserInfo,
+            }
+            #[derive(Serialize)]
+            #[serde(rename_all = "lowercase")]
+            enum Role { Admin, Guest }
+            #[derive(Serialize)]
+            struct Avatar { url: String, size: (u32, u32) }
+        "#;
+        assert_eq!(
+            declare(source, "UserProps"),
+            "{ userId: number; displayName: string | null; tags: string[]; \
+             scores: Record<string, number>; \"data-role\": \"admin\" | \"guest\"; \
+             avatar?: { url: string; size: [number, number] } | null; extra: unknown; \
+             external: unknown }"
+        );
+    }
+
+    #[test]
+    fn stop_recursive_types() {
+        let source = "struct Node { children: Vec<Node> }";
+        assert_eq!(declare(source, "Node"), "{ children: unknown[] }");
+        assert_eq!(
+            declare("struct Ids(Vec<Option<u8>>);", "Ids"),
+            "(number | null)[]"
+        );
+    }
+
+    #[test]
+    fn rename_rules() {
+        assert_eq!(rename("user_id", Some("PascalCase")), "UserId");
+        assert_eq!(rename("user_id", Some("kebab-case")), "user-id");
+        assert_eq!(rename("NotFound", Some("snake_case")), "not_found");
+        assert_eq!(
+            rename("NotFound", Some("SCREAMING_SNAKE_CASE")),
+            "NOT_FOUND"
+        );
+        assert_eq!(rename("user_id", None), "user_id");
+    }
+}
@@ -0,0 +1,166 @@
+use rsx::build::{self, GENERATED_HEADER, TYPESCRIPT_FILE};
+use rsx::config::Config;
+use std::fs;
+use std::path::Path;
+
+fn write(root: &Path, path: &str, content: &str) {
+    let path = root.join(path);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+}
+
+fn config(root: &Path) -> Config {
+    Config {
+        root: Some(root.to_string_lossy().to_string()),
+        ..Config::default()
+    }
+}
+
+#[test]
+fn test_generate_page_modules() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    write(
+        root,
+        "src/pages/users/[id].rsx",
+        "---\nuse rsx::{Request, Response};\n\npub async fn get_server_props(req: Request) -> Response {\n    todo!()\n}\n\n#[derive(Serialize, rsx::Props)]\npub struct UserProps {\n    id: u64,\n    tags: Vec<String>,\n}\n\npub async fn get_static_paths() -> Vec<serde_json::Value> {\n    vec![]\n}\n---\n<template><p></p></template>",
+    );
+    write(
+        root,
+        "src/pages/about.rsx",
+        "---\nconst TITLE: &str = \"about\";\npub const REVALIDATE: u64 = 60;\n---\n<template><p></p></template>",
+    );
+    write(root, "src/pages/plain.rsx", "<template><p></p></template>");
+    write(root, "generated/page_removed.rs", GENERATED_HEADER);
+    write(root, "generated/handwritten.rs", "// keep me");
+
+    let modules = build::generate(&config(root)).unwrap();
+    let names: Vec<_> = modules.iter().map(|m| m.name.as_str()).collect();
+    assert_eq!(names, vec!["about", "users/[id]"]);
+    assert!(!modules[0].has_props);
+    assert!(modules[1].has_props);
+    assert!(modules[1].has_static_paths);
+    assert!(modules[0].has_revalidate);
+    assert!(!modules[1].has_revalidate);
+
+    let page = fs::read_to_string(root.join("generated/page_users__id_.rs")).unwrap();
+    assert_eq!(
+        page.lines().nth(3),
+        Some("pub async fn get_server_props(req: Request) -> Response {")
+    );
+
+    let registry = fs::read_to_string(root.join("generated/mod.rs")).unwrap();
+    assert!(registry.contains("pub mod page_about;"));
+    assert!(
+        registry.contains("props.register(\"users/[id]\", page_users__id_::get_server_props);")
+    );
+    assert!(!registry.contains("page_about::get_server_props"));
+    assert!(registry.contains(
+        "props.register_static_paths(\"users/[id]\", page_users__id_::get_static_paths);"
+    ));
+
+    assert!(registry.contains(
+        "props.register_revalidate(\"about\", ::std::time::Duration::from_secs(page_about::REVALIDATE));"
+    ));
+
+    let types = fs::read_to_string(root.join("generated").join(TYPESCRIPT_FILE)).unwrap();
+    assert!(
+        types.contains("export type UsersIdProps = { id: number; tags: string[] };"),
+        "{types}"
+    );
+    assert!(!types.contains("AboutProps"));
+
+    assert!(!root.join("generated/page_removed.rs").exists());
+    assert!(root.join("generated/handwritten.rs").exists());
+}
+
+#[test]
+fn test_frontmatter_errors_point_to_rsx_lines() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    write(
+        root,
+        "src/pages/broken.rsx",
+        "---\nuse rsx::Request;\n\ntrait Broken {\n    fn name() -> String\n}\n---\n<template></template>",
+    );
+    let err = build::generate(&config(root)).unwrap_err().to_string();
+    assert!(err.contains("broken.rsx:6:1: "), "{err}");
+    assert!(err.contains("expected curly braces or `;`"), "{err}");
+}
+
+#[test]
+fn test_generate_component_modules() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    write(
+        root,
+        "src/pages/index.rsx",
+        "<script>\n    import Header from '../components/header.rsx';\n    import App from '../react/app.tsx';\n</script>\n<template><Header></Header></template>",
+    );
+    write(
+        root,
+        "src/components/header.rsx",
+        "---\nuse rsx::{Request, Response};\n\npub async fn get_server_props(req: Request) -> Response {\n    todo!()\n}\n---\n<script>\n    import Logo from './logo.rsx';\n</script>\n<template><Logo></Logo></template>",
+    );
+    write(
+        root,
+        "src/pages/_layout.rsx",
+        "---\nuse rsx::{Request, Response};\n\npub async fn get_server_props(req: Request) -> Response {\n    todo!()\n}\n---\n<template><main><slot></slot></main></template>",
+    );
+    write(
+        root,
+        "src/components/logo.rsx",
+        "---\nconst SIZE: u32 = 24;\n---\n<template><img></template>",
+    );
+
+    let modules = build::generate(&config(root)).unwrap();
+    let names: Vec<_> = modules
+        .iter()
+        .map(|m| (m.name.as_str(), m.component))
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            ("src_pages__layout", true),
+            ("src_components_header", true),
+            ("src_components_logo", true)
+        ]
+    );
+    let registry = fs::read_to_string(root.join("generated/mod.rs")).unwrap();
+    assert!(registry.contains("pub mod component_src_components_logo;"));
+    assert!(registry.contains(
+        "props.register_component(\"src_pages__layout\", component_src_pages__layout::get_server_props);"
+    ));
+    assert!(registry.contains(
+        "props.register_component(\"src_components_header\", component_src_components_header::get_server_props);"
+    ));
+}
+
+#[test]
+fn test_generate_error_page_modules() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    write(root, "src/pages/index.rsx", "<template><p></p></template>");
+    write(
+        root,
+        "src/pages/404.rsx",
+        "---\nuse rsx::{Request, Response};\n\npub async fn get_server_props(req: Request) -> Response {\n    todo!()\n}\n---\n<script>\n    import Search from '../components/search.rsx';\n</script>\n<template><Search></Search></template>",
+    );
+    write(
+        root,
+        "src/components/search.rsx",
+        "---\nuse rsx::{Request, Response};\n\npub async fn get_server_props(req: Request) -> Response {\n    todo!()\n}\n---\n<template><input></template>",
+    );
+
+    let modules = build::generate(&config(root)).unwrap();
+    let names: Vec<_> = modules
+        .iter()
+        .map(|m| (m.name.as_str(), m.component))
+        .collect();
+    assert_eq!(names, vec![("404", false), ("src_components_search", true)]);
+    let registry = fs::read_to_string(root.join("generated/mod.rs")).unwrap();
+    assert!(registry.contains("props.register(\"404\", page_404::get_server_props);"));
+    assert!(registry.contains(
+        "props.register_component(\"src_components_search\", component_src_components_search::get_server_props);"
+    ));
+}
@@ -0,0 +1,43 @@
+use actix_web::{HttpResponse, body};
+use rsx::Response;
+use serde_json::{Value, json};
+
+async fn ok<T>(value: T) -> Result<T, std::io::Error> {
+    Ok(value)
+}
+
+async fn fail(message: &str) -> Result<u8, std::io::Error> {
+    Err(std::io::Error::other(message.to_string()))
+}
+
+async fn parse(value: &str) -> Result<u32, std::num::ParseIntError> {
+    value.parse()
+}
+
+#[actix_rt::test]
+async fn test_response_json() {
+    let title = "列表";
+    let response: Response = rsx::response_json!({ "title": title, "list": [1, 2, 3] });
+    let response = HttpResponse::from(response);
+    assert_eq!(
+        response.headers().get("content-type").unwrap(),
+        "application/json"
+    );
+    let bytes = body::to_bytes(response.into_body()).await.unwrap();
+    let value: Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(value, json!({ "title": "列表", "list": [1, 2, 3] }));
+}
+
+#[actix_rt::test]
+async fn test_request_all() {
+    let (a, b) = rsx::request_all!(ok(1), ok("two")).await.unwrap();
+    assert_eq!((a, b), (1, "two"));
+
+    let err = rsx::request_all!(ok(1), fail("boom"), parse("3"))
+        .await
+        .unwrap_err();
+    assert_eq!(err.to_string(), "boom");
+
+    let err = rsx::request_all!(ok(1), parse("x")).await.unwrap_err();
+    assert!(err.downcast_ref::<std::num::ParseIntError>().is_some());
+}
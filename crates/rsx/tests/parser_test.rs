@@ -0,0 +1,50 @@
+use rsx::parser::{self, BlockKind};
+use std::path::PathBuf;
+
+fn app_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../app/src")
+}
+
+#[test]
+fn test_parse_app_pages() {
+    for entry in walkdir::WalkDir::new(app_dir().join("pages")) {
+        let entry = entry.unwrap();
+        if entry.path().extension().is_none_or(|ext| ext != "rsx") {
+            continue;
+        }
+        let file = parser::parse_file(entry.path()).unwrap();
+        assert!(file.template.is_some(), "{:?}", entry.path());
+        assert!(file.script.is_some(), "{:?}", entry.path());
+    }
+}
+
+#[test]
+fn test_parse_app_components() {
+    let file = parser::parse_file(app_dir().join("components/skeleton.rsx")).unwrap();
+    assert!(file.frontmatter.is_none());
+    assert!(file.block(BlockKind::Template).is_some());
+    assert!(file.block(BlockKind::Script).is_some());
+    assert!(file.block(BlockKind::Style).is_some());
+}
+
+#[test]
+fn test_parse_page_frontmatter_lines() {
+    let file = parser::parse_file(app_dir().join("pages/each.rsx")).unwrap();
+    let frontmatter = file.frontmatter.as_ref().unwrap();
+    assert_eq!(frontmatter.span.start.line, 2);
+    assert!(frontmatter.content.contains("get_server_props"));
+    assert_eq!(
+        file.import("Meta").unwrap().source,
+        "../components/meta.rsx"
+    );
+}
+
+#[test]
+fn test_parse_file_error_has_path() {
+    let dir = std::env::temp_dir().join("rsx_parser_test");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("broken.rsx");
+    std::fs::write(&path, "<template>\n<div></div>\n").unwrap();
+    let err = parser::parse_file(&path).unwrap_err();
+    assert!(err.to_string().contains("broken.rsx:1:1"));
+}
@@ -0,0 +1,443 @@
+use actix_web::{App, http::header, test as actix_test};
+use rsx::config::Config;
+use rsx::props::PropsRegistry;
+use rsx::router::Router;
+use rsx::routes::RouteTable;
+use rsx::{Context, Props, Request, Response, ServerResponse, StatusCode, json};
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+fn app_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../app")
+}
+
+fn router() -> Router {
+    Router::from_dir(app_dir().join("src/pages"), app_dir()).unwrap()
+}
+
+#[test]
+fn test_route_table() {
+    let router = router();
+    let paths: Vec<&str> = router.routes().iter().map(|r| r.path.as_str()).collect();
+    assert_eq!(
+        paths,
+        vec![
+            "/",
+            "/csr",
+            "/each",
+            "/if",
+            "/raw",
+            "/ssr",
+            "/syntax",
+            "/users/[id]"
+        ]
+    );
+    let index = router.routes().iter().find(|r| r.name == "index").unwrap();
+    assert!(index.file.ends_with("pages/index.rsx"));
+}
+
+#[test]
+fn test_reload_with_added_and_removed_pages() {
+    let dir = tempfile::tempdir().unwrap();
+    let pages = dir.path().join("pages");
+    std::fs::create_dir_all(&pages).unwrap();
+    std::fs::write(pages.join("index.rsx"), "<template><p>1</p></template>").unwrap();
+    std::fs::write(pages.join("old.rsx"), "<template><p>old</p></template>").unwrap();
+    let router = Router::from_dir(&pages, dir.path()).unwrap();
+    assert_eq!(router.route_changes().unwrap(), (vec![], vec![]));
+
+    std::fs::remove_file(pages.join("old.rsx")).unwrap();
+    std::fs::write(pages.join("new.rsx"), "<template><p>new</p></template>").unwrap();
+    std::fs::write(pages.join("404.rsx"), "<template><p>404</p></template>").unwrap();
+    std::fs::write(pages.join("index.rsx"), "<template><p>2</p></template>").unwrap();
+    // 新增和删除的页面在重启后生效，其他页面照常重新编译
+    let changed = ["old.rsx", "new.rsx", "404.rsx", "index.rsx"].map(|file| pages.join(file));
+    assert_eq!(router.reload(&changed).unwrap(), ["index"]);
+    assert_eq!(
+        router.route_changes().unwrap(),
+        (
+            vec!["/new".to_string(), "404".to_string()],
+            vec!["/old".to_string()]
+        )
+    );
+    let html = router.engine().render("index", &json!({})).unwrap();
+    assert_eq!(html, "<p>2</p>");
+}
+
+#[test]
+fn test_route_table_with_api() {
+    let config = Config {
+        root: Some(app_dir().to_string_lossy().to_string()),
+        ..Config::default()
+    };
+    let table = RouteTable::scan(&config).unwrap();
+    assert_eq!(table.pages.len(), router().routes().len());
+    let user = table
+        .pages
+        .iter()
+        .find(|r| r.path == "/users/[id]")
+        .unwrap();
+    assert_eq!(user.file, PathBuf::from("src/pages/users/[id].rsx"));
+    let json = table
+        .api
+        .iter()
+        .find(|r| r.method == "POST" && r.path == "/api/json")
+        .unwrap();
+    assert_eq!(json.file, PathBuf::from("src/api/json.rs"));
+    assert!(table.to_string().contains("DELETE  /api/auth/check_login"));
+}
+
+#[actix_rt::test]
+async fn test_serve_pages() {
+    let app = actix_test::init_service(App::new().service(router().scope())).await;
+    for uri in ["/each", "/each.html", "/", "/index.html"] {
+        let req = actix_test::TestRequest::get().uri(uri).to_request();
+        let res = actix_test::call_service(&app, req).await;
+        assert!(res.status().is_success(), "{uri}");
+        assert_eq!(
+            res.headers().get("content-type").unwrap(),
+            "text/html; charset=utf-8"
+        );
+    }
+
+    let req = actix_test::TestRequest::get().uri("/missing").to_request();
+    let res = actix_test::call_service(&app, req).await;
+    assert_eq!(res.status(), 404);
+}
+
+/// 与`users/[id].rsx`的frontmatter一致的props函数
+fn props() -> PropsRegistry {
+    let mut props = PropsRegistry::new();
+    props.register("users/[id]", |req: Request| async move {
+        let Ok(id) = req.param::<u64>("id") else {
+            return Response::from(ServerResponse::new(StatusCode::NOT_FOUND));
+        };
+        ServerResponse::json(json!({ "id": id, "title": "用户详情" }))
+            .unwrap()
+            .header(
+                header::CACHE_CONTROL,
+                header::HeaderValue::from_static("no-store"),
+            )
+            .into()
+    });
+    props.register("each", |_req: Request| async {
+        ServerResponse::json(json!({ "data": ["a", "b"], "title": "列表渲染" }))
+    });
+    props
+}
+
+#[actix_rt::test]
+async fn test_render_server_props() {
+    let router = router().with_props(props());
+    let app = actix_test::init_service(App::new().service(router.scope())).await;
+
+    let req = actix_test::TestRequest::get().uri("/each").to_request();
+    let body = actix_test::call_and_read_body(&app, req).await;
+    let html = String::from_utf8(body.to_vec()).unwrap();
+    assert!(html.contains("<title>列表渲染</title>"), "{html}");
+    assert!(html.contains("b"), "{html}");
+
+    let req = actix_test::TestRequest::get().uri("/users/7").to_request();
+    let res = actix_test::call_service(&app, req).await;
+    assert_eq!(
+        res.headers().get(header::CACHE_CONTROL).unwrap(),
+        "no-store"
+    );
+    assert_eq!(
+        res.headers().get(header::CONTENT_TYPE).unwrap(),
+        "text/html; charset=utf-8"
+    );
+
+    // 非2xx的props响应跳过渲染
+    let req = actix_test::TestRequest::get()
+        .uri("/users/abc")
+        .to_request();
+    let res = actix_test::call_service(&app, req).await;
+    assert_eq!(res.status(), 404);
+}
+
+#[derive(Serialize, Props)]
+struct UserProps {
+    id: u64,
+    title: &'static str,
+}
+
+#[actix_rt::test]
+async fn test_render_typed_props_with_hydration() {
+    let mut props = PropsRegistry::new();
+    props.register("users/[id]", |_req: Request, ctx: Context| async move {
+        anyhow::Ok(UserProps {
+            id: ctx.param("id")?,
+            title: "<用户>",
+        })
+    });
+    let app =
+        actix_test::init_service(App::new().service(router().with_props(props).scope())).await;
+
+    let req = actix_test::TestRequest::get().uri("/users/42").to_request();
+    let body = actix_test::call_and_read_body(&app, req).await;
+    let html = String::from_utf8(body.to_vec()).unwrap();
+    assert!(html.contains("<h1>用户 42</h1>"), "{html}");
+    assert!(
+        html.contains(r#"<script id="__rsx_script__" type="application/json">{"id":42,"params":{"id":"42"},"title":"\u003c用户\u003e"}</script></body>"#),
+        "{html}"
+    );
+
+    // 路由参数解析失败返回404
+    let req = actix_test::TestRequest::get()
+        .uri("/users/abc")
+        .to_request();
+    let res = actix_test::call_service(&app, req).await;
+    assert_eq!(res.status(), 404);
+
+    // 没有props函数的页面不输出hydration数据
+    let req = actix_test::TestRequest::get().uri("/each").to_request();
+    let body = actix_test::call_and_read_body(&app, req).await;
+    assert!(
+        !String::from_utf8(body.to_vec())
+            .unwrap()
+            .contains("__rsx_script__")
+    );
+}
+
+#[actix_rt::test]
+async fn test_render_component_props() {
+    let mut props = PropsRegistry::new();
+    props.register("ssr", |_req: Request| async {
+        ServerResponse::json(json!({ "title": "服务端渲染" }))
+    });
+    props.register_component(
+        "src_components_meta",
+        |_req: Request, ctx: Context| async move {
+            if ctx.query("deny").is_some() {
+                return Response::from(ServerResponse::new(StatusCode::FORBIDDEN));
+            }
+            ServerResponse::json(json!({ "PUBLIC_PATH": "/static" }))
+                .unwrap()
+                .into()
+        },
+    );
+    let app =
+        actix_test::init_service(App::new().service(router().with_props(props).scope())).await;
+
+    let req = actix_test::TestRequest::get().uri("/ssr").to_request();
+    let body = actix_test::call_and_read_body(&app, req).await;
+    let html = String::from_utf8(body.to_vec()).unwrap();
+    assert!(html.contains(r#"href="/static/favicon.svg""#), "{html}");
+    assert!(html.contains("id=\"skeleton\""), "{html}");
+    // 组件props只在服务端使用，不进入hydration数据；页面props与岛props在同一个脚本中
+    assert_eq!(html.matches("id=\"__rsx_script__\"").count(), 1, "{html}");
+    assert!(!html.contains("__rsx_components"), "{html}");
+
+    // 组件props函数的非2xx响应直接返回
+    let req = actix_test::TestRequest::get()
+        .uri("/ssr?deny=1")
+        .to_request();
+    let res = actix_test::call_service(&app, req).await;
+    assert_eq!(res.status(), 403);
+}
+
+#[actix_rt::test]
+async fn test_serve_dynamic_pages() {
+    let app =
+        actix_test::init_service(App::new().service(router().with_props(props()).scope())).await;
+    for uri in ["/users/42", "/users/42.html"] {
+        let req = actix_test::TestRequest::get().uri(uri).to_request();
+        let body = actix_test::call_and_read_body(&app, req).await;
+        let html = String::from_utf8(body.to_vec()).unwrap();
+        assert!(html.contains("<h1>用户 42</h1>"), "{uri}: {html}");
+    }
+
+    let req = actix_test::TestRequest::get()
+        .uri("/users/42/posts")
+        .to_request();
+    let res = actix_test::call_service(&app, req).await;
+    assert_eq!(res.status(), 404);
+}
+
+#[actix_rt::test]
+async fn test_render_nested_layouts() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    let write = |path: &str, content: &str| {
+        let path = root.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    };
+    write(
+        "src/pages/_layout.rsx",
+        "<template><html><head><title>{title} - {site}</title></head><body><slot></slot></body></html></template>",
+    );
+    write(
+        "src/pages/admin/_layout.rsx",
+        "<template><main class=\"admin\"><slot></slot></main></template>",
+    );
+    write(
+        "src/pages/admin/index.rsx",
+        "<template><h1>{title}</h1></template>",
+    );
+    write(
+        "src/pages/plain.rsx",
+        "<template layout=\"none\"><p>plain</p></template>",
+    );
+    write(
+        "src/pages/login.rsx",
+        "<template layout=\"auth\"><form></form></template>",
+    );
+    write(
+        "src/layouts/auth.rsx",
+        "<template><div class=\"auth\"><slot></slot></div></template>",
+    );
+
+    let router = Router::from_dir(root.join("src/pages"), root).unwrap();
+    let paths: Vec<&str> = router.routes().iter().map(|r| r.path.as_str()).collect();
+    assert_eq!(paths, vec!["/admin", "/login", "/plain"]);
+
+    let mut props = PropsRegistry::new();
+    props.register("admin/index", |_req: Request| async {
+        ServerResponse::json(json!({ "title": "后台" }))
+    });
+    props.register_component("src_pages__layout", |_req: Request| async {
+        ServerResponse::json(json!({ "site": "rsx" }))
+    });
+    let app = actix_test::init_service(App::new().service(router.with_props(props).scope())).await;
+    let render = |uri: &'static str| {
+        let app = &app;
+        async move {
+            let req = actix_test::TestRequest::get().uri(uri).to_request();
+            let body = actix_test::call_and_read_body(app, req).await;
+            String::from_utf8(body.to_vec()).unwrap()
+        }
+    };
+
+    let html = render("/admin").await;
+    assert!(
+        html.starts_with(
+            "<!DOCTYPE html><html><head><title>后台 - rsx</title></head><body>\
+             <main class=\"admin\"><h1>后台</h1></main><script"
+        ),
+        "{html}"
+    );
+    assert_eq!(render("/plain").await, "<p>plain</p>");
+    assert_eq!(
+        render("/login").await,
+        "<div class=\"auth\"><form></form></div>"
+    );
+}
+
+#[actix_rt::test]
+async fn test_stream_deferred_regions() {
+    use actix_web::body::MessageBody;
+    use futures::channel::oneshot;
+    use std::sync::Mutex;
+
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    let write = |path: &str, content: &str| {
+        let path = root.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    };
+    write(
+        "src/components/skeleton.rsx",
+        "<template><div class=\"skeleton\"></div></template>",
+    );
+    let page = "<script>\n    import Skeleton from '../components/skeleton.rsx';\n</script>\n\
+         <template><html><head><title>{title}</title></head><body>\
+         <defer await=\"news\"><Skeleton slot=\"fallback\"></Skeleton><h1>{news}</h1></defer>\
+         <defer await=\"broken\"><p>{broken}</p></defer><defer await=\"panicked\"><p>{panicked}</p></defer>\
+         </body></html></template>";
+    write("src/pages/news.rsx", page);
+    write("src/pages/static.rsx", page);
+    write(
+        "src/pages/whole.rsx",
+        "<template><p>{title}:{news}</p></template>",
+    );
+
+    let (tx, rx) = oneshot::channel::<&'static str>();
+    let rx = Arc::new(Mutex::new(Some(rx)));
+    let mut props = PropsRegistry::new();
+    props.register("news", move |_req: Request, ctx: Context| {
+        let rx = rx.lock().unwrap().take().unwrap();
+        ctx.defer("news", async move { Ok(rx.await?) });
+        ctx.defer("broken", async {
+            Err::<String, _>(anyhow::anyhow!("boom"))
+        });
+        ctx.defer("panicked", async {
+            panic!("boom") as anyhow::Result<String>
+        });
+        async { ServerResponse::json(json!({ "title": "新闻" })) }
+    });
+    props.register("whole", |_req: Request, ctx: Context| {
+        ctx.defer("news", async { Ok("速递") });
+        async { ServerResponse::json(json!({ "title": "新闻" })) }
+    });
+    let router = Router::from_dir(root.join("src/pages"), root)
+        .unwrap()
+        .with_props(props);
+    let app = actix_test::init_service(App::new().service(router.scope())).await;
+
+    // 外壳用props函数返回的数据渲染，延迟数据就绪之前先发送
+    let req = actix_test::TestRequest::get().uri("/news").to_request();
+    let res = actix_test::call_service(&app, req).await;
+    assert_eq!(res.status(), 200);
+    let mut body = std::pin::pin!(res.into_body());
+    let mut next = async || {
+        let chunk = futures_util::future::poll_fn(|cx| body.as_mut().poll_next(cx)).await;
+        chunk.map(|chunk| String::from_utf8(chunk.unwrap().to_vec()).unwrap())
+    };
+    let shell = next().await.unwrap();
+    assert!(
+        shell.starts_with(
+            "<!DOCTYPE html><html><head><title>新闻</title></head><body>\
+             <div id=\"__rsx_defer_0\" style=\"display:contents\"><div class=\"skeleton\"></div></div>"
+        ),
+        "{shell}"
+    );
+    assert!(shell.ends_with("</script>"), "{shell}");
+
+    // 失败和panic的区块显示错误提示，不影响其他区块
+    let mut failed = String::new();
+    while !(failed.contains("__rsx_fill(1)") && failed.contains("__rsx_fill(2)")) {
+        failed.push_str(&next().await.unwrap());
+    }
+    assert_eq!(
+        failed
+            .matches("role=\"alert\">Failed to load</div>")
+            .count(),
+        2
+    );
+    assert!(!failed.contains("__rsx_fill(0)"), "{failed}");
+
+    tx.send("新闻速递").unwrap();
+    assert_eq!(
+        next().await.unwrap(),
+        "<template id=\"__rsx_fill_0\"><h1>新闻速递</h1></template><script>__rsx_fill(0)</script>"
+    );
+    let rest = next().await.unwrap();
+    assert!(
+        rest.ends_with(
+            "<script id=\"__rsx_script__\" type=\"application/json\">{\"news\":\"新闻速递\",\"params\":{},\"title\":\"新闻\"}</script></body></html>"
+        ),
+        "{rest}"
+    );
+    assert!(next().await.is_none());
+
+    // 没有props函数的页面直接渲染区块内容
+    let req = actix_test::TestRequest::get().uri("/static").to_request();
+    let body = actix_test::call_and_read_body(&app, req).await;
+    assert_eq!(
+        String::from_utf8(body.to_vec()).unwrap(),
+        "<!DOCTYPE html><html><head><title></title></head><body><h1></h1><p></p><p></p></body></html>"
+    );
+
+    // 没有<defer>区块的页面等待延迟数据后整体渲染
+    let req = actix_test::TestRequest::get().uri("/whole").to_request();
+    let body = actix_test::call_and_read_body(&app, req).await;
+    assert!(
+        String::from_utf8(body.to_vec())
+            .unwrap()
+            .starts_with("<p>新闻:速递</p>")
+    );
+}
@@ -0,0 +1,681 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::ServiceResponse;
+use actix_web::{App, HttpResponse, Scope, test as actix_test, web};
+use futures::StreamExt;
+use rsx::config::Config;
+use rsx::context::User;
+use rsx::dev::{DEV_WS_PATH, DevMessage, DevServer};
+use rsx::error::ERROR_ID_HEADER;
+use rsx::isr::{CACHE_HEADER, Isr};
+use rsx::props::PropsRegistry;
+use rsx::router::Router;
+use rsx::server::RsxServer;
+use rsx::{Context, Request, ServerResponse, json};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+
+fn config() -> Config {
+    let app_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../app");
+    Config {
+        root: Some(app_dir.to_string_lossy().to_string()),
+        ..Config::default()
+    }
+}
+
+fn api_scope() -> Scope {
+    web::scope("/api").route(
+        "/ping",
+        web::get().to(|| async { HttpResponse::Ok().body("pong") }),
+    )
+}
+
+#[actix_rt::test]
+async fn test_mount_pages_scopes_and_public() {
+    let server = RsxServer::new(config()).scope(api_scope);
+    let app = actix_test::init_service(App::new().configure(server.configure().unwrap())).await;
+
+    let req = actix_test::TestRequest::get().uri("/api/ping").to_request();
+    let body = actix_test::call_and_read_body(&app, req).await;
+    assert_eq!(body, "pong");
+
+    for uri in ["/", "/each", "/users/1", "/logo.svg"] {
+        let req = actix_test::TestRequest::get().uri(uri).to_request();
+        let res = actix_test::call_service(&app, req).await;
+        assert!(res.status().is_success(), "{uri}");
+    }
+
+    let req = actix_test::TestRequest::get().uri("/missing").to_request();
+    let res = actix_test::call_service(&app, req).await;
+    assert_eq!(res.status(), 404);
+}
+
+#[actix_rt::test]
+async fn test_inject_context_user_and_state() {
+    let mut props = PropsRegistry::new();
+    props.register("users/[id]", |_req: Request, ctx: Context| async move {
+        let user_id = ctx.get_user_id()?.to_string();
+        let site = ctx.state::<String>()?.to_string();
+        ServerResponse::json(
+            json!({ "title": format!("{site}:{user_id}:{}", ctx.param::<u64>("id")?) }),
+        )
+        .map_err(anyhow::Error::from)
+    });
+    let server = RsxServer::new(config())
+        .props(props)
+        .state(web::Data::new("rsx".to_string()))
+        .authenticate(|req| {
+            req.cookie("token")
+                .map(|token| User::new(token.value().to_string()))
+        });
+    let app = actix_test::init_service(App::new().configure(server.configure().unwrap())).await;
+
+    let req = actix_test::TestRequest::get().uri("/users/42").to_request();
+    let res = actix_test::call_service(&app, req).await;
+    assert_eq!(res.status(), 401);
+
+    let req = actix_test::TestRequest::get()
+        .uri("/users/42")
+        .insert_header(("cookie", "token=7"))
+        .to_request();
+    let body = actix_test::call_and_read_body(&app, req).await;
+    let body = String::from_utf8(body.to_vec()).unwrap();
+    assert!(body.contains("rsx:7:42"), "{body}");
+}
+
+#[test]
+fn test_missing_pages_dir() {
+    let config = Config {
+        root: Some("/nonexistent".to_string()),
+        ..Config::default()
+    };
+    assert!(RsxServer::new(config).configure().is_err());
+}
+
+#[actix_rt::test]
+async fn test_graceful_shutdown_drains_requests_and_runs_hooks() {
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let config = Config {
+        host: "127.0.0.1".to_string(),
+        port,
+        shutdown_timeout: 5,
+        ..config()
+    };
+    static STARTED: AtomicBool = AtomicBool::new(false);
+    let slow = || {
+        web::scope("/slow")
+            .route(
+                "",
+                web::get().to(|| async {
+                    STARTED.store(true, Ordering::SeqCst);
+                    actix_rt::time::sleep(Duration::from_millis(300)).await;
+                    HttpResponse::Ok().body("done")
+                }),
+            )
+            // 响应头先返回，响应体稍后发送完，如流式SSR
+            .route(
+                "/stream",
+                web::get().to(|| async {
+                    let chunks =
+                        futures::stream::iter(["shell", "-filled"]).then(|chunk| async move {
+                            if chunk != "shell" {
+                                actix_rt::time::sleep(Duration::from_millis(800)).await;
+                            }
+                            Ok::<_, actix_web::Error>(web::Bytes::from_static(chunk.as_bytes()))
+                        });
+                    HttpResponse::Ok().streaming(chunks)
+                }),
+            )
+    };
+    let flushed = Arc::new(AtomicBool::new(false));
+    let hook_flushed = flushed.clone();
+    let (stop, stopped) = futures::channel::oneshot::channel::<()>();
+    let server = actix_rt::spawn(
+        RsxServer::new(config)
+            .scope(slow)
+            .on_shutdown(move || async move { hook_flushed.store(true, Ordering::SeqCst) })
+            .run_until(async move {
+                let _ = stopped.await;
+            }),
+    );
+
+    let url = format!("http://127.0.0.1:{port}/slow");
+    let mut ready = false;
+    for _ in 0..50 {
+        if std::net::TcpStream::connect(("127.0.0.1", port)).is_ok() {
+            ready = true;
+            break;
+        }
+        actix_rt::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert!(ready, "server did not start");
+
+    let streaming = reqwest::get(format!("{url}/stream")).await.unwrap();
+    let request = actix_rt::spawn(async move { reqwest::get(url).await?.text().await });
+    while !STARTED.load(Ordering::SeqCst) {
+        actix_rt::time::sleep(Duration::from_millis(10)).await;
+    }
+    stop.send(()).unwrap();
+
+    assert_eq!(request.await.unwrap().unwrap(), "done");
+    assert_eq!(streaming.text().await.unwrap(), "shell-filled");
+    server.await.unwrap().unwrap();
+    assert!(flushed.load(Ordering::SeqCst));
+    assert!(std::net::TcpStream::connect(("127.0.0.1", port)).is_err());
+}
+
+#[actix_rt::test]
+async fn test_export_static_pages() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    let write = |path: &str, content: &str| {
+        let path = root.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    };
+    write("src/pages/index.rsx", "<template><h1>首页</h1></template>");
+    write("src/pages/about.rsx", "<template><p>{title}</p></template>");
+    write(
+        "src/pages/posts/[id].rsx",
+        "<template><article>{title}</article></template>",
+    );
+    write("src/pages/users/[id].rsx", "<template><p></p></template>");
+    write(
+        "src/pages/tags/[name].rsx",
+        "<template><p>{name}</p></template>",
+    );
+    write(
+        "src/pages/404.rsx",
+        "<template><h1>{message}</h1></template>",
+    );
+
+    let mut props = PropsRegistry::new();
+    props.register("about", |_req: Request| async {
+        ServerResponse::json(json!({ "title": "关于" }))
+    });
+    props.register("posts/[id]", |req: Request| async move {
+        let id: u64 = req.param("id")?;
+        if id == 3 {
+            return Ok(ServerResponse::new(rsx::StatusCode::NOT_FOUND));
+        }
+        Ok::<_, anyhow::Error>(ServerResponse::json(
+            json!({ "title": format!("文章{id}") }),
+        )?)
+    });
+    props.register_static_paths("posts/[id]", || async {
+        vec![json!({ "id": 1 }), json!({ "id": "2" }), json!({ "id": 3 })]
+    });
+    props.register("tags/[name]", |req: Request| async move {
+        let name: String = req.param("name")?;
+        Ok::<_, anyhow::Error>(ServerResponse::json(json!({ "name": name }))?)
+    });
+    props.register_static_paths("tags/[name]", || async {
+        vec![json!({ "name": "rust web" })]
+    });
+    let config = Config {
+        root: Some(root.to_string_lossy().to_string()),
+        ..Config::default()
+    };
+    let pages = RsxServer::new(config).props(props).export().await.unwrap();
+
+    let urls: Vec<&str> = pages.iter().map(|page| page.url.as_str()).collect();
+    assert_eq!(
+        urls,
+        ["/", "/about", "/posts/1", "/posts/2", "/tags/rust%20web"]
+    );
+    let dist = root.join("dist");
+    let read = |path: &str| std::fs::read_to_string(dist.join(path)).unwrap();
+    assert_eq!(read("index.html"), "<h1>首页</h1>");
+    assert!(read("about.html").starts_with("<p>关于</p>"));
+    assert_eq!(read("about/index.html"), read("about.html"));
+    assert!(read("posts/2/index.html").starts_with("<article>文章2</article>"));
+    // 参数编码后请求，解码后写入文件
+    assert!(read("tags/rust web.html").starts_with("<p>rust web</p>"));
+    // 404的参数和没有get_static_paths的动态路由不导出
+    assert!(!dist.join("posts/3.html").exists());
+    assert!(!dist.join("users").exists());
+    assert_eq!(read("404.html"), "<h1>Not Found</h1>");
+}
+
+#[actix_rt::test]
+async fn test_incremental_static_regeneration() {
+    let dir = tempfile::tempdir().unwrap();
+    let pages = dir.path().join("src/pages");
+    std::fs::create_dir_all(&pages).unwrap();
+    std::fs::write(
+        pages.join("news.rsx"),
+        "<template><p>{count}</p></template>",
+    )
+    .unwrap();
+
+    let renders = Arc::new(AtomicUsize::new(0));
+    let mut props = PropsRegistry::new();
+    let counter = renders.clone();
+    props.register("news", move |_req: Request| {
+        let count = counter.fetch_add(1, Ordering::SeqCst) + 1;
+        async move {
+            actix_rt::time::sleep(Duration::from_millis(20)).await;
+            ServerResponse::json(json!({ "count": count }))
+        }
+    });
+    props.register_revalidate("news", Duration::from_secs(60));
+    let config = Config {
+        root: Some(dir.path().to_string_lossy().to_string()),
+        ..Config::default()
+    };
+    let server = RsxServer::new(config).props(props).scope(|| {
+        web::scope("/api").route(
+            "/revalidate",
+            web::post().to(|isr: web::Data<Isr>| async move {
+                HttpResponse::Ok().body(isr.revalidate("/news").to_string())
+            }),
+        )
+    });
+    let app = actix_test::init_service(App::new().configure(server.configure().unwrap())).await;
+    let get = |uri: &'static str| {
+        let app = &app;
+        async move {
+            let req = actix_test::TestRequest::get().uri(uri).to_request();
+            let res = actix_test::call_service(app, req).await;
+            let state = res
+                .headers()
+                .get(CACHE_HEADER)
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .to_string();
+            let body = actix_test::read_body(res).await;
+            let body = String::from_utf8(body.to_vec()).unwrap();
+            (state, body[..body.find("</p>").unwrap() + 4].to_string())
+        }
+    };
+
+    // 并发的首次请求只渲染一次
+    let first = futures_util::future::join_all([get("/news"), get("/news.html")]).await;
+    assert_eq!(renders.load(Ordering::SeqCst), 1);
+    assert!(first.iter().all(|(_, body)| body == "<p>1</p>"));
+    assert_eq!(
+        get("/news").await,
+        ("HIT".to_string(), "<p>1</p>".to_string())
+    );
+
+    let req = actix_test::TestRequest::post()
+        .uri("/api/revalidate")
+        .to_request();
+    assert_eq!(actix_test::call_and_read_body(&app, req).await, "true");
+
+    // 过期后返回旧页面，并发请求只触发一次后台再生成
+    let stale = futures_util::future::join_all([get("/news"), get("/news")]).await;
+    assert!(
+        stale
+            .iter()
+            .all(|page| *page == ("STALE".to_string(), "<p>1</p>".to_string()))
+    );
+    actix_rt::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(renders.load(Ordering::SeqCst), 2);
+    assert_eq!(
+        get("/news").await,
+        ("HIT".to_string(), "<p>2</p>".to_string())
+    );
+}
+
+#[actix_rt::test]
+async fn test_bypass_page_cache_for_users() {
+    let dir = tempfile::tempdir().unwrap();
+    let pages = dir.path().join("src/pages");
+    std::fs::create_dir_all(&pages).unwrap();
+    std::fs::write(
+        pages.join("dashboard.rsx"),
+        "<template><p>{user}</p></template>",
+    )
+    .unwrap();
+
+    let mut props = PropsRegistry::new();
+    props.register("dashboard", |_req: Request, ctx: Context| async move {
+        let user = ctx.get_user_id().unwrap_or("guest").to_string();
+        ServerResponse::json(json!({ "user": user }))
+    });
+    props.register_revalidate("dashboard", Duration::from_secs(60));
+    let config = Config {
+        root: Some(dir.path().to_string_lossy().to_string()),
+        ..Config::default()
+    };
+    let server = RsxServer::new(config).props(props).authenticate(|req| {
+        req.headers()
+            .get("x-user")
+            .and_then(|user| user.to_str().ok())
+            .map(User::new)
+    });
+    let app = actix_test::init_service(App::new().configure(server.configure().unwrap())).await;
+    let get = |header: Option<(&'static str, &'static str)>| {
+        let app = &app;
+        async move {
+            let mut req = actix_test::TestRequest::get().uri("/dashboard");
+            if let Some(header) = header {
+                req = req.insert_header(header);
+            }
+            let res = actix_test::call_service(app, req.to_request()).await;
+            let state = res.headers().get(CACHE_HEADER).unwrap().to_str().unwrap();
+            let state = state.to_string();
+            let body = String::from_utf8(actix_test::read_body(res).await.to_vec()).unwrap();
+            (state, body[..body.find("</p>").unwrap() + 4].to_string())
+        }
+    };
+    let page = |state: &str, body: &str| (state.to_string(), body.to_string());
+
+    assert_eq!(get(None).await, page("MISS", "<p>guest</p>"));
+    // 不同用户的页面不读写缓存
+    assert_eq!(get(Some(("x-user", "1"))).await, page("BYPASS", "<p>1</p>"));
+    assert_eq!(get(Some(("x-user", "2"))).await, page("BYPASS", "<p>2</p>"));
+    assert_eq!(
+        get(Some(("authorization", "Bearer t"))).await,
+        page("BYPASS", "<p>guest</p>")
+    );
+    assert_eq!(
+        get(Some(("cookie", "session=1"))).await.0,
+        "BYPASS".to_string()
+    );
+    assert_eq!(get(None).await, page("HIT", "<p>guest</p>"));
+}
+
+#[actix_rt::test]
+async fn test_serve_vite_assets_under_base() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    let write = |path: &str, content: &str| {
+        let path = root.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    };
+    write(
+        "src/pages/index.rsx",
+        "<script>\n    import App from '../react/app.tsx';\n</script>\n\
+         <template><html><head></head><body><App client:load></App></body></html></template>",
+    );
+    write(
+        "dist/.vite/manifest.json",
+        r#"{"src/react/app.tsx": {"file": "assets/app-1a2b3c.js", "src": "src/react/app.tsx", "isEntry": true}}"#,
+    );
+    write("dist/assets/app-1a2b3c.js", "export function mount() {}");
+    let config = Config {
+        root: Some(root.to_string_lossy().to_string()),
+        base: "/app/".to_string(),
+        ..Config::default()
+    };
+    let configure = RsxServer::new(config).configure().unwrap();
+    let app = actix_test::init_service(App::new().configure(configure)).await;
+
+    let req = actix_test::TestRequest::get().uri("/").to_request();
+    let body = actix_test::call_and_read_body(&app, req).await;
+    let html = String::from_utf8(body.to_vec()).unwrap();
+    assert!(
+        html.contains("data-src=\"/app/assets/app-1a2b3c.js\""),
+        "{html}"
+    );
+    assert!(
+        html.contains("<link rel=\"modulepreload\" href=\"/app/assets/app-1a2b3c.js\">"),
+        "{html}"
+    );
+    let req = actix_test::TestRequest::get()
+        .uri("/app/assets/app-1a2b3c.js")
+        .to_request();
+    let res = actix_test::call_service(&app, req).await;
+    assert_eq!(res.status(), 200);
+}
+
+#[actix_rt::test]
+async fn test_dev_server_recompiles_changed_pages() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    let write = |path: &str, content: &str| {
+        let path = root.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, content).unwrap();
+        path
+    };
+    let card = write(
+        "src/components/card.rsx",
+        "<template><div class=\"card\"><slot></slot></div></template>",
+    );
+    let index = |css: &str| {
+        format!(
+            "<script>\n    import Card from '../components/card.rsx';\n</script>\n\
+             <template><Card>首页</Card></template><style global>{css}</style>"
+        )
+    };
+    let index_path = write("src/pages/index.rsx", &index("body { margin: 0; }"));
+    write("src/pages/about.rsx", "<template><p>关于</p></template>");
+    write("public/app.css", "body {}");
+    let config = Config {
+        root: Some(root.to_string_lossy().to_string()),
+        ..Config::default()
+    };
+    let router = Arc::new(Router::new(&config).unwrap().with_dev_client());
+    let dev = DevServer::new(&config, router.clone());
+    let app = actix_test::init_service(App::new().configure(|cfg| router.configure(cfg))).await;
+    let get = |uri: &'static str| {
+        let app = &app;
+        async move {
+            let req = actix_test::TestRequest::get().uri(uri).to_request();
+            let body = actix_test::call_and_read_body(app, req).await;
+            String::from_utf8(body.to_vec()).unwrap()
+        }
+    };
+    let body = get("/").await;
+    assert!(body.contains("<div class=\"card\">首页</div>"), "{body}");
+    assert!(body.contains("<script id=\"__rsx_dev__\" data-page=\"index\">"));
+
+    // 开发模式的服务器接受客户端脚本的WebSocket连接
+    let server = RsxServer::new(config.clone()).dev();
+    let dev_app = actix_test::init_service(App::new().configure(server.configure().unwrap())).await;
+    let req = actix_test::TestRequest::get()
+        .uri(DEV_WS_PATH)
+        .insert_header(("upgrade", "websocket"))
+        .insert_header(("connection", "upgrade"))
+        .insert_header(("sec-websocket-version", "13"))
+        .insert_header(("sec-websocket-key", "dGhlIHNhbXBsZSBub25jZQ=="))
+        .to_request();
+    let res = actix_test::call_service(&dev_app, req).await;
+    assert_eq!(res.status(), 101);
+
+    // 修改组件只重新编译用到它的页面，不需要重启
+    write(
+        "src/components/card.rsx",
+        "<template><section><slot></slot></section></template>",
+    );
+    assert_eq!(
+        dev.handle_changes(&[card]),
+        [DevMessage::Reload {
+            pages: Some(vec!["index".to_string()])
+        }]
+    );
+    assert!(get("/").await.contains("<section>首页</section>"));
+
+    // 模板不变、只修改全局样式时推送新样式
+    write("src/pages/index.rsx", &index("body { margin: 1px; }"));
+    assert_eq!(
+        dev.handle_changes(std::slice::from_ref(&index_path)),
+        [DevMessage::Style {
+            page: "index".to_string(),
+            css: "body { margin: 1px; }".to_string()
+        }]
+    );
+
+    // 编译失败时保留原来的页面
+    let about = write(
+        "src/pages/about.rsx",
+        "<script>\n    import Missing from './missing.rsx';\n</script>\n<template><Missing></Missing></template>",
+    );
+    assert!(dev.handle_changes(&[about]).is_empty());
+    assert!(get("/about").await.contains("<p>关于</p>"));
+
+    assert_eq!(
+        dev.handle_changes(&[root.join("public/app.css"), root.join("public/logo.svg")]),
+        [
+            DevMessage::Css {
+                path: "/app.css".to_string()
+            },
+            DevMessage::Reload { pages: None }
+        ]
+    );
+}
+
+/// 出错页面的错误ID和内容
+async fn error_page(res: ServiceResponse<impl MessageBody>) -> (String, String) {
+    assert_eq!(res.status(), 500);
+    let id = res.headers().get(ERROR_ID_HEADER).unwrap();
+    let id = id.to_str().unwrap().to_string();
+    let body = actix_test::read_body(res).await;
+    (id, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[actix_rt::test]
+async fn test_render_error_pages() {
+    let dir = tempfile::tempdir().unwrap();
+    let pages = dir.path().join("src/pages");
+    std::fs::create_dir_all(&pages).unwrap();
+    std::fs::write(
+        pages.join("broken.rsx"),
+        "---\nuse rsx::{Request, ServerResponse};\n\n\
+         pub async fn get_server_props(req: Request) -> anyhow::Result<ServerResponse> {\n    \
+         load(req).await\n}\n---\n<template><p>{title}</p></template>",
+    )
+    .unwrap();
+    // 变量与Handlebars内置的helper同名，渲染时缺少参数
+    std::fs::write(
+        pages.join("count.rsx"),
+        "<template>\n  <h1>统计</h1>\n  <p>{len}</p>\n</template>",
+    )
+    .unwrap();
+    let props = || {
+        let mut props = PropsRegistry::new();
+        props.register("broken", |_req: Request| async {
+            Err::<ServerResponse, _>(anyhow::anyhow!("db down").context("load post"))
+        });
+        props
+    };
+    let config = Config {
+        root: Some(dir.path().to_string_lossy().to_string()),
+        ..Config::default()
+    };
+    let req = || {
+        actix_test::TestRequest::get()
+            .uri("/broken?x=1")
+            .to_request()
+    };
+
+    // 生产模式只显示错误ID
+    let server = RsxServer::new(config.clone()).props(props());
+    let app = actix_test::init_service(App::new().configure(server.configure().unwrap())).await;
+    let (id, body) = error_page(actix_test::call_service(&app, req()).await).await;
+    assert!(body.contains(&id));
+    assert!(!body.contains("db down"));
+
+    // 开发模式显示错误链、出错的源码行和请求信息
+    let router = Router::new(&config)
+        .unwrap()
+        .with_dev_client()
+        .with_props(props());
+    let app = actix_test::init_service(App::new().configure(|cfg| router.configure(cfg))).await;
+    let (_, body) = error_page(actix_test::call_service(&app, req()).await).await;
+    assert!(body.contains("<h1>load post</h1>"), "{body}");
+    assert!(body.contains("<li>db down</li>"));
+    assert!(body.contains("<h2>src/pages/broken.rsx:4</h2>"));
+    assert!(body.contains("<td>GET</td><td>/broken?x&#x3D;1</td>"));
+    assert!(body.contains("data-page=\"broken\""));
+
+    // 模板渲染错误定位到出错的模板行
+    let req = actix_test::TestRequest::get().uri("/count").to_request();
+    let (_, body) = error_page(actix_test::call_service(&app, req).await).await;
+    assert!(body.contains("len param"), "{body}");
+    assert!(body.contains("<h2>src/pages/count.rsx:3</h2>"), "{body}");
+    assert!(body.contains("<span class=\"line hl\"><span class=\"no\">3</span>  &lt;p&gt;{len}"));
+}
+
+#[actix_rt::test]
+async fn test_custom_error_pages() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    let write = |path: &str, content: &str| {
+        let path = root.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    };
+    write("src/pages/index.rsx", "<template><h1>首页</h1></template>");
+    write("src/pages/boom.rsx", "<template><p></p></template>");
+    write("src/pages/posts/[id].rsx", "<template><p></p></template>");
+    write(
+        "src/pages/404.rsx",
+        "<template><h1>{status} {message}</h1></template>",
+    );
+    write(
+        "src/pages/_error.rsx",
+        "<template><p>{status}: {message} #{error_id}</p></template>",
+    );
+    write("public/robots.txt", "");
+
+    let mut props = PropsRegistry::new();
+    props.register("boom", |_req: Request| async {
+        if true {
+            panic!("boom");
+        }
+        ServerResponse::json(json!({}))
+    });
+    props.register("posts/[id]", |req: Request| async move {
+        let id: u64 = req.param("id")?;
+        ServerResponse::json(json!({ "id": id })).map_err(anyhow::Error::from)
+    });
+    let config = Config {
+        root: Some(root.to_string_lossy().to_string()),
+        ..Config::default()
+    };
+    let router = Router::new(&config).unwrap();
+    let paths: Vec<&str> = router.routes().iter().map(|r| r.path.as_str()).collect();
+    assert_eq!(paths, ["/", "/boom", "/posts/[id]"]);
+    assert_eq!(router.error_pages().collect::<Vec<_>>(), ["404", "_error"]);
+
+    let server = RsxServer::new(config).props(props);
+    let app = actix_test::init_service(App::new().configure(server.configure().unwrap())).await;
+    let get = |uri: &'static str| {
+        let app = &app;
+        async move {
+            let req = actix_test::TestRequest::get().uri(uri).to_request();
+            let res = actix_test::call_service(app, req).await;
+            let status = res.status().as_u16();
+            let id = res
+                .headers()
+                .get(ERROR_ID_HEADER)
+                .map(|id| id.to_str().unwrap().to_string());
+            let body = actix_test::read_body(res).await;
+            (status, String::from_utf8(body.to_vec()).unwrap(), id)
+        }
+    };
+
+    // 未匹配的路径和路由参数错误使用404页面
+    for uri in ["/missing", "/posts/abc"] {
+        let (status, body, _) = get(uri).await;
+        assert_eq!(status, 404, "{uri}");
+        assert_eq!(body, "<h1>404 Not Found</h1>", "{uri}");
+    }
+    assert_eq!(get("/robots.txt").await.0, 200);
+
+    // panic按500处理，没有500页面时使用_error页面
+    let (status, body, id) = get("/boom").await;
+    assert_eq!(status, 500);
+    assert_eq!(
+        body,
+        format!("<p>500: Internal Server Error #{}</p>", id.unwrap())
+    );
+
+    // 只注册页面路由时由默认服务处理
+    let app = actix_test::init_service(App::new().configure(|cfg| router.configure(cfg))).await;
+    let req = actix_test::TestRequest::get().uri("/a/b").to_request();
+    let res = actix_test::call_service(&app, req).await;
+    assert_eq!(res.status(), 404);
+    assert_eq!(actix_test::read_body(res).await, "<h1>404 Not Found</h1>");
+}
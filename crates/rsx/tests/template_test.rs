@@ -0,0 +1,331 @@
+use rsx::manifest::Manifest;
+use rsx::template::style::scope_attr;
+use rsx::template::{COMPONENTS_KEY, TemplateEngine};
+use serde_json::json;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn app_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../app")
+}
+
+fn engine() -> TemplateEngine {
+    let mut engine = TemplateEngine::new(app_dir());
+    for page in ["if", "each", "raw"] {
+        engine
+            .register_page(page, app_dir().join(format!("src/pages/{page}.rsx")))
+            .unwrap();
+    }
+    engine
+}
+
+#[test]
+fn test_render_if_page() {
+    let html = engine()
+        .render("if", &json!({"data": [1, 2, 3, 4], "title": "条件渲染"}))
+        .unwrap();
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains("<title>条件渲染</title>"));
+    // Meta组件和页面都声明了charset，只保留页面的
+    assert_eq!(html.matches("charset=").count(), 1, "{html}");
+    assert!(html.contains("<meta charset=\"UTF-8\">"));
+    assert_eq!(html.matches("class=\"even\"").count(), 2);
+    assert_eq!(html.matches("class=\"odd\"").count(), 2);
+    assert!(!html.contains(":key"));
+}
+
+#[test]
+fn test_render_each_page() {
+    let html = engine()
+        .render(
+            "each",
+            &json!({"data": ["a", "b", "c"], "title": "列表渲染"}),
+        )
+        .unwrap();
+    assert_eq!(html.matches("<li data-rsx-").count(), 3);
+    assert!(html.contains("c\n"));
+}
+
+#[test]
+fn test_render_raw_page() {
+    let html = engine()
+        .render(
+            "raw",
+            &json!({"data": "<div>hello html</div>", "title": "html渲染"}),
+        )
+        .unwrap();
+    assert!(html.contains("<div>hello html</div>"));
+}
+
+#[test]
+fn test_components_registered_as_partials() {
+    let engine = engine();
+    assert!(
+        engine
+            .handlebars()
+            .get_template("src_components_meta")
+            .is_some()
+    );
+}
+
+fn write(root: &Path, path: &str, content: &str) -> PathBuf {
+    let path = root.join(path);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(&path, content).unwrap();
+    path
+}
+
+#[test]
+fn test_render_components_with_slots() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    write(
+        root,
+        "components/card.rsx",
+        "<template><section title={title}><h2><slot name=\"header\">默认标题</slot></h2><slot></slot><p>{count}</p></section></template>",
+    );
+    let page = write(
+        root,
+        "pages/index.rsx",
+        "<script>\n    import Card from '../components/card.rsx';\n</script>\n<template><Card title={heading}><template slot=\"header\">{heading}</template><b>{body}</b></Card><Card title=\"空\"></Card></template>",
+    );
+    let mut engine = TemplateEngine::new(root);
+    engine.register_page("index", &page).unwrap();
+    assert_eq!(engine.page_components("index"), ["components_card"]);
+
+    let html = engine
+        .render(
+            "index",
+            &json!({
+                "heading": "新闻",
+                "body": "正文",
+                COMPONENTS_KEY: { "components_card": { "count": 3, "title": "被属性覆盖" } },
+            }),
+        )
+        .unwrap();
+    assert_eq!(
+        html,
+        "<section title=\"新闻\"><h2>新闻</h2><b>正文</b><p>3</p></section>\
+         <section title=\"空\"><h2>默认标题</h2><p>3</p></section>"
+    );
+}
+
+#[test]
+fn test_render_app_skeleton_children() {
+    let mut engine = TemplateEngine::new(app_dir());
+    engine
+        .register_page("ssr", app_dir().join("src/pages/ssr.rsx"))
+        .unwrap();
+    assert!(
+        engine
+            .page_components("ssr")
+            .contains(&"src_components_skeleton".to_string())
+    );
+    let html = engine.render("ssr", &json!({"title": "ssr"})).unwrap();
+    assert!(html.contains("id=\"skeleton\""), "{html}");
+}
+
+#[test]
+fn test_render_scoped_styles() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    let card_css = ".card { color: red; }";
+    write(
+        root,
+        "components/card.rsx",
+        &format!(
+            "<template><div class=\"card\"><slot></slot></div></template><style scoped>{card_css}</style>"
+        ),
+    );
+    let page_css = ".card :global(b) { margin: 0; }\n:global(body) { margin: 0; }";
+    let page = write(
+        root,
+        "pages/index.rsx",
+        &format!(
+            "<script>\n    import Card from '../components/card.rsx';\n</script>\n\
+             <template><html><head><title>t</title></head><body><Card><b>1</b></Card><Card><b>2</b></Card></body></html></template>\n\
+             <style scoped>{page_css}</style>"
+        ),
+    );
+    let card = scope_attr(card_css);
+    let scope = scope_attr(page_css);
+    let mut engine = TemplateEngine::new(root);
+    engine.register_page("index", &page).unwrap();
+    let html = engine.render("index", &json!({})).unwrap();
+    assert!(
+        html.contains(&format!(
+            "<style id=\"__rsx_style__\">.card[{card}] {{ color: red; }}\n.card[{scope}] b {{ margin: 0; }}\nbody {{ margin: 0; }}</style></head>"
+        )),
+        "{html}"
+    );
+    // 插槽内容使用页面的作用域，样式只输出一次
+    assert!(
+        html.contains(&format!(
+            "<body {scope}><div {card} class=\"card\"><b {scope}>1</b></div><div {card} class=\"card\"><b {scope}>2</b></div></body>"
+        )),
+        "{html}"
+    );
+    assert!(html.contains("<head><title>t</title>"), "{html}");
+}
+
+#[test]
+fn test_render_styles_without_head() {
+    let mut engine = TemplateEngine::new(app_dir());
+    engine
+        .register_page("syntax", app_dir().join("src/pages/syntax.rsx"))
+        .unwrap();
+    let html = engine
+        .render("syntax", &json!({"title": "t", "url": "/syntax"}))
+        .unwrap();
+    // 没有scoped属性的样式为全局样式，页面没有<head>和<body>时插入到最前面
+    assert!(html.starts_with("<style id=\"__rsx_style__\">"), "{html}");
+    assert!(!html.contains("data-rsx-"), "{html}");
+}
+
+#[test]
+fn test_render_page_head_without_html() {
+    let mut engine = TemplateEngine::new(app_dir());
+    engine
+        .register_page("csr", app_dir().join("src/pages/csr.rsx"))
+        .unwrap();
+    let html = engine.render("csr", &json!({})).unwrap();
+    let head = &html[html.find("<head>").expect(&html)..html.find("</head>").expect(&html)];
+    assert!(head.contains("<title>客户端渲染</title>"), "{html}");
+    assert!(head.contains("<meta charset=\"UTF-8\">"), "{html}");
+    assert!(head.contains("name=\"viewport\""), "{html}");
+    assert!(head.contains("href=\"/logo.svg\""), "{html}");
+    assert!(head.contains("<style id=\"__rsx_style__\">"), "{html}");
+    assert!(html.contains("data-component=\"CsrApp\""), "{html}");
+}
+
+#[test]
+fn test_render_deduplicated_head() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    write(
+        root,
+        "components/seo.rsx",
+        "<template><head><title>{title}</title><meta name=\"description\" content={title} /></head><h1>{title}</h1></template>",
+    );
+    write(
+        root,
+        "components/meta.rsx",
+        "<template><meta charset=\"utf-8\" /><meta name=\"description\" content=\"默认\" /><script key=\"analytics\" src=\"/a.js\"></script></template>",
+    );
+    let page = write(
+        root,
+        "pages/index.rsx",
+        "<script>\n    import Meta from '../components/meta.rsx';\n    import Seo from '../components/seo.rsx';\n</script>\n\
+         <template><html><head><Meta /><title>站点</title><script key=\"analytics\" src=\"/b.js\"></script></head>\n\
+         <body><Seo title={heading} /></body></html></template>",
+    );
+    let mut engine = TemplateEngine::new(root);
+    engine.register_page("index", &page).unwrap();
+    let html = engine.render("index", &json!({"heading": "新闻"})).unwrap();
+    // 位置为第一次出现的位置，内容为最后一次出现的内容
+    assert_eq!(
+        html,
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\" /><meta name=\"description\" content=\"新闻\" />\
+         <script src=\"/b.js\"></script><title>新闻</title></head>\n<body><h1>新闻</h1></body></html>"
+    );
+}
+
+#[test]
+fn test_detect_component_cycles() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    write(
+        root,
+        "components/a.rsx",
+        "<script>\n    import B from './b.rsx';\n</script>\n<template><B></B></template>",
+    );
+    write(
+        root,
+        "components/b.rsx",
+        "<script>\n    import A from './a.rsx';\n</script>\n<template><A></A></template>",
+    );
+    let page = write(
+        root,
+        "pages/index.rsx",
+        "<script>\n    import A from '../components/a.rsx';\n</script>\n<template><A></A></template>",
+    );
+    let err = TemplateEngine::new(root)
+        .register_page("index", &page)
+        .unwrap_err();
+    assert!(
+        format!("{err:#}").contains(
+            "component cycle detected: components/a.rsx -> components/b.rsx -> components/a.rsx"
+        ),
+        "{err:#}"
+    );
+}
+
+#[test]
+fn test_render_client_only_islands() {
+    let mut engine = TemplateEngine::new(app_dir());
+    engine
+        .register_page("ssr", app_dir().join("src/pages/ssr.rsx"))
+        .unwrap();
+    let html = engine
+        .render(
+            "ssr",
+            &json!({"title": "ssr", "newsInfo": {"title": "<新闻>"}}),
+        )
+        .unwrap();
+    assert!(
+        html.contains(
+            "<rsx-island data-island=\"0\" data-component=\"NewsApp\" \
+             data-src=\"/src/react/news.app.tsx\" data-directive=\"only\" data-value=\"react\"></rsx-island>"
+        ),
+        "{html}"
+    );
+    assert!(
+        html.contains(
+            r#"<script id="__rsx_script__" type="application/json">{"__rsx_islands":{"0":{"newsInfo":{"title":"\u003c新闻\u003e"}}}}</script><script type="module">"#
+        ),
+        "{html}"
+    );
+    assert!(
+        html.trim_end().ends_with("</script></body>\n    </html>"),
+        "{html}"
+    );
+}
+
+#[test]
+fn test_render_islands_with_vite_manifest() {
+    let manifest = Manifest::from_json(
+        r#"{
+            "_react-Cx9a1B2c.js": {"file": "assets/react-Cx9a1B2c.js"},
+            "src/react/news.app.tsx": {
+                "file": "assets/news.app-BRBmoGS9.js",
+                "src": "src/react/news.app.tsx",
+                "isEntry": true,
+                "imports": ["_react-Cx9a1B2c.js"],
+                "css": ["assets/news.app-5UjPuW-k.css"]
+            }
+        }"#,
+    )
+    .unwrap();
+    let mut engine = TemplateEngine::new(app_dir()).with_manifest(manifest);
+    engine
+        .register_page("ssr", app_dir().join("src/pages/ssr.rsx"))
+        .unwrap();
+    let html = engine
+        .render(
+            "ssr",
+            &json!({"title": "ssr", "newsInfo": {"title": "新闻"}}),
+        )
+        .unwrap();
+    assert!(
+        html.contains("data-src=\"/assets/news.app-BRBmoGS9.js\""),
+        "{html}"
+    );
+    assert!(
+        html.contains(
+            "<link rel=\"stylesheet\" href=\"/assets/news.app-5UjPuW-k.css\">\
+             <link rel=\"modulepreload\" href=\"/assets/news.app-BRBmoGS9.js\">\
+             <link rel=\"modulepreload\" href=\"/assets/react-Cx9a1B2c.js\"></head>"
+        ),
+        "{html}"
+    );
+}
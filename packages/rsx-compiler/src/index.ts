@@ -175,4 +175,23 @@ export class RsxCompiler {
     async getPageHtml(page: RsxFileAst): Promise<string> {
         return page.template?.content || ''
     }
+
+    /**
+     * 页面和组件的`<script>`中导入的客户端组件，即非.rsx的相对路径导入，返回绝对路径
+     */
+    getClientComponents(): string[] {
+        const files = new Set<string>()
+        for (const file of [...this.astPages, ...this.astComponents]) {
+            for (const item of file.script?.ast?.body || []) {
+                if (item.type !== 'ImportDeclaration') {
+                    continue
+                }
+                const source = item.source.value
+                if (source.startsWith('.') && !source.endsWith('.rsx')) {
+                    files.add(path.resolve(file.dir, source))
+                }
+            }
+        }
+        return [...files]
+    }
 }
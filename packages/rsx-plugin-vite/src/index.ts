@@ -4,8 +4,39 @@ import { createLogger, type Plugin } from 'vite'
 
 const logger = createLogger('info')
 
+/**
+ * 渲染React岛组件的模块
+ */
+export const ISLAND_RENDERER = 'rsx/react'
+
+/**
+ * 为只有默认导出的React岛组件生成`mount(el, props, hydrate)`，rsx的岛加载脚本通过它渲染组件；
+ * 已导出`mount`或没有默认导出时返回null
+ */
+export function islandMount(code: string): string | null {
+    if (/export\s+(async\s+)?function\s+mount\b|export\s+(const|let|var)\s+mount\b/.test(code)) {
+        return null
+    }
+    const named = code.match(/export\s+default\s+(?:(?:async\s+)?function\*?|class)\s+([A-Za-z_$][\w$]*)/)
+    if (!named && !/export\s+default\s/.test(code)) {
+        return null
+    }
+    // 具名的函数和类保留声明，其他默认导出先赋值给变量
+    const component = named ? named[1] : '__rsx_component'
+    const body = code.replace(/export\s+default\s+/, named ? '' : 'const __rsx_component = ')
+    return `${body}
+export default ${component}
+import { render as __rsx_render } from '${ISLAND_RENDERER}'
+export function mount(el, props, hydrate) {
+    __rsx_render(${component}, props, el, hydrate)
+}
+`
+}
+
 export const RsxPlugin: () => Plugin = () => {
     let ctx: RsxCompilerContext, compiler: RsxCompiler
+    // .rsx中导入的客户端组件
+    let islands = new Set<string>()
     return {
         name: 'rsx',
         enforce: 'pre',
@@ -21,6 +52,17 @@ export const RsxPlugin: () => Plugin = () => {
             compiler = new RsxCompiler(ctx)
             logger.info(`rsx plugin config resolved: ${JSON.stringify(config)}`)
         },
+        buildStart: async () => {
+            await compiler.initAst()
+            islands = new Set(compiler.getClientComponents())
+        },
+        transform(code, id) {
+            const file = id.split('?')[0]
+            if (!islands.has(file) || !/\.[jt]sx$/.test(file)) {
+                return null
+            }
+            return islandMount(code)
+        },
         configureServer(server) {
             server.middlewares.use(async (req, res, next) => {
                 const uri = req.url || ''
@@ -1,5 +1,64 @@
-import { test } from 'vitest'
+import * as fs from 'node:fs/promises'
+import * as path from 'node:path'
+import { RsxCompiler, RsxCompilerContext } from 'rsx-compiler'
+import { transformWithEsbuild } from 'vite'
+import { afterAll, expect, test, vi } from 'vitest'
+import { ISLAND_RENDERER, islandMount } from '../src/index'
 
-test('test', () => {
-    console.log('rsx-plugin-vite test')
+const app = path.resolve(__dirname, '../../../app')
+const tmp = path.join(__dirname, '.tmp')
+const renderer = path.resolve(__dirname, '../../rsx/src/react.ts')
+
+const { createRoot, hydrateRoot, render } = vi.hoisted(() => {
+    const render = vi.fn()
+    return { render, createRoot: vi.fn(() => ({ render })), hydrateRoot: vi.fn() }
+})
+vi.mock('react-dom/client', () => ({ createRoot, hydrateRoot }))
+
+afterAll(() => fs.rm(tmp, { recursive: true, force: true }))
+
+test('find client components of the app', async () => {
+    const compiler = new RsxCompiler(new RsxCompilerContext({ root: app }))
+    await compiler.initAst()
+    expect(compiler.getClientComponents().sort()).toEqual(
+        ['csr', 'index', 'news'].map((name) => path.join(app, `src/react/${name}.app.tsx`))
+    )
+})
+
+test('generate mount for default exports', () => {
+    expect(islandMount('export function mount() {}\nexport default function A() {}')).toBeNull()
+    expect(islandMount('export const a = 1')).toBeNull()
+    const code = islandMount('const A = () => null\nexport default A')
+    expect(code).toContain('const __rsx_component = A')
+    expect(code).toContain('__rsx_render(__rsx_component, props, el, hydrate)')
+})
+
+test('mount app islands with react', async () => {
+    await fs.mkdir(tmp, { recursive: true })
+    for (const name of ['csr', 'index', 'news']) {
+        const source = await fs.readFile(path.join(app, `src/react/${name}.app.tsx`), 'utf8')
+        const wrapped = islandMount(source)
+        expect(wrapped).not.toBeNull()
+        // 测试中直接使用rsx包中的渲染模块
+        const { code } = await transformWithEsbuild(
+            (wrapped as string).replace(`'${ISLAND_RENDERER}'`, JSON.stringify(renderer)),
+            `${name}.app.tsx`,
+            { jsx: 'automatic' }
+        )
+        const file = path.join(tmp, `${name}.app.mjs`)
+        await fs.writeFile(file, code)
+        const module = await import(file)
+        expect(typeof module.default).toBe('function')
+
+        // 与岛加载脚本一致：client:only时hydrate为false，占位元素为空时同样在客户端渲染
+        const el = { hasChildNodes: () => false }
+        module.mount(el, { title: name }, false)
+        expect(createRoot).toHaveBeenLastCalledWith(el)
+        expect(render.mock.lastCall?.[0].type).toBe(module.default)
+        expect(render.mock.lastCall?.[0].props).toEqual({ title: name })
+
+        const rendered = { hasChildNodes: () => true }
+        module.mount(rendered, {}, true)
+        expect(hydrateRoot.mock.lastCall?.[0]).toBe(rendered)
+    }
 })
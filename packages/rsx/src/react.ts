@@ -0,0 +1,17 @@
+import { type ComponentType, createElement } from 'react'
+import { createRoot, hydrateRoot } from 'react-dom/client'
+
+/**
+ * 渲染React岛组件，由rsx-plugin-vite生成的`mount`调用
+ *
+ * 占位元素中已有服务端渲染的内容且允许hydrate时使用`hydrateRoot`，否则使用`createRoot`；
+ * `client:only`的岛`hydrate`为false
+ */
+export function render<P extends object>(Component: ComponentType<P>, props: P, el: Element, hydrate: boolean) {
+    const element = createElement(Component, props)
+    if (hydrate && el.hasChildNodes()) {
+        hydrateRoot(el, element)
+    } else {
+        createRoot(el).render(element)
+    }
+}
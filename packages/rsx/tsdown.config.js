@@ -10,7 +10,7 @@ const external = [...dependencies, ...peerDependencies, ...NODEJS_BUILDIN_MODULE
 
 export default [
     defineConfig({
-        entry: 'src/index.ts',
+        entry: ['src/index.ts', 'src/react.ts'],
         platform: 'node',
         format: 'esm',
         outDir: 'dist',